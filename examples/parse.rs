@@ -1,5 +1,7 @@
 extern crate mp4;
 
 fn main (){
-	mp4::parse_file("test.mp4");
-}
\ No newline at end of file
+	if let Err(err) = mp4::parse_file("test.mp4") {
+		println!("{}", err);
+	}
+}
@@ -0,0 +1,171 @@
+/*
+
+AV1 Codec ISO Media File Format Binding 2.3.3 Syntax
+
+aligned (8) class AV1CodecConfigurationRecord {
+    unsigned int (1) marker = 1;
+    unsigned int (7) version = 1;
+    unsigned int (3) seq_profile;
+    unsigned int (5) seq_level_idx_0;
+    unsigned int (1) seq_tier_0;
+    unsigned int (1) high_bitdepth;
+    unsigned int (1) twelve_bit;
+    unsigned int (1) monochrome;
+    unsigned int (1) chroma_subsampling_x;
+    unsigned int (1) chroma_subsampling_y;
+    unsigned int (2) chroma_sample_position;
+    unsigned int (3) reserved = 0;
+
+    unsigned int (1) initial_presentation_delay_present;
+    if (initial_presentation_delay_present) {
+        unsigned int (4) initial_presentation_delay_minus_one;
+    } else {
+        unsigned int (4) reserved = 0;
+    }
+
+    unsigned int (8)[] configOBUs;
+}
+
+class AV1CodecConfigurationBox extends Box('av1C') {
+    AV1CodecConfigurationRecord av1Config;
+}
+
+The fields mirror the sequence header OBU, which is normally the first of `configOBUs`.
+
+**/
+
+use super::{Mp4File, Kind, Header, Atom, Mp4Error};
+use codec::av1::SequenceHeader;
+use std::io::{Read, Seek, Write};
+use byteorder::{BigEndian, WriteBytesExt};
+
+#[derive(Debug, Clone)]
+pub struct Av1c {
+    header: Header,
+    marker : bool,
+    version: u8,
+    seq_profile    : u8,
+    seq_level_idx_0: u8,
+    seq_tier_0     : u8,
+    high_bitdepth  : bool,
+    twelve_bit     : bool,
+    monochrome     : bool,
+    chroma_subsampling_x  : bool,
+    chroma_subsampling_y  : bool,
+    chroma_sample_position: u8,
+    initial_presentation_delay_present  : bool,
+    initial_presentation_delay_minus_one: u8,
+    config_obus: Vec<u8>
+}
+
+impl Av1c {
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, header: Header) -> Result<Self, Mp4Error>{
+        if header.data_size < 4 {
+            return Err(header.invalid_size());
+        }
+        let byte = f.read_u8()?;
+        let marker = byte >> 7 == 1;
+        let version = byte & 0x7F;
+        let byte = f.read_u8()?;
+        let seq_profile = byte >> 5;
+        let seq_level_idx_0 = byte & 0x1F;
+        let byte = f.read_u8()?;
+        let seq_tier_0 = byte >> 7;
+        let high_bitdepth = (byte >> 6) & 1 == 1;
+        let twelve_bit = (byte >> 5) & 1 == 1;
+        let monochrome = (byte >> 4) & 1 == 1;
+        let chroma_subsampling_x = (byte >> 3) & 1 == 1;
+        let chroma_subsampling_y = (byte >> 2) & 1 == 1;
+        let chroma_sample_position = byte & 0x03;
+        let byte = f.read_u8()?;
+        let initial_presentation_delay_present = (byte >> 4) & 1 == 1;
+        let initial_presentation_delay_minus_one = byte & 0x0F;
+        let config_obus = f.read_bytes(header.data_size - 4)?;
+
+        f.offset_inc(header.data_size);
+        Ok(Av1c{
+            header,
+            marker,
+            version,
+            seq_profile,
+            seq_level_idx_0,
+            seq_tier_0,
+            high_bitdepth,
+            twelve_bit,
+            monochrome,
+            chroma_subsampling_x,
+            chroma_subsampling_y,
+            chroma_sample_position,
+            initial_presentation_delay_present,
+            initial_presentation_delay_minus_one,
+            config_obus
+        })
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        let mut data: Vec<u8> = Vec::new();
+        data.write_u8((self.marker as u8) << 7 | self.version)?;
+        data.write_u8(self.seq_profile << 5 | self.seq_level_idx_0)?;
+        data.write_u8(self.seq_tier_0 << 7 | (self.high_bitdepth as u8) << 6 | (self.twelve_bit as u8) << 5
+                      | (self.monochrome as u8) << 4 | (self.chroma_subsampling_x as u8) << 3
+                      | (self.chroma_subsampling_y as u8) << 2 | self.chroma_sample_position)?;
+        data.write_u8((self.initial_presentation_delay_present as u8) << 4 | self.initial_presentation_delay_minus_one)?;
+        data.write_all(&self.config_obus)?;
+        self.header.write(w, &data)
+    }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+    pub fn seq_profile(&self) -> u8 {
+        self.seq_profile
+    }
+    pub fn seq_level_idx_0(&self) -> u8 {
+        self.seq_level_idx_0
+    }
+    pub fn seq_tier_0(&self) -> u8 {
+        self.seq_tier_0
+    }
+    pub fn bit_depth(&self) -> u8 {
+        match (self.high_bitdepth, self.twelve_bit) {
+            (true, true)  => 12,
+            (true, false) => 10,
+            _ => 8
+        }
+    }
+    pub fn monochrome(&self) -> bool {
+        self.monochrome
+    }
+    pub fn chroma_subsampling_x(&self) -> bool {
+        self.chroma_subsampling_x
+    }
+    pub fn chroma_subsampling_y(&self) -> bool {
+        self.chroma_subsampling_y
+    }
+    pub fn chroma_sample_position(&self) -> u8 {
+        self.chroma_sample_position
+    }
+    pub fn initial_presentation_delay(&self) -> Option<u8> {
+        if self.initial_presentation_delay_present {
+            Some(self.initial_presentation_delay_minus_one + 1)
+        } else {
+            None
+        }
+    }
+    pub fn config_obus(&self) -> &[u8] {
+        &self.config_obus
+    }
+    // Short form codec string `av01.P.LLT.DD`, e.g. `av01.0.08M.10`.
+    pub fn codec_string(&self) -> String {
+        let tier = if self.seq_tier_0 == 1 { "H" } else { "M" };
+        format!("av01.{}.{:02}{}.{:02}", self.seq_profile, self.seq_level_idx_0, tier, self.bit_depth())
+    }
+    // Decode the sequence header OBU carried in `configOBUs`.
+    pub fn sequence_header(&self) -> Result<SequenceHeader, Mp4Error> {
+        match SequenceHeader::from_obus(&self.config_obus)? {
+            Some(sequence_header) => Ok(sequence_header),
+            None => Err(self.header.invalid_value("av1C has no sequence header OBU"))
+        }
+    }
+}
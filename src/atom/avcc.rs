@@ -0,0 +1,204 @@
+/*
+
+ISO/IEC 14496-15 5.3.3.1.2 Syntax
+
+aligned(8) class AVCDecoderConfigurationRecord {
+    unsigned int(8) configurationVersion = 1;
+    unsigned int(8) AVCProfileIndication;
+    unsigned int(8) profile_compatibility;
+    unsigned int(8) AVCLevelIndication;
+    bit(6) reserved = ‘111111’b;
+    unsigned int(2) lengthSizeMinusOne;
+    bit(3) reserved = ‘111’b;
+    unsigned int(5) numOfSequenceParameterSets;
+    for (i=0; i< numOfSequenceParameterSets; i++) {
+        unsigned int(16) sequenceParameterSetLength ;
+        bit(8*sequenceParameterSetLength) sequenceParameterSetNALUnit;
+    }
+    unsigned int(8) numOfPictureParameterSets;
+    for (i=0; i< numOfPictureParameterSets; i++) {
+        unsigned int(16) pictureParameterSetLength;
+        bit(8*pictureParameterSetLength) pictureParameterSetNALUnit;
+    }
+    if( profile_idc == 100 || profile_idc == 110 ||
+        profile_idc == 122 || profile_idc == 144 )
+    {
+        bit(6) reserved = ‘111111’b;
+        unsigned int(2) chroma_format;
+        bit(5) reserved = ‘11111’b;
+        unsigned int(3) bit_depth_luma_minus8;
+        bit(5) reserved = ‘11111’b;
+        unsigned int(3) bit_depth_chroma_minus8;
+        unsigned int(8) numOfSequenceParameterSetExt;
+        for (i=0; i< numOfSequenceParameterSetExt; i++) {
+            unsigned int(16) sequenceParameterSetExtLength;
+            bit(8*sequenceParameterSetExtLength) sequenceParameterSetExtNALUnit;
+        }
+    }
+}
+
+class AVCConfigurationBox extends Box(‘avcC’) {
+    AVCDecoderConfigurationRecord() AVCConfig;
+}
+
+`lengthSizeMinusOne` indicates the length in bytes of the NALUnitLength field in an AVC video
+    sample or AVC parameter set sample of the associated stream minus one.
+
+Many writers omit the high profile extension; it is only read when bytes remain.
+The record is kept as it is and decoded on request, so a malformed one does not
+stop the parse of the file.
+
+**/
+
+use super::{Mp4File, Kind, Header, Atom, Mp4Error};
+use codec::h264::{Sps, Pps};
+use std::io::{Read, Seek, Write};
+
+#[derive(Debug, Clone)]
+pub struct AvccExt {
+    pub chroma_format   : u8,
+    pub bit_depth_luma  : u8,
+    pub bit_depth_chroma: u8,
+    pub sequence_parameter_set_exts: Vec<Vec<u8>>
+}
+
+#[derive(Debug, Clone)]
+pub struct AvcConfig {
+    pub configuration_version: u8,
+    pub profile_indication   : u8,
+    pub profile_compatibility: u8,
+    pub level_indication     : u8,
+    pub length_size_minus_one: u8,
+    pub sequence_parameter_sets: Vec<Vec<u8>>,
+    pub picture_parameter_sets : Vec<Vec<u8>>,
+    pub ext: Option<AvccExt>
+}
+
+impl AvcConfig {
+    // Size in bytes of the length prefix of each NAL unit in the samples.
+    pub fn nal_length_size(&self) -> u8 {
+        self.length_size_minus_one + 1
+    }
+}
+
+// Reads the record out of a byte slice.
+struct Record<'a> {
+    data: &'a [u8],
+    pos : usize
+}
+
+impl<'a> Record<'a> {
+    fn remaining(&self) -> usize {
+        self.data.len() - self.pos
+    }
+    fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], &'static str> {
+        if len > self.remaining() {
+            return Err("avcC record is truncated");
+        }
+        let bytes = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+    fn read_u8(&mut self) -> Result<u8, &'static str> {
+        Ok(self.read_bytes(1)?[0])
+    }
+    // Read `count` parameter sets, each prefixed by its u16 length.
+    fn read_parameter_sets(&mut self, count: u8) -> Result<Vec<Vec<u8>>, &'static str> {
+        let mut sets = Vec::new();
+        for _ in 0..count {
+            let len = (self.read_u8()? as usize) << 8 | self.read_u8()? as usize;
+            sets.push(self.read_bytes(len)?.to_vec());
+        }
+        Ok(sets)
+    }
+}
+
+fn parse_config(data: &[u8]) -> Result<AvcConfig, &'static str> {
+    let mut r = Record { data, pos: 0 };
+    let configuration_version = r.read_u8()?;
+    let profile_indication = r.read_u8()?;
+    let profile_compatibility = r.read_u8()?;
+    let level_indication = r.read_u8()?;
+    let length_size_minus_one = r.read_u8()? & 0x03;
+    let sps_count = r.read_u8()? & 0x1F;
+    let sequence_parameter_sets = r.read_parameter_sets(sps_count)?;
+    let pps_count = r.read_u8()?;
+    let picture_parameter_sets = r.read_parameter_sets(pps_count)?;
+
+    let high_profile = matches!(profile_indication, 100 | 110 | 122 | 144);
+    let ext = if high_profile && r.remaining() >= 4 {
+        let chroma_format = r.read_u8()? & 0x03;
+        let bit_depth_luma = 8 + (r.read_u8()? & 0x07);
+        let bit_depth_chroma = 8 + (r.read_u8()? & 0x07);
+        let ext_count = r.read_u8()?;
+        Some(AvccExt {
+            chroma_format,
+            bit_depth_luma,
+            bit_depth_chroma,
+            sequence_parameter_set_exts: r.read_parameter_sets(ext_count)?
+        })
+    } else {
+        None
+    };
+    Ok(AvcConfig {
+        configuration_version,
+        profile_indication,
+        profile_compatibility,
+        level_indication,
+        length_size_minus_one,
+        sequence_parameter_sets,
+        picture_parameter_sets,
+        ext
+    })
+}
+
+#[derive(Debug, Clone)]
+pub struct Avcc {
+    header: Header,
+    data: Vec<u8>
+}
+
+impl Avcc {
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, header: Header) -> Result<Self, Mp4Error>{
+        let data = f.read_bytes(header.data_size)?;
+
+        f.offset_inc(header.data_size);
+        Ok(Avcc{
+            header,
+            data
+        })
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        self.header.write(w, &self.data)
+    }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+    // Decode the AVCDecoderConfigurationRecord.
+    pub fn config(&self) -> Result<AvcConfig, Mp4Error> {
+        parse_config(&self.data).map_err(|reason| self.header.invalid_value(reason))
+    }
+    // RFC 6381 codec string, e.g. `avc1.64001F`; `format` is the sample entry format.
+    pub fn codec_string(&self, format: &str) -> Result<String, Mp4Error> {
+        let config = self.config()?;
+        Ok(format!("{}.{:02X}{:02X}{:02X}", format, config.profile_indication,
+                   config.profile_compatibility, config.level_indication))
+    }
+    // Decode the first SPS.
+    pub fn sps(&self) -> Result<Sps, Mp4Error> {
+        match self.config()?.sequence_parameter_sets.first() {
+            Some(nal) => Sps::parse(nal),
+            None => Err(self.header.invalid_value("avcC has no sequence parameter set"))
+        }
+    }
+    // Decode the first PPS.
+    pub fn pps(&self) -> Result<Pps, Mp4Error> {
+        match self.config()?.picture_parameter_sets.first() {
+            Some(nal) => Pps::parse(nal),
+            None => Err(self.header.invalid_value("avcC has no picture parameter set"))
+        }
+    }
+}
@@ -0,0 +1,296 @@
+/*
+
+ISO/IEC 14496-14 5.6.1 Syntax
+
+aligned(8) class ESDBox extends FullBox(‘esds’, version = 0, 0) {
+    ES_Descriptor ES;
+}
+
+ISO/IEC 14496-1 7.2.2.2 / 8.3.3 Descriptor header
+
+abstract aligned(8) expandable(2^28-1) class BaseDescriptor : bit(8) tag=0 {
+    // the size is coded as 1 to 4 bytes of 7 bits, the high bit set on all but the last
+}
+
+class ES_Descriptor extends BaseDescriptor : bit(8) tag=ES_DescrTag (0x03) {
+    bit(16) ES_ID;
+    bit(1) streamDependenceFlag;
+    bit(1) URL_Flag;
+    bit(1) OCRstreamFlag;
+    bit(5) streamPriority;
+    if (streamDependenceFlag)
+        bit(16) dependsOn_ES_ID;
+    if (URL_Flag) {
+        bit(8) URLlength;
+        bit(8) URLstring[URLlength];
+    }
+    if (OCRstreamFlag)
+        bit(16) OCR_ES_Id;
+    DecoderConfigDescriptor decConfigDescr;
+    SLConfigDescriptor slConfigDescr;
+    ...
+}
+
+class DecoderConfigDescriptor extends BaseDescriptor : bit(8) tag=DecoderConfigDescrTag (0x04) {
+    bit(8) objectTypeIndication;
+    bit(6) streamType;
+    bit(1) upStream;
+    const bit(1) reserved=1;
+    bit(24) bufferSizeDB;
+    bit(32) maxBitrate;
+    bit(32) avgBitrate;
+    DecoderSpecificInfo decSpecificInfo[0 .. 1];              // tag 0x05
+    profileLevelIndicationIndexDescriptor profileLevelIndicationIndexDescr [0..255];
+}
+
+class SLConfigDescriptor extends BaseDescriptor : bit(8) tag=SLConfigDescrTag (0x06) {
+    bit(8) predefined;
+    ...
+}
+
+`objectTypeIndication`
+    0x40        MPEG-4 Audio (AAC, HE-AAC, ...), DecoderSpecificInfo is an AudioSpecificConfig
+    0x66..0x68  MPEG-2 AAC Main / LC / SSR
+    0x69        MPEG-2 Audio (Layer 1 / 2 / 3)
+    0x6B        MPEG-1 Audio (MP3)
+`streamType` is 0x05 for audio, 0x04 for visual.
+
+The descriptors are kept as they are, since writers pad the size fields differently;
+the decoded tree is a read-only view of them, decoded on request so that an
+unsupported version or a malformed descriptor does not stop the parse of the file.
+
+**/
+
+use super::{Mp4File, Kind, Header, Atom, Mp4Error};
+use codec::aac::{AudioSpecificConfig, AOT_SBR, AOT_PS};
+use std::io::{Read, Seek, Write};
+
+pub const OTI_MPEG4_AUDIO     : u8 = 0x40;
+pub const OTI_MPEG2_AAC_MAIN  : u8 = 0x66;
+pub const OTI_MPEG2_AAC_LC    : u8 = 0x67;
+pub const OTI_MPEG2_AAC_SSR   : u8 = 0x68;
+pub const OTI_MPEG2_AUDIO     : u8 = 0x69;
+pub const OTI_MPEG1_AUDIO     : u8 = 0x6B;
+
+const ES_DESCR_TAG           : u8 = 0x03;
+const DECODER_CONFIG_DESCR_TAG: u8 = 0x04;
+const DEC_SPECIFIC_INFO_TAG  : u8 = 0x05;
+const SL_CONFIG_DESCR_TAG    : u8 = 0x06;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EsDescriptor {
+    pub es_id          : u16,
+    pub stream_priority: u8,
+    pub depends_on_es_id: Option<u16>,
+    pub url             : Option<Vec<u8>>,
+    pub ocr_es_id       : Option<u16>,
+    pub decoder_config  : Option<DecoderConfigDescriptor>,
+    pub sl_config_predefined: Option<u8>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecoderConfigDescriptor {
+    pub object_type_indication: u8,
+    pub stream_type   : u8,
+    pub up_stream     : bool,
+    pub buffer_size_db: u32,
+    pub max_bitrate   : u32,
+    pub avg_bitrate   : u32,
+    pub decoder_specific_info: Option<Vec<u8>>
+}
+
+impl DecoderConfigDescriptor {
+    // MPEG-4 or MPEG-2 AAC.
+    pub fn is_aac(&self) -> bool {
+        matches!(self.object_type_indication,
+                 OTI_MPEG4_AUDIO | OTI_MPEG2_AAC_MAIN | OTI_MPEG2_AAC_LC | OTI_MPEG2_AAC_SSR)
+    }
+    // MPEG-1 or MPEG-2 audio, in practice Layer 3.
+    pub fn is_mp3(&self) -> bool {
+        self.object_type_indication == OTI_MPEG1_AUDIO || self.object_type_indication == OTI_MPEG2_AUDIO
+    }
+}
+
+// Reads descriptors out of a byte slice.
+struct Descriptors<'a> {
+    data: &'a [u8],
+    pos : usize
+}
+
+impl<'a> Descriptors<'a> {
+    fn new(data: &'a [u8]) -> Descriptors<'a> {
+        Descriptors { data, pos: 0 }
+    }
+    fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+    fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], &'static str> {
+        if len > self.data.len() - self.pos {
+            return Err("esds descriptor exceeds its parent");
+        }
+        let bytes = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+    fn read_uint(&mut self, len: usize) -> Result<u32, &'static str> {
+        Ok(self.read_bytes(len)?.iter().fold(0, |n, byte| n << 8 | *byte as u32))
+    }
+    // Tag and payload of the next descriptor.
+    fn read_descriptor(&mut self) -> Result<(u8, Descriptors<'a>), &'static str> {
+        let tag = self.read_uint(1)? as u8;
+        let mut size = 0usize;
+        for i in 0..4 {
+            let byte = self.read_uint(1)?;
+            size = size << 7 | (byte & 0x7F) as usize;
+            if byte & 0x80 == 0 {
+                break;
+            }
+            if i == 3 {
+                return Err("esds descriptor size is longer than 4 bytes");
+            }
+        }
+        Ok((tag, Descriptors::new(self.read_bytes(size)?)))
+    }
+}
+
+fn parse_es_descriptor(d: &mut Descriptors) -> Result<EsDescriptor, &'static str> {
+    let es_id = d.read_uint(2)? as u16;
+    let flags = d.read_uint(1)? as u8;
+    let depends_on_es_id = if flags & 0x80 != 0 { Some(d.read_uint(2)? as u16) } else { None };
+    let url = if flags & 0x40 != 0 {
+        let len = d.read_uint(1)? as usize;
+        Some(d.read_bytes(len)?.to_vec())
+    } else {
+        None
+    };
+    let ocr_es_id = if flags & 0x20 != 0 { Some(d.read_uint(2)? as u16) } else { None };
+
+    let mut decoder_config = None;
+    let mut sl_config_predefined = None;
+    while !d.is_empty() {
+        let (tag, mut payload) = d.read_descriptor()?;
+        match tag {
+            DECODER_CONFIG_DESCR_TAG => decoder_config = Some(parse_decoder_config(&mut payload)?),
+            SL_CONFIG_DESCR_TAG => sl_config_predefined = Some(payload.read_uint(1)? as u8),
+            _ => {}
+        }
+    }
+    Ok(EsDescriptor {
+        es_id,
+        stream_priority: flags & 0x1F,
+        depends_on_es_id,
+        url,
+        ocr_es_id,
+        decoder_config,
+        sl_config_predefined
+    })
+}
+
+fn parse_decoder_config(d: &mut Descriptors) -> Result<DecoderConfigDescriptor, &'static str> {
+    let object_type_indication = d.read_uint(1)? as u8;
+    let byte = d.read_uint(1)? as u8;
+    let buffer_size_db = d.read_uint(3)?;
+    let max_bitrate = d.read_uint(4)?;
+    let avg_bitrate = d.read_uint(4)?;
+    let mut decoder_specific_info = None;
+    while !d.is_empty() {
+        let (tag, payload) = d.read_descriptor()?;
+        if tag == DEC_SPECIFIC_INFO_TAG {
+            decoder_specific_info = Some(payload.data.to_vec());
+        }
+    }
+    Ok(DecoderConfigDescriptor {
+        object_type_indication,
+        stream_type   : byte >> 2,
+        up_stream     : (byte >> 1) & 1 == 1,
+        buffer_size_db,
+        max_bitrate,
+        avg_bitrate,
+        decoder_specific_info
+    })
+}
+
+#[derive(Debug, Clone)]
+pub struct Esds {
+    header: Header,
+    data: Vec<u8>
+}
+
+impl Esds {
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, mut header: Header) -> Result<Self, Mp4Error>{
+        header.parse_version(f)?;
+        header.parse_flags(f)?;
+        let data = f.read_bytes(header.data_size)?;
+
+        f.offset_inc(header.data_size);
+        Ok(Esds{
+            header,
+            data
+        })
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        self.header.write(w, &self.data)
+    }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+    // Decode the ES_Descriptor.
+    pub fn es_descriptor(&self) -> Result<EsDescriptor, Mp4Error> {
+        self.header.check_version(0)?;
+        let mut d = Descriptors::new(&self.data);
+        let es_descriptor = match d.read_descriptor() {
+            Ok((ES_DESCR_TAG, mut payload)) => parse_es_descriptor(&mut payload),
+            Ok(_) => Err("esds does not start with an ES_Descriptor"),
+            Err(reason) => Err(reason)
+        };
+        es_descriptor.map_err(|reason| self.header.invalid_value(reason))
+    }
+    pub fn decoder_config(&self) -> Result<DecoderConfigDescriptor, Mp4Error> {
+        match self.es_descriptor()?.decoder_config {
+            Some(config) => Ok(config),
+            None => Err(self.header.invalid_value("esds has no DecoderConfigDescriptor"))
+        }
+    }
+    pub fn object_type_indication(&self) -> Result<u8, Mp4Error> {
+        self.decoder_config().map(|config| config.object_type_indication)
+    }
+    // False as well when the descriptors cannot be decoded.
+    pub fn is_aac(&self) -> bool {
+        self.decoder_config().is_ok_and(|config| config.is_aac())
+    }
+    pub fn is_mp3(&self) -> bool {
+        self.decoder_config().is_ok_and(|config| config.is_mp3())
+    }
+    // RFC 6381 codec string: `mp4a.40.<audio object type>` for MPEG-4 audio, `mp4a.<OTI>` otherwise.
+    // HE-AAC is reported as object type 5 and HE-AAC v2 as 29 however SBR is signalled.
+    pub fn codec_string(&self) -> Result<String, Mp4Error> {
+        match self.object_type_indication()? {
+            OTI_MPEG4_AUDIO => {
+                let config = self.audio_specific_config()?;
+                let audio_object_type = if config.is_he_aac_v2() {
+                    AOT_PS
+                } else if config.is_he_aac() {
+                    AOT_SBR
+                } else {
+                    config.audio_object_type
+                };
+                Ok(format!("mp4a.40.{}", audio_object_type))
+            },
+            object_type_indication => Ok(format!("mp4a.{:02X}", object_type_indication))
+        }
+    }
+    // Decode the DecoderSpecificInfo of an AAC stream.
+    pub fn audio_specific_config(&self) -> Result<AudioSpecificConfig, Mp4Error> {
+        let config = self.decoder_config()?;
+        if !config.is_aac() {
+            return Err(self.header.invalid_value("esds does not describe an AAC stream"));
+        }
+        match config.decoder_specific_info {
+            Some(ref info) => AudioSpecificConfig::parse(info),
+            None => Err(self.header.invalid_value("esds has no DecoderSpecificInfo"))
+        }
+    }
+}
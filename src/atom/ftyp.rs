@@ -2,7 +2,7 @@
 // Complete List of all known MP4 / QuickTime 'ftyp' designations:
 //  http://www.fileType.com
 
-/**
+/*
 avc1    MP4 Base w/ AVC ext [ISO 14496-12:2005]         ISO     YES video/mp4   [11]
 iso2    MP4 Base Media v2 [ISO 14496-12:2005]           ISO     YES video/mp4   [6]
 isom    MP4  Base Media v1 [IS0 14496-12:2003]          ISO     YES video/mp4   [5]
@@ -35,10 +35,12 @@ For example, "qt" is really "qt  " - note the two trailing spaces
 
 use std::str;
 use std::str::FromStr;
-use std::string::ToString;
+use std::fmt;
 use std::fs::File;
 
-use super::{Mp4File, Kind, Header, Atom};
+use super::{Mp4File, Kind, Header, Atom, Mp4Error};
+use std::io::{Read, Seek, Write};
+use byteorder::{BigEndian, WriteBytesExt};
 
 #[derive(Debug, Clone)]
 pub enum FileType{
@@ -63,7 +65,9 @@ pub enum FileType{
     F4A,
     F4B,
     // 3GPP/GSM
-    mmp4
+    mmp4,
+    // Any other brand, kept as read
+    Other([u8; 4])
 }
 
 impl FromStr for FileType {
@@ -93,9 +97,9 @@ impl FromStr for FileType {
     }
 }
 
-impl ToString for FileType {
-    fn to_string(&self) -> String {
-        match *self {
+impl fmt::Display for FileType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
             FileType::avc1 => "avc1".to_owned(),
             FileType::iso2 => "iso2".to_owned(),
             FileType::isom => "isom".to_owned(),
@@ -113,24 +117,25 @@ impl ToString for FileType {
             FileType::F4P  => "F4P\u{0}".to_owned(),
             FileType::F4A  => "F4A\u{0}".to_owned(),
             FileType::F4B  => "F4B\u{0}".to_owned(),
-            FileType::mmp4 => "mmp4".to_owned()
-        }
+            FileType::mmp4 => "mmp4".to_owned(),
+            FileType::Other(ref bytes) => String::from_utf8_lossy(bytes).into_owned()
+        };
+        f.write_str(&name)
     }
 }
 
 impl FileType {
     pub fn from_bytes(bytes: &[u8; 4]) -> Result<Self, &'static str> {
-        let kind_str = match str::from_utf8(bytes) {
-            Ok(s)  => s,
-            Err(_) => {
-                println!("ftyp ({:?}) parse error.", bytes);
-                return Err("ftyp parse error.");
-            }
-        };
-        FileType::from_str(kind_str)
+        match str::from_utf8(bytes).ok().and_then(|s| FileType::from_str(s).ok()) {
+            Some(file_type) => Ok(file_type),
+            None => Ok(FileType::Other(*bytes))
+        }
     }
     pub fn into_bytes(&self) -> Vec<u8> {
-        self.to_string().into_bytes()
+        match *self {
+            FileType::Other(ref bytes) => bytes.to_vec(),
+            _ => self.to_string().into_bytes()
+        }
     }
 }
 
@@ -205,28 +210,60 @@ pub struct Ftyp {
 }
 
 impl Ftyp {
-    fn parse_filetype(f: &mut Mp4File) -> Result<FileType, &'static str>{
+    pub fn new(major_brand: FileType, minor_version: u32, compatible_brands: Vec<FileType>) -> Ftyp {
+        Ftyp{
+            header: Header::new(Kind::ftyp),
+            major_brand,
+            minor_version,
+            compatible_brands
+        }
+    }
+    fn parse_filetype<R: Read + Seek>(f: &mut Mp4File<R>, header: &Header) -> Result<FileType, Mp4Error>{
         let ft_bytes: [u8; 4] = [
-            f.read_u8().unwrap(), f.read_u8().unwrap(),
-            f.read_u8().unwrap(), f.read_u8().unwrap()
+            f.read_u8()?, f.read_u8()?,
+            f.read_u8()?, f.read_u8()?
         ];
-        FileType::from_bytes(&ft_bytes)
+        FileType::from_bytes(&ft_bytes).map_err(|reason| header.invalid_value(reason))
     }
-    pub fn parse(f: &mut Mp4File, header: Header) -> Result<Self, &'static str>{
-        let major_brand = Ftyp::parse_filetype(f).unwrap();
-        let minor_version = f.read_u32().unwrap();
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, header: Header) -> Result<Self, Mp4Error>{
+        if header.data_size < 8 {
+            return Err(header.invalid_size());
+        }
+        let major_brand = Ftyp::parse_filetype(f, &header)?;
+        let minor_version = f.read_u32()?;
         let mut compatible_brands: Vec<FileType> = Vec::new();
         let mut idx = (header.data_size - 8) / 4;
         while idx > 0 {
-            compatible_brands.push(Ftyp::parse_filetype(f).unwrap());
+            compatible_brands.push(Ftyp::parse_filetype(f, &header)?);
             idx -= 1;
         }
         f.offset_inc(header.data_size);
         Ok(Ftyp{
-            header: header,
-            major_brand: major_brand,
-            minor_version: minor_version,
-            compatible_brands: compatible_brands
+            header,
+            major_brand,
+            minor_version,
+            compatible_brands
         })
     }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        let mut data: Vec<u8> = Vec::new();
+        data.write_all(&self.major_brand.into_bytes())?;
+        data.write_u32::<BigEndian>(self.minor_version)?;
+        for brand in &self.compatible_brands {
+            data.write_all(&brand.into_bytes())?;
+        }
+        self.header.write(w, &data)
+    }
+    pub fn major_brand(&self) -> &FileType {
+        &self.major_brand
+    }
+    pub fn minor_version(&self) -> u32 {
+        self.minor_version
+    }
+    pub fn compatible_brands(&self) -> &Vec<FileType> {
+        &self.compatible_brands
+    }
 }
@@ -0,0 +1,293 @@
+/*
+
+ISO/IEC 14496-15 8.3.3.1.2 Syntax
+
+aligned(8) class HEVCDecoderConfigurationRecord {
+    unsigned int(8) configurationVersion = 1;
+    unsigned int(2) general_profile_space;
+    unsigned int(1) general_tier_flag;
+    unsigned int(5) general_profile_idc;
+    unsigned int(32) general_profile_compatibility_flags;
+    unsigned int(48) general_constraint_indicator_flags;
+    unsigned int(8) general_level_idc;
+    bit(4) reserved = ‘1111’b;
+    unsigned int(12) min_spatial_segmentation_idc;
+    bit(6) reserved = ‘111111’b;
+    unsigned int(2) parallelismType;
+    bit(6) reserved = ‘111111’b;
+    unsigned int(2) chromaFormat;
+    bit(5) reserved = ‘11111’b;
+    unsigned int(3) bitDepthLumaMinus8;
+    bit(5) reserved = ‘11111’b;
+    unsigned int(3) bitDepthChromaMinus8;
+    bit(16) avgFrameRate;
+    bit(2) constantFrameRate;
+    bit(3) numTemporalLayers;
+    bit(1) temporalIdNested;
+    unsigned int(2) lengthSizeMinusOne;
+    unsigned int(8) numOfArrays;
+    for (j=0; j < numOfArrays; j++) {
+        bit(1) array_completeness;
+        unsigned int(1) reserved = 0;
+        unsigned int(6) NAL_unit_type;
+        unsigned int(16) numNalus;
+        for (i=0; i< numNalus; i++) {
+            unsigned int(16) nalUnitLength;
+            bit(8*nalUnitLength) nalUnit;
+        }
+    }
+}
+
+class HEVCConfigurationBox extends Box(‘hvcC’) {
+    HEVCDecoderConfigurationRecord() HEVCConfig;
+}
+
+`avgFrameRate` gives the average frame rate in units of frames/(256 seconds), 0 if unspecified.
+`array_completeness` when equal to 1 indicates that all NAL units of the given type are in the
+    following array and none are in the stream.
+
+Bytes after the record are kept as they are.
+
+**/
+
+use super::{Mp4File, Kind, Header, Atom, Mp4Error};
+use codec::h265::{Vps, Sps, NAL_UNIT_TYPE_VPS, NAL_UNIT_TYPE_SPS};
+use std::io::{Read, Seek, Write};
+use byteorder::{BigEndian, WriteBytesExt};
+
+// Bytes of the fields before the NAL unit arrays.
+const FIELDS_SIZE: u64 = 23;
+
+#[derive(Debug, Clone)]
+pub struct HvccArray {
+    pub array_completeness: bool,
+    pub nal_unit_type: u8,
+    pub nal_units    : Vec<Vec<u8>>
+}
+
+#[derive(Debug, Clone)]
+pub struct Hvcc {
+    header: Header,
+    configuration_version: u8,
+    general_profile_space: u8,
+    general_tier_flag    : bool,
+    general_profile_idc  : u8,
+    general_profile_compatibility_flags: u32,
+    general_constraint_indicator_flags : u64,
+    general_level_idc    : u8,
+    min_spatial_segmentation_idc: u16,
+    parallelism_type     : u8,
+    chroma_format        : u8,
+    bit_depth_luma       : u8,
+    bit_depth_chroma     : u8,
+    avg_frame_rate       : u16,
+    constant_frame_rate  : u8,
+    num_temporal_layers  : u8,
+    temporal_id_nested   : bool,
+    length_size_minus_one: u8,
+    arrays  : Vec<HvccArray>,
+    trailing: Vec<u8>
+}
+
+impl Hvcc {
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, header: Header) -> Result<Self, Mp4Error>{
+        if header.data_size < FIELDS_SIZE {
+            return Err(header.invalid_size());
+        }
+        let configuration_version = f.read_u8()?;
+        let byte = f.read_u8()?;
+        let general_profile_space = byte >> 6;
+        let general_tier_flag = (byte >> 5) & 1 == 1;
+        let general_profile_idc = byte & 0x1F;
+        let general_profile_compatibility_flags = f.read_u32()?;
+        let general_constraint_indicator_flags = (f.read_u32()? as u64) << 16 | f.read_u16()? as u64;
+        let general_level_idc = f.read_u8()?;
+        let min_spatial_segmentation_idc = f.read_u16()? & 0x0FFF;
+        let parallelism_type = f.read_u8()? & 0x03;
+        let chroma_format = f.read_u8()? & 0x03;
+        let bit_depth_luma = 8 + (f.read_u8()? & 0x07);
+        let bit_depth_chroma = 8 + (f.read_u8()? & 0x07);
+        let avg_frame_rate = f.read_u16()?;
+        let byte = f.read_u8()?;
+        let constant_frame_rate = byte >> 6;
+        let num_temporal_layers = (byte >> 3) & 0x07;
+        let temporal_id_nested = (byte >> 2) & 1 == 1;
+        let length_size_minus_one = byte & 0x03;
+
+        let num_of_arrays = f.read_u8()?;
+        let mut consumed = FIELDS_SIZE;
+        let mut arrays: Vec<HvccArray> = Vec::new();
+        for _ in 0..num_of_arrays {
+            let byte = f.read_u8()?;
+            let num_nalus = f.read_u16()?;
+            consumed += 3;
+            let mut nal_units = Vec::new();
+            for _ in 0..num_nalus {
+                let len = f.read_u16()? as u64;
+                nal_units.push(f.read_bytes(len)?);
+                consumed += 2 + len;
+            }
+            arrays.push(HvccArray {
+                array_completeness: byte >> 7 == 1,
+                nal_unit_type: byte & 0x3F,
+                nal_units
+            });
+        }
+
+        if consumed > header.data_size {
+            return Err(header.invalid_size());
+        }
+        let trailing = f.read_bytes(header.data_size - consumed)?;
+
+        f.offset_inc(header.data_size);
+        Ok(Hvcc{
+            header,
+            configuration_version,
+            general_profile_space,
+            general_tier_flag,
+            general_profile_idc,
+            general_profile_compatibility_flags,
+            general_constraint_indicator_flags,
+            general_level_idc,
+            min_spatial_segmentation_idc,
+            parallelism_type,
+            chroma_format,
+            bit_depth_luma,
+            bit_depth_chroma,
+            avg_frame_rate,
+            constant_frame_rate,
+            num_temporal_layers,
+            temporal_id_nested,
+            length_size_minus_one,
+            arrays,
+            trailing
+        })
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        let mut data: Vec<u8> = Vec::new();
+        data.write_u8(self.configuration_version)?;
+        data.write_u8(self.general_profile_space << 6 | (self.general_tier_flag as u8) << 5 | self.general_profile_idc)?;
+        data.write_u32::<BigEndian>(self.general_profile_compatibility_flags)?;
+        data.write_u32::<BigEndian>((self.general_constraint_indicator_flags >> 16) as u32)?;
+        data.write_u16::<BigEndian>(self.general_constraint_indicator_flags as u16)?;
+        data.write_u8(self.general_level_idc)?;
+        data.write_u16::<BigEndian>(0xF000 | self.min_spatial_segmentation_idc)?;
+        data.write_u8(0xFC | self.parallelism_type)?;
+        data.write_u8(0xFC | self.chroma_format)?;
+        data.write_u8(0xF8 | (self.bit_depth_luma - 8))?;
+        data.write_u8(0xF8 | (self.bit_depth_chroma - 8))?;
+        data.write_u16::<BigEndian>(self.avg_frame_rate)?;
+        data.write_u8(self.constant_frame_rate << 6 | self.num_temporal_layers << 3
+                      | (self.temporal_id_nested as u8) << 2 | self.length_size_minus_one)?;
+        data.write_u8(self.arrays.len() as u8)?;
+        for array in &self.arrays {
+            data.write_u8((array.array_completeness as u8) << 7 | array.nal_unit_type)?;
+            data.write_u16::<BigEndian>(array.nal_units.len() as u16)?;
+            for nal in &array.nal_units {
+                data.write_u16::<BigEndian>(nal.len() as u16)?;
+                data.write_all(nal)?;
+            }
+        }
+        data.write_all(&self.trailing)?;
+        self.header.write(w, &data)
+    }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn configuration_version(&self) -> u8 {
+        self.configuration_version
+    }
+    pub fn general_profile_space(&self) -> u8 {
+        self.general_profile_space
+    }
+    pub fn general_tier_flag(&self) -> bool {
+        self.general_tier_flag
+    }
+    pub fn general_profile_idc(&self) -> u8 {
+        self.general_profile_idc
+    }
+    pub fn general_profile_compatibility_flags(&self) -> u32 {
+        self.general_profile_compatibility_flags
+    }
+    pub fn general_constraint_indicator_flags(&self) -> u64 {
+        self.general_constraint_indicator_flags
+    }
+    pub fn general_level_idc(&self) -> u8 {
+        self.general_level_idc
+    }
+    pub fn min_spatial_segmentation_idc(&self) -> u16 {
+        self.min_spatial_segmentation_idc
+    }
+    pub fn parallelism_type(&self) -> u8 {
+        self.parallelism_type
+    }
+    pub fn chroma_format(&self) -> u8 {
+        self.chroma_format
+    }
+    pub fn bit_depth_luma(&self) -> u8 {
+        self.bit_depth_luma
+    }
+    pub fn bit_depth_chroma(&self) -> u8 {
+        self.bit_depth_chroma
+    }
+    // Frames per 256 seconds.
+    pub fn avg_frame_rate(&self) -> u16 {
+        self.avg_frame_rate
+    }
+    pub fn constant_frame_rate(&self) -> u8 {
+        self.constant_frame_rate
+    }
+    pub fn num_temporal_layers(&self) -> u8 {
+        self.num_temporal_layers
+    }
+    pub fn temporal_id_nested(&self) -> bool {
+        self.temporal_id_nested
+    }
+    // Size in bytes of the length prefix of each NAL unit in the samples.
+    pub fn nal_length_size(&self) -> u8 {
+        self.length_size_minus_one + 1
+    }
+    pub fn arrays(&self) -> &[HvccArray] {
+        &self.arrays
+    }
+    // NAL units of `nal_unit_type` across all arrays.
+    pub fn nal_units(&self, nal_unit_type: u8) -> Vec<&[u8]> {
+        self.arrays.iter()
+            .filter(|array| array.nal_unit_type == nal_unit_type)
+            .flat_map(|array| array.nal_units.iter().map(|nal| nal.as_slice()))
+            .collect()
+    }
+    // ISO/IEC 14496-15 E.3 codec string, e.g. `hvc1.2.4.L123.B0`; `format` is the sample entry format.
+    pub fn codec_string(&self, format: &str) -> String {
+        let profile_space = ["", "A", "B", "C"][self.general_profile_space as usize];
+        let tier = if self.general_tier_flag { "H" } else { "L" };
+        let mut codec = format!("{}.{}{}.{:X}.{}{}", format, profile_space, self.general_profile_idc,
+                                self.general_profile_compatibility_flags.reverse_bits(), tier,
+                                self.general_level_idc);
+        // Six constraint bytes, trailing zero bytes omitted.
+        let mut constraints: Vec<u8> = (0..6).rev()
+            .map(|i| (self.general_constraint_indicator_flags >> (8 * i)) as u8)
+            .collect();
+        while constraints.last() == Some(&0) {
+            constraints.pop();
+        }
+        for byte in constraints {
+            codec.push_str(&format!(".{:X}", byte));
+        }
+        codec
+    }
+    // Decode the first VPS.
+    pub fn vps(&self) -> Result<Vps, Mp4Error> {
+        match self.nal_units(NAL_UNIT_TYPE_VPS).first() {
+            Some(nal) => Vps::parse(nal),
+            None => Err(self.header.invalid_value("hvcC has no video parameter set"))
+        }
+    }
+    // Decode the first SPS.
+    pub fn sps(&self) -> Result<Sps, Mp4Error> {
+        match self.nal_units(NAL_UNIT_TYPE_SPS).first() {
+            Some(nal) => Sps::parse(nal),
+            None => Err(self.header.invalid_value("hvcC has no sequence parameter set"))
+        }
+    }
+}
@@ -1,11 +1,12 @@
 
 use std::str;
 use std::str::FromStr;
-use std::string::ToString;
+use std::fmt;
 /**
 Atom Types:
 Atoms:
 
+```text
 ftyp
 pdin
 moov
@@ -18,6 +19,11 @@ moov
             minf
                 stbl
                     stsd
+                        avcC
+                        hvcC
+                        av1C
+                        vpcC
+                        esds
                     stts
                     stsc
                     stsz
@@ -51,6 +57,7 @@ moof
     mfhd
     traf
         tfhd
+        tfdt
         trun
         sdtp
         sbgp
@@ -92,16 +99,17 @@ meta
         tsel
 meco
     mere
+```
 
-
+```text
 [
     'ftyp', 'pdin', 'moov', 'mvhd', 'trak', 'tkhd', 'mdia', 
-    'mdhd', 'hdlr', 'minf', 'stbl', 'stsd', 'stts', 'stsc', 
+    'mdhd', 'hdlr', 'minf', 'stbl', 'stsd', 'avcC', 'hvcC', 'av1C', 'vpcC', 'esds', 'stts', 'stsc', 
     'stsz', 'stz2', 'stss', 'stco', 'co64', 'ctts', 'stsh', 
     'padb', 'stdp', 'sdtp', 'sbgp', 'sgpd', 'subs', 'dinf', 
     'dref', 'nmhd', 'hmhd', 'smhd', 'vmhd', 'tref', 'edts', 
     'elst', 'mvex', 'mehd', 'trex', 'ipmc', 'moof', 'mfhd', 
-    'traf', 'tfhd', 'trun', 'sdtp', 'sbgp', 'subs', 'mfra', 
+    'traf', 'tfhd', 'tfdt', 'trun', 'sdtp', 'sbgp', 'subs', 'mfra', 
     'tfra', 'mfro', 'mdat', 'free', 'skip', 'udta', 'cprt', 
     'meta', 'hdlr', 'dinf', 'dref', 'ipmc', 'iloc', 'ipro', 
     'sinf', 'frma', 'imif', 'schm', 'schi', 'iinf', 'xml', 
@@ -135,10 +143,11 @@ for atom in atoms:
     else:
         print "error"
 print "\n".join(sss)
-
+```
 
 Container atom types:
 
+```text
     dinf
     edts
     ipro
@@ -154,11 +163,14 @@ Container atom types:
     stbl
     traf
     trak
+```
 
 **/
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Kind{
+    av1C,
+    avcC,
     bxml,
     co64,
     cprt,
@@ -168,6 +180,7 @@ pub enum Kind{
     dref,
     edts,
     elst,
+    esds,
     fecr,
     fiin,
     fpar,
@@ -176,6 +189,7 @@ pub enum Kind{
     ftyp,
     hdlr,
     hmhd,
+    hvcC,
     iinf,
     iloc,
     imif,
@@ -221,6 +235,7 @@ pub enum Kind{
     stts,
     stz2,
     subs,
+    tfdt,
     tfhd,
     tfra,
     tkhd,
@@ -233,6 +248,7 @@ pub enum Kind{
     udta,
     uuid,
     vmhd,
+    vpcC,
     xml,
     strk,
     stri,
@@ -244,6 +260,8 @@ impl FromStr for Kind {
     type Err = &'static str;
     fn from_str(s: &str) -> Result<Self, Self::Err>{
         match s {
+            "av1C" => Ok(Kind::av1C),
+            "avcC" => Ok(Kind::avcC),
             "bxml" => Ok(Kind::bxml),
             "co64" => Ok(Kind::co64),
             "cprt" => Ok(Kind::cprt),
@@ -253,6 +271,7 @@ impl FromStr for Kind {
             "dref" => Ok(Kind::dref),
             "edts" => Ok(Kind::edts),
             "elst" => Ok(Kind::elst),
+            "esds" => Ok(Kind::esds),
             "fecr" => Ok(Kind::fecr),
             "fiin" => Ok(Kind::fiin),
             "fpar" => Ok(Kind::fpar),
@@ -261,6 +280,7 @@ impl FromStr for Kind {
             "ftyp" => Ok(Kind::ftyp),
             "hdlr" => Ok(Kind::hdlr),
             "hmhd" => Ok(Kind::hmhd),
+            "hvcC" => Ok(Kind::hvcC),
             "iinf" => Ok(Kind::iinf),
             "iloc" => Ok(Kind::iloc),
             "imif" => Ok(Kind::imif),
@@ -306,6 +326,7 @@ impl FromStr for Kind {
             "stts" => Ok(Kind::stts),
             "stz2" => Ok(Kind::stz2),
             "subs" => Ok(Kind::subs),
+            "tfdt" => Ok(Kind::tfdt),
             "tfhd" => Ok(Kind::tfhd),
             "tfra" => Ok(Kind::tfra),
             "tkhd" => Ok(Kind::tkhd),
@@ -318,6 +339,7 @@ impl FromStr for Kind {
             "udta" => Ok(Kind::udta),
             "uuid" => Ok(Kind::uuid),
             "vmhd" => Ok(Kind::vmhd),
+            "vpcC" => Ok(Kind::vpcC),
             "xml" | "xml\u{0}" => Ok(Kind::xml),
             "strk" => Ok(Kind::strk),
             "stri" => Ok(Kind::stri),
@@ -327,9 +349,11 @@ impl FromStr for Kind {
     }
 }
 
-impl ToString for Kind {
-    fn to_string(&self) -> String {
-        match *self {
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            Kind::av1C => "av1C".to_owned(),
+            Kind::avcC => "avcC".to_owned(),
             Kind::bxml => "bxml".to_owned(),
             Kind::co64 => "co64".to_owned(),
             Kind::cprt => "cprt".to_owned(),
@@ -339,6 +363,7 @@ impl ToString for Kind {
             Kind::dref => "dref".to_owned(),
             Kind::edts => "edts".to_owned(),
             Kind::elst => "elst".to_owned(),
+            Kind::esds => "esds".to_owned(),
             Kind::fecr => "fecr".to_owned(),
             Kind::fiin => "fiin".to_owned(),
             Kind::fpar => "fpar".to_owned(),
@@ -347,6 +372,7 @@ impl ToString for Kind {
             Kind::ftyp => "ftyp".to_owned(),
             Kind::hdlr => "hdlr".to_owned(),
             Kind::hmhd => "hmhd".to_owned(),
+            Kind::hvcC => "hvcC".to_owned(),
             Kind::iinf => "iinf".to_owned(),
             Kind::iloc => "iloc".to_owned(),
             Kind::imif => "imif".to_owned(),
@@ -392,6 +418,7 @@ impl ToString for Kind {
             Kind::stts => "stts".to_owned(),
             Kind::stz2 => "stz2".to_owned(),
             Kind::subs => "subs".to_owned(),
+            Kind::tfdt => "tfdt".to_owned(),
             Kind::tfhd => "tfhd".to_owned(),
             Kind::tfra => "tfra".to_owned(),
             Kind::tkhd => "tkhd".to_owned(),
@@ -404,12 +431,14 @@ impl ToString for Kind {
             Kind::udta => "udta".to_owned(),
             Kind::uuid => "uuid".to_owned(),
             Kind::vmhd => "vmhd".to_owned(),
+            Kind::vpcC => "vpcC".to_owned(),
             Kind::xml  => "xml\u{0}".to_owned(),
             Kind::strk => "strk".to_owned(),
             Kind::stri => "stri".to_owned(),
             Kind::strd => "strd".to_owned(),
             Kind::Unrecognized(ref s) => s.to_owned()
-        }
+        };
+        f.write_str(&name)
     }
 }
 
@@ -417,10 +446,7 @@ impl Kind {
     pub fn from_bytes(bytes: &[u8; 4]) -> Result<Self, &'static str> {
         let kind_str = match str::from_utf8(bytes) {
             Ok(s)  => s,
-            Err(_) => {
-                println!("Atom Kind ({:?}) parse error.", bytes);
-                return Err("Atom Kind parse error.");
-            }
+            Err(_) => return Err("box type is not valid UTF-8")
         };
         Kind::from_str(kind_str)
     }
@@ -1,5 +1,7 @@
 
-use super::{Mp4File, Kind, Header, Atom};
+use super::{Mp4File, Kind, Header, Atom, Mp4Error};
+use std::io::{Read, Seek, Write};
+use byteorder::{BigEndian, WriteBytesExt};
 
 /**
 Box Type: ‘mdat’
@@ -16,18 +18,50 @@ pub struct Mdat {
 }
 
 impl Mdat {
-    pub fn parse(f: &mut Mp4File, header: Header) -> Result<Self, &'static str>{
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, header: Header) -> Result<Self, Mp4Error>{
         let curr_offset = f.offset();
         f.seek(curr_offset+header.data_size);
         f.offset_inc(header.data_size);
         Ok(Mdat{
-            header: header
+            header
         })
     }
-    // pub fn read(&self, buf: &mut [u8]) -> Result<usize>{
-
-    // }
-    // pub fn read_to_end(&self, buf: &mut Vec<u8>) -> Result<usize>{
-
-    // }
+    // File offset of the first byte of media data.
+    pub fn data_offset(&self) -> u64 {
+        self.header.offset + self.header.header_size
+    }
+    pub fn data_size(&self) -> u64 {
+        self.header.data_size
+    }
+    // True if the byte range `[offset, offset + size)` lies inside this box's media data.
+    pub fn contains(&self, offset: u64, size: u64) -> bool {
+        offset >= self.data_offset()
+            && offset.checked_add(size).is_some_and(|end| end <= self.data_offset() + self.data_size())
+    }
+    // Read from `pos` bytes into the media data; returns the number of bytes read,
+    // which is short only at the end of the box.
+    pub fn read<R: Read + Seek>(&self, f: &mut Mp4File<R>, pos: u64, buf: &mut [u8]) -> Result<usize, Mp4Error>{
+        if pos >= self.data_size() {
+            return Ok(0);
+        }
+        let len = ::std::cmp::min(buf.len() as u64, self.data_size() - pos) as usize;
+        f.read_exact_at(self.data_offset() + pos, &mut buf[..len])?;
+        Ok(len)
+    }
+    pub fn read_to_end<R: Read + Seek>(&self, f: &mut Mp4File<R>, buf: &mut Vec<u8>) -> Result<usize, Mp4Error>{
+        let start = buf.len();
+        buf.resize(start + self.data_size() as usize, 0u8);
+        f.read_exact_at(self.data_offset(), &mut buf[start..])?;
+        Ok(buf.len() - start)
+    }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn write_header<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        self.header.write_header(w, self.data_size())
+    }
+    // The payload stays in the source file; see `Mp4File::write_to`.
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        Err(self.header.invalid_value("mdat payload is not held in memory"))
+    }
 }
\ No newline at end of file
@@ -1,4 +1,4 @@
-/**
+/*
 
 meco
     mere
@@ -6,7 +6,9 @@ meco
 **/
 
 use std::string::String;
-use super::{Mp4File, Kind, Header, Atom};
+use super::{Mp4File, Kind, Header, Atom, Mp4Error};
+use std::io::{Read, Seek, Write};
+use byteorder::{BigEndian, WriteBytesExt};
 
 /**
 
@@ -33,13 +35,27 @@ pub struct Meco {
 }
 
 impl Meco {
-    pub fn parse(f: &mut Mp4File, mut header: Header) -> Result<Self, &'static str>{
-        let children: Vec<Atom> = Atom::parse_children(f);
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, mut header: Header) -> Result<Self, Mp4Error>{
+        let children: Vec<Atom> = Atom::parse_children(f, &header)?;
         Ok(Meco{
-            header: header,
-            children: children
+            header,
+            children
         })
     }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn children(&self) -> &Vec<Atom> {
+        &self.children
+    }
+    pub fn children_mut(&mut self) -> &mut Vec<Atom> {
+        &mut self.children
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        let mut data: Vec<u8> = Vec::new();
+        Atom::write_children(&self.children, &mut data)?;
+        self.header.write(w, &data)
+    }
 }
 
 /**
@@ -83,6 +99,7 @@ When a meta-box is used, then URLs may be used to refer to items in the meta-box
 either using an absolute URL, or using a relative URL. Absolute URLs may only be used 
 to refer to items in a file-level meta box.
 
+```text
     When interpreting data that is in the context of a meta-box (i.e. the file for a file-level meta-box, 
     the presentation for a movie-level meta-box, or the track for a track-level meta-box), 
     the items in the meta-box are treated as shadowing files in the same location as that from which 
@@ -109,6 +126,7 @@ to refer to items in a file-level meta box.
     using the normal rules: <http://a.com/d/flower.gif> and again it checks to see if flower.gif is 
     a named item (and hence shadowing a separate file of this name), and then if it is not, fetches flower.gif 
     from a.com.
+```
 
 8.11.10 Static Metadata
 
@@ -135,6 +153,7 @@ an entire stream, at the track level.
 
 MPEG-7 metadata is stored in meta boxes to this specification.
 
+```text
     1) The handler-type is ‘mp7t’ for textual metadata in Unicode format;
     2) The handler-type is ‘mp7b’ for binary metadata compressed in the BIM format. 
         In this case, the binary XML box contains the configuration information 
@@ -147,6 +166,7 @@ MPEG-7 metadata is stored in meta boxes to this specification.
         the item containing the MPEG-7 binarized XML.
     5) If an MPEG-7 box is used at the file level, then the brand ‘mp71’ should be a member of 
         the compatible-brands list in the file-type box.
+```
 
 **/
 
@@ -159,22 +179,32 @@ pub struct Mere {
 }
 
 impl Mere {
-    pub fn parse(f: &mut Mp4File, mut header: Header) -> Result<Self, &'static str>{
-        header.parse_version(f);
-        header.parse_flags(f);
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, mut header: Header) -> Result<Self, Mp4Error>{
+        header.parse_version(f)?;
+        header.parse_flags(f)?;
 
         // let curr_offset = f.offset();
         // f.seek(curr_offset+header.data_size);
-        let first_metabox_handler_type  = f.read_u32().unwrap();
-        let second_metabox_handler_type = f.read_u32().unwrap();
-        let metabox_relation            = f.read_u8().unwrap();
+        let first_metabox_handler_type  = f.read_u32()?;
+        let second_metabox_handler_type = f.read_u32()?;
+        let metabox_relation            = f.read_u8()?;
 
         f.offset_inc(header.data_size);
         Ok(Mere{
-            header: header,
-            first_metabox_handler_type: first_metabox_handler_type,
-            second_metabox_handler_type: second_metabox_handler_type,
-            metabox_relation: metabox_relation
+            header,
+            first_metabox_handler_type,
+            second_metabox_handler_type,
+            metabox_relation
         })
     }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        let mut data: Vec<u8> = Vec::new();
+        data.write_u32::<BigEndian>(self.first_metabox_handler_type)?;
+        data.write_u32::<BigEndian>(self.second_metabox_handler_type)?;
+        data.write_u8(self.metabox_relation)?;
+        self.header.write(w, &data)
+    }
 }
\ No newline at end of file
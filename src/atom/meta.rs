@@ -1,4 +1,4 @@
-/**
+/*
 
 meta
     hdlr
@@ -27,7 +27,9 @@ meta
 **/
 
 use std::string::String;
-use super::{Mp4File, Kind, Header, Atom};
+use super::{Mp4File, Kind, Header, Atom, Mp4Error};
+use std::io::{Read, Seek, Write};
+use byteorder::{BigEndian, WriteBytesExt};
 
 /**
 8.11.1 The Meta box
@@ -74,20 +76,27 @@ the handler type may be the same as the item_type.
 
 #[derive(Debug, Clone)]
 pub struct Meta {
-    header: Header
+    header: Header,
+    data  : Vec<u8>
 }
 
 impl Meta {
-    pub fn parse(f: &mut Mp4File, mut header: Header) -> Result<Self, &'static str>{
-        header.parse_version(f);
-        header.parse_flags(f);
-        let curr_offset = f.offset();
-        f.seek(curr_offset+header.data_size);
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, mut header: Header) -> Result<Self, Mp4Error>{
+        header.parse_version(f)?;
+        header.parse_flags(f)?;
+        let data = f.read_bytes(header.data_size)?;
         f.offset_inc(header.data_size);
         Ok(Meta{
-            header: header
+            header,
+            data
         })
     }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        self.header.write(w, &self.data)
+    }
 }
 
 /**
@@ -127,24 +136,31 @@ pub struct Xml {
 }
 
 impl Xml {
-    pub fn parse(f: &mut Mp4File, mut header: Header) -> Result<Self, &'static str>{
-        header.parse_version(f);
-        header.parse_flags(f);
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, mut header: Header) -> Result<Self, Mp4Error>{
+        header.parse_version(f)?;
+        header.parse_flags(f)?;
 
         // let curr_offset = f.offset();
         // f.seek(curr_offset+header.data_size);
         let mut xml_bytes: Vec<u8> = Vec::new();
         for _ in 0..header.data_size {
-            xml_bytes.push(f.read_u8().unwrap());
+            xml_bytes.push(f.read_u8()?);
         }
-        let xml: String = String::from_utf8(xml_bytes).unwrap();
+        let xml: String = String::from_utf8(xml_bytes)
+                .map_err(|_| header.invalid_value("xml is not valid UTF-8"))?;
 
         f.offset_inc(header.data_size);
         Ok(Xml{
-            header: header,
-            xml   : xml
+            header,
+            xml
         })
     }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        self.header.write(w, self.xml.as_bytes())
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -154,22 +170,28 @@ pub struct Bxml {
 }
 
 impl Bxml {
-    pub fn parse(f: &mut Mp4File, mut header: Header) -> Result<Self, &'static str>{
-        header.parse_version(f);
-        header.parse_flags(f);
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, mut header: Header) -> Result<Self, Mp4Error>{
+        header.parse_version(f)?;
+        header.parse_flags(f)?;
 
         // let curr_offset = f.offset();
         // f.seek(curr_offset+header.data_size);
         let mut data: Vec<u8> = Vec::new();
         for _ in 0..header.data_size {
-            data.push(f.read_u8().unwrap());
+            data.push(f.read_u8()?);
         }
         // let xml: String = String::from_utf8(xml_bytes).unwrap();
 
         f.offset_inc(header.data_size);
         Ok(Bxml{
-            header: header,
-            data  : data
+            header,
+            data
         })
     }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        self.header.write(w, &self.data)
+    }
 }
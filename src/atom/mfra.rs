@@ -1,4 +1,4 @@
-/**
+/*
 
 mfra
     tfra
@@ -6,7 +6,9 @@ mfra
 
 **/
 
-use super::{Mp4File, Kind, Header, Atom};
+use super::{Mp4File, Kind, Header, Atom, Mp4Error};
+use std::io::{Read, Seek, Write};
+use byteorder::{BigEndian, WriteBytesExt};
 
 #[derive(Debug, Clone)]
 pub struct Mfra {
@@ -15,13 +17,27 @@ pub struct Mfra {
 }
 
 impl Mfra {
-    pub fn parse(f: &mut Mp4File, header: Header) -> Result<Self, &'static str>{
-        let children: Vec<Atom> = Atom::parse_children(f);
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, header: Header) -> Result<Self, Mp4Error>{
+        let children: Vec<Atom> = Atom::parse_children(f, &header)?;
         Ok(Mfra{
-            header: header,
-            children: children
+            header,
+            children
         })
     }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn children(&self) -> &Vec<Atom> {
+        &self.children
+    }
+    pub fn children_mut(&mut self) -> &mut Vec<Atom> {
+        &mut self.children
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        let mut data: Vec<u8> = Vec::new();
+        Atom::write_children(&self.children, &mut data)?;
+        self.header.write(w, &data)
+    }
 }
 
 /**
@@ -65,22 +81,26 @@ aligned(8) class TrackFragmentRandomAccessBox extends FullBox(‘tfra’, versio
 #[derive(Debug, Clone)]
 pub struct Tfra {
     header: Header,
-    sequence_number: u32
+    data  : Vec<u8>
 }
 
 impl Tfra {
-    pub fn parse(f: &mut Mp4File, mut header: Header) -> Result<Self, &'static str>{
-        header.parse_version(f);
-        header.parse_flags(f);
-        let curr_offset = f.offset();
-        // f.seek(curr_offset+header.data_size);
-        let sequence_number: u32 = f.read_u32().unwrap();
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, mut header: Header) -> Result<Self, Mp4Error>{
+        header.parse_version(f)?;
+        header.parse_flags(f)?;
+        let data = f.read_bytes(header.data_size)?;
         f.offset_inc(header.data_size);
         Ok(Tfra{
-            header: header,
-            sequence_number: sequence_number
+            header,
+            data
         })
     }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        self.header.write(w, &self.data)
+    }
 }
 
 /**
@@ -118,16 +138,24 @@ pub struct Mfro {
 }
 
 impl Mfro {
-    pub fn parse(f: &mut Mp4File, mut header: Header) -> Result<Self, &'static str>{
-        header.parse_version(f);
-        header.parse_flags(f);
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, mut header: Header) -> Result<Self, Mp4Error>{
+        header.parse_version(f)?;
+        header.parse_flags(f)?;
         // let curr_offset = f.offset();
         // f.seek(curr_offset+header.data_size);
-        let size: u32 = f.read_u32().unwrap();
+        let size: u32 = f.read_u32()?;
         f.offset_inc(header.data_size);
         Ok(Mfro{
-            header: header,
-            size  : size
+            header,
+            size
         })
     }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        let mut data: Vec<u8> = Vec::new();
+        data.write_u32::<BigEndian>(self.size)?;
+        self.header.write(w, &data)
+    }
 }
\ No newline at end of file
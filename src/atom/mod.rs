@@ -8,7 +8,7 @@
 
 // box types: http://mp4ra.org/atoms.html
 
-/**
+/*
     Box Struct:
     
         size(u32), type(u32), largesize(u64),
@@ -32,6 +32,11 @@ moov
             minf
                 stbl
                     stsd
+                        avcC
+                        hvcC
+                        av1C
+                        vpcC
+                        esds
                     stts
                     stsc
                     stsz
@@ -65,6 +70,7 @@ moof
     mfhd
     traf
         tfhd
+        tfdt
         trun
         sdtp
         sbgp
@@ -153,8 +159,9 @@ use std::string::ToString;
 use std::convert::AsRef;
 
 use std::io::{Write, Read, ErrorKind, SeekFrom, Seek};
-use ::byteorder::{BigEndian, ReadBytesExt};
+use ::byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 pub use super::Mp4File;
+use super::Mp4Error;
 
 mod kind;
 
@@ -168,38 +175,56 @@ mod meta;
 mod mfra;
 mod moof;
 mod moov;
+mod stsd;
+mod avcc;
+mod hvcc;
+mod av1c;
+mod vpcc;
+mod esds;
 mod ignore;
 mod unrecognized;
 
 pub use self::kind::Kind;
 
-use self::ftyp::Ftyp;
-use self::freespace::{Free, Skip};
-use self::mdat::Mdat;
-use self::pdin::Pdin;
-use self::uuid::Uuid;
+pub use self::ftyp::{Ftyp, FileType};
+pub use self::freespace::{Free, Skip};
+pub use self::mdat::Mdat;
+pub use self::pdin::Pdin;
+pub use self::uuid::Uuid;
 
-use self::moov::{
+pub use self::moov::{
     Moov, Mvhd, Trak, Tkhd, Tref, Mdia, Mdhd, Hdlr,
-    Minf, Vmhd, Smhd, Hmhd, Nmhd, Stbl, Stsd, Stdp,
+    Minf, Vmhd, Smhd, Hmhd, Nmhd, Stbl, Stdp,
     Stts, Ctts, Cslg, Stss, Stsh, Sdtp, Stsc, Stsz,
     Stz2, Stco, Co64, Padb, 
-    Mvex, Mehd, Trex
+    Mvex, Mehd, Trex, Edts, Elst,
+    TRACK_ENABLED, TRACK_IN_MOVIE, TRACK_IN_PREVIEW,
+    STTS_Entry, CTTS_Entry_Offset, ELST_Entry, TrackReference
+};
+pub use self::stsd::{
+    Stsd, SampleEntry, VisualSampleEntry, AudioSampleEntry,
+    UnknownSampleEntry, QuickTimeSound
 };
-use self::moof::{
-    Moof, Mfhd, Traf, Tfhd, Trun, 
+pub use self::avcc::{Avcc, AvccExt, AvcConfig};
+pub use self::hvcc::{Hvcc, HvccArray};
+pub use self::av1c::Av1c;
+pub use self::vpcc::{Vpcc, VpcConfig};
+pub use self::esds::{Esds, EsDescriptor, DecoderConfigDescriptor};
+pub use self::moof::{
+    Moof, Mfhd, Traf, Tfhd, Tfdt, Trun, 
+    TfFlags, TrFlags
 };
-use self::mfra::{
+pub use self::mfra::{
     Mfra, Tfra, Mfro
 };
-use self::meta::{
+pub use self::meta::{
     Meta, Xml, Bxml
 };
-use self::meco::{
+pub use self::meco::{
     Meco, Mere
 };
-use self::ignore::Ignore;
-use self::unrecognized::Unrecognized;
+pub use self::ignore::Ignore;
+pub use self::unrecognized::Unrecognized;
 
 #[derive(Debug, Clone)]
 pub struct Entry {
@@ -208,6 +233,25 @@ pub struct Entry {
     sample_description_index: u32
 }
 
+impl Entry {
+    pub fn new(first_chunk: u32, samples_per_chunk: u32, sample_description_index: u32) -> Entry {
+        Entry {
+            first_chunk,
+            samples_per_chunk,
+            sample_description_index
+        }
+    }
+    pub fn first_chunk(&self) -> u32 {
+        self.first_chunk
+    }
+    pub fn samples_per_chunk(&self) -> u32 {
+        self.samples_per_chunk
+    }
+    pub fn sample_description_index(&self) -> u32 {
+        self.sample_description_index
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Sample {
     duration: Option<u32>,
@@ -217,7 +261,35 @@ pub struct Sample {
     description_index      : Option<u32>
 }
 
+impl Sample {
+    pub fn new(duration: Option<u32>, size: Option<u32>, flags: Option<u32>, composition_time_offset: Option<i32>) -> Sample {
+        Sample {
+            duration,
+            size,
+            flags,
+            composition_time_offset,
+            description_index      : None
+        }
+    }
+    pub fn duration(&self) -> Option<u32> {
+        self.duration
+    }
+    pub fn size(&self) -> Option<u32> {
+        self.size
+    }
+    pub fn flags(&self) -> Option<u32> {
+        self.flags
+    }
+    pub fn composition_time_offset(&self) -> Option<i32> {
+        self.composition_time_offset
+    }
+    pub fn description_index(&self) -> Option<u32> {
+        self.description_index
+    }
+}
+
 /**
+```text
     aligned(8) class Box (unsigned int(32) boxtype,
                           optional unsigned int(8)[16] extended_type) {
         unsigned int(32) size;
@@ -289,6 +361,7 @@ pub struct Sample {
         version: u8,
         flags  : [u8; 3],   // 24 Bits
     }
+```
 **/
 
 #[derive(Debug, Clone)]
@@ -309,15 +382,48 @@ pub struct Header {
 }
 
 impl Header {
-    pub fn parse(f: &mut Mp4File) -> Result<Header, &'static str>{
+    // Header of a new plain Box; sizes are filled in when the box is written.
+    pub fn new(kind: Kind) -> Header {
+        Header {
+            size: 8u32,
+            kind,
+
+            largesize  : None,
+            usertype   : None,
+            version    : None,
+            flags      : None,
+
+            atom_size  : 8u64,
+            header_size: 8u64,
+            data_size  : 0u64,
+            offset     : 0u64,
+        }
+    }
+    // Header of a new FullBox.
+    pub fn new_full(kind: Kind, version: u8, flags: u32) -> Header {
+        let mut header = Header::new(kind);
+        header.version = Some(version);
+        header.flags = Some([(flags >> 16) as u8, (flags >> 8) as u8, flags as u8]);
+        header.header_size += 4;
+        header.atom_size += 4;
+        header
+    }
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>) -> Result<Header, Mp4Error>{
         let curr_offset = f.offset();
-        let size: u32 = f.read_u32().unwrap();
+        let size: u32 = f.read_u32()?;
 
         let kind_bytes: [u8; 4] = [
-            f.read_u8().unwrap(), f.read_u8().unwrap(),
-            f.read_u8().unwrap(), f.read_u8().unwrap(),
+            f.read_u8()?, f.read_u8()?,
+            f.read_u8()?, f.read_u8()?,
         ];
-        let kind = Kind::from_bytes(&kind_bytes).unwrap();
+        let kind = match Kind::from_bytes(&kind_bytes) {
+            Ok(kind) => kind,
+            Err(reason) => return Err(Mp4Error::InvalidValue {
+                kind  : Kind::Unrecognized(String::from_utf8_lossy(&kind_bytes).into_owned()),
+                offset: curr_offset,
+                reason
+            })
+        };
 
         let header_size = 8u64;
         let atom_size = size as u64;
@@ -327,77 +433,210 @@ impl Header {
         f.offset_inc(header_size);
 
         let mut header = Header{
-            size: size,
-            kind: kind,
+            size,
+            kind,
 
             largesize  : None,
             usertype   : None,
             version    : None,
             flags      : None,
 
-            atom_size  : atom_size,    // atom size , include header and data.
-            header_size: header_size,  // atom header size, not include data size.
-            data_size  : data_size,    // atom data size , not include header size.
+            atom_size,    // atom size , include header and data.
+            header_size,  // atom header size, not include data size.
+            data_size,    // atom data size , not include header size.
             offset     : curr_offset,  // file offset.
         };
         if size == 1u32 {
-            header.parse_largesize(f);
-        } else if size < 1u32 {
-            return Err("can not parse this mp4 file.");
+            header.parse_largesize(f)?;
+        } else if size == 0u32 {
+            // box extends to end of file
+            header.atom_size = f.file_size().saturating_sub(curr_offset);
+            if header.atom_size < header_size {
+                return Err(header.invalid_size());
+            }
+            header.data_size = header.atom_size - header_size;
+        } else if atom_size < header_size {
+            return Err(header.invalid_size());
         } else {
             header.data_size = atom_size - header_size;
         }
         Ok(header)
     }
-    pub fn parse_largesize(&mut self, f: &mut Mp4File){
-        assert_eq!(self.size, 1u32);
+    pub fn parse_largesize<R: Read + Seek>(&mut self, f: &mut Mp4File<R>) -> Result<(), Mp4Error>{
+        if self.size != 1 {
+            return Err(self.invalid_size());
+        }
 
-        let largesize = f.read_u64().unwrap();
+        let largesize = f.read_u64()?;
         self.atom_size = largesize;
-        self.header_size = self.header_size + 8;
+        self.header_size += 8;
+        if largesize < self.header_size {
+            return Err(self.invalid_size());
+        }
         self.data_size = largesize - self.header_size;
 
         self.largesize = Some(largesize);
         f.offset_inc(8);
+        Ok(())
     }
-    pub fn parse_usertype(&mut self, f: &mut Mp4File){
-        let usertype: [u8; 16] = [
-            f.read_u8().unwrap(), f.read_u8().unwrap(),
-            f.read_u8().unwrap(), f.read_u8().unwrap(),
-            f.read_u8().unwrap(), f.read_u8().unwrap(),
-            f.read_u8().unwrap(), f.read_u8().unwrap(),
-            f.read_u8().unwrap(), f.read_u8().unwrap(),
-            f.read_u8().unwrap(), f.read_u8().unwrap(),
-            f.read_u8().unwrap(), f.read_u8().unwrap(),
-            f.read_u8().unwrap(), f.read_u8().unwrap()
-        ];
+    pub fn parse_usertype<R: Read + Seek>(&mut self, f: &mut Mp4File<R>) -> Result<(), Mp4Error>{
+        let mut usertype = [0u8; 16];
+        for byte in usertype.iter_mut() {
+            *byte = f.read_u8()?;
+        }
         self.usertype = Some(usertype);
 
-        self.header_size = self.header_size + 16;
-        assert!((self.atom_size - self.header_size) >= 0);
-        self.data_size = self.atom_size - self.header_size;
+        self.header_size += 16;
+        self.update_data_size()?;
         f.offset_inc(16);
+        Ok(())
     }
-    pub fn parse_version(&mut self, f: &mut Mp4File){
-        let version = f.read_u8().unwrap();
+    pub fn parse_version<R: Read + Seek>(&mut self, f: &mut Mp4File<R>) -> Result<(), Mp4Error>{
+        let version = f.read_u8()?;
         self.version = Some(version);
 
-        self.header_size = self.header_size + 1;
-        assert!((self.atom_size - self.header_size) >= 0);
-        self.data_size = self.atom_size - self.header_size;
+        self.header_size += 1;
+        self.update_data_size()?;
         f.offset_inc(1);
+        Ok(())
     }
-    pub fn parse_flags(&mut self, f: &mut Mp4File){
+    pub fn parse_flags<R: Read + Seek>(&mut self, f: &mut Mp4File<R>) -> Result<(), Mp4Error>{
         let flags: [u8; 3] = [
-            f.read_u8().unwrap(), f.read_u8().unwrap(), 
-            f.read_u8().unwrap()
+            f.read_u8()?, f.read_u8()?,
+            f.read_u8()?
         ];
         self.flags = Some(flags);
 
-        self.header_size = self.header_size + 3;
-        assert!((self.atom_size - self.header_size) >= 0);
-        self.data_size = self.atom_size - self.header_size;
+        self.header_size += 3;
+        self.update_data_size()?;
         f.offset_inc(3);
+        Ok(())
+    }
+    pub fn kind(&self) -> &Kind {
+        &self.kind
+    }
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+    pub fn largesize(&self) -> Option<u64> {
+        self.largesize
+    }
+    pub fn usertype(&self) -> Option<[u8; 16]> {
+        self.usertype
+    }
+    pub fn version(&self) -> Option<u8> {
+        self.version
+    }
+    pub fn flags(&self) -> Option<[u8; 3]> {
+        self.flags
+    }
+    // The 24-bit FullBox flags as an integer, 0 for a plain Box.
+    pub fn flag_bits(&self) -> u32 {
+        match self.flags {
+            Some(flags) => (flags[0] as u32) << 16 | (flags[1] as u32) << 8 | flags[2] as u32,
+            None => 0
+        }
+    }
+    pub fn atom_size(&self) -> u64 {
+        self.atom_size
+    }
+    // True for a `size == 0` box, whose size was taken from the remaining stream length.
+    pub fn extends_to_eof(&self) -> bool {
+        self.size == 0u32
+    }
+    pub fn header_size(&self) -> u64 {
+        self.header_size
+    }
+    pub fn data_size(&self) -> u64 {
+        self.data_size
+    }
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+    // Fails with `UnsupportedVersion` unless the FullBox version is at most `max`.
+    pub fn check_version(&self, max: u8) -> Result<u8, Mp4Error>{
+        let version = self.version.unwrap_or(0);
+        if version > max {
+            return Err(Mp4Error::UnsupportedVersion {
+                kind   : self.kind.clone(),
+                offset : self.offset,
+                version
+            });
+        }
+        Ok(version)
+    }
+    // File offset just past the box; a size that overflows it is invalid.
+    pub fn end(&self) -> Result<u64, Mp4Error> {
+        self.offset.checked_add(self.atom_size).ok_or_else(|| self.invalid_size())
+    }
+    pub fn invalid_size(&self) -> Mp4Error {
+        Mp4Error::InvalidSize {
+            kind  : self.kind.clone(),
+            offset: self.offset,
+            size  : self.atom_size
+        }
+    }
+    pub fn invalid_value(&self, reason: &'static str) -> Mp4Error {
+        Mp4Error::InvalidValue {
+            kind  : self.kind.clone(),
+            offset: self.offset,
+            reason
+        }
+    }
+    // Write the header of this box for a body of `data_size` bytes (not counting version and flags).
+    // The 64-bit `largesize` form is kept if the box was read that way and used when
+    // the size no longer fits in 32 bits; a `size == 0` box keeps extending to the end of file.
+    pub fn write_header<W: Write>(&self, w: &mut W, data_size: u64) -> Result<(), Mp4Error>{
+        let mut header_size = 8u64;
+        if self.usertype.is_some() {
+            header_size += 16;
+        }
+        if self.version.is_some() {
+            header_size += 1;
+        }
+        if self.flags.is_some() {
+            header_size += 3;
+        }
+        let large = self.largesize.is_some() || header_size + data_size > u32::MAX as u64;
+        if large {
+            header_size += 8;
+        }
+        let atom_size = header_size + data_size;
+
+        if large {
+            w.write_u32::<BigEndian>(1u32)?;
+        } else if self.extends_to_eof() {
+            w.write_u32::<BigEndian>(0u32)?;
+        } else {
+            w.write_u32::<BigEndian>(atom_size as u32)?;
+        }
+        w.write_all(&self.kind.into_bytes())?;
+        if large {
+            w.write_u64::<BigEndian>(atom_size)?;
+        }
+        if let Some(usertype) = self.usertype {
+            w.write_all(&usertype)?;
+        }
+        if let Some(version) = self.version {
+            w.write_u8(version)?;
+        }
+        if let Some(flags) = self.flags {
+            w.write_all(&flags)?;
+        }
+        Ok(())
+    }
+    // Write the header followed by `data`.
+    pub fn write<W: Write>(&self, w: &mut W, data: &[u8]) -> Result<(), Mp4Error>{
+        self.write_header(w, data.len() as u64)?;
+        w.write_all(data)?;
+        Ok(())
+    }
+    fn update_data_size(&mut self) -> Result<(), Mp4Error>{
+        if self.atom_size < self.header_size {
+            return Err(self.invalid_size());
+        }
+        self.data_size = self.atom_size - self.header_size;
+        Ok(())
     }
 }
 
@@ -415,6 +654,8 @@ pub enum Atom {
     trak(Trak),
     tkhd(Tkhd),
     tref(Tref),
+    edts(Edts),
+    elst(Elst),
     mdia(Mdia),
     mdhd(Mdhd),
     hdlr(Hdlr),
@@ -436,6 +677,11 @@ pub enum Atom {
     stco(Stco),
     co64(Co64),
     stsd(Stsd),
+    avcC(Avcc),
+    hvcC(Hvcc),
+    av1C(Av1c),
+    vpcC(Vpcc),
+    esds(Esds),
     stdp(Stdp),
     stts(Stts),
     ctts(Ctts),
@@ -450,6 +696,7 @@ pub enum Atom {
     mfhd(Mfhd),
     traf(Traf),
     tfhd(Tfhd),
+    tfdt(Tfdt),
     trun(Trun),
     // MFRA
     mfra(Mfra),
@@ -468,119 +715,356 @@ pub enum Atom {
 }
 
 impl Atom {
-    fn parse_kind(f: &mut Mp4File) -> Result<Kind, &'static str> {
-        let kind_bytes: [u8; 4] = [
-            f.read_u8().unwrap(), f.read_u8().unwrap(),
-            f.read_u8().unwrap(), f.read_u8().unwrap(),
-        ];
-        Kind::from_bytes(&kind_bytes)
+    pub fn header(&self) -> &Header {
+        match *self {
+            Atom::ftyp(ref atom)         => atom.header(),
+            Atom::free(ref atom)         => atom.header(),
+            Atom::skip(ref atom)         => atom.header(),
+            Atom::mdat(ref atom)         => atom.header(),
+            Atom::pdin(ref atom)         => atom.header(),
+            Atom::uuid(ref atom)         => atom.header(),
+            Atom::moov(ref atom)         => atom.header(),
+            Atom::mvhd(ref atom)         => atom.header(),
+            Atom::trak(ref atom)         => atom.header(),
+            Atom::edts(ref atom)         => atom.header(),
+            Atom::elst(ref atom)         => atom.header(),
+            Atom::tkhd(ref atom)         => atom.header(),
+            Atom::tref(ref atom)         => atom.header(),
+            Atom::mdia(ref atom)         => atom.header(),
+            Atom::mdhd(ref atom)         => atom.header(),
+            Atom::hdlr(ref atom)         => atom.header(),
+            Atom::minf(ref atom)         => atom.header(),
+            Atom::vmhd(ref atom)         => atom.header(),
+            Atom::smhd(ref atom)         => atom.header(),
+            Atom::hmhd(ref atom)         => atom.header(),
+            Atom::nmhd(ref atom)         => atom.header(),
+            Atom::mvex(ref atom)         => atom.header(),
+            Atom::mehd(ref atom)         => atom.header(),
+            Atom::trex(ref atom)         => atom.header(),
+            Atom::stbl(ref atom)         => atom.header(),
+            Atom::stsc(ref atom)         => atom.header(),
+            Atom::stsz(ref atom)         => atom.header(),
+            Atom::stz2(ref atom)         => atom.header(),
+            Atom::stco(ref atom)         => atom.header(),
+            Atom::co64(ref atom)         => atom.header(),
+            Atom::stsd(ref atom)         => atom.header(),
+            Atom::avcC(ref atom)         => atom.header(),
+            Atom::hvcC(ref atom)         => atom.header(),
+            Atom::av1C(ref atom)         => atom.header(),
+            Atom::vpcC(ref atom)         => atom.header(),
+            Atom::esds(ref atom)         => atom.header(),
+            Atom::stdp(ref atom)         => atom.header(),
+            Atom::stts(ref atom)         => atom.header(),
+            Atom::ctts(ref atom)         => atom.header(),
+            Atom::cslg(ref atom)         => atom.header(),
+            Atom::stss(ref atom)         => atom.header(),
+            Atom::stsh(ref atom)         => atom.header(),
+            Atom::sdtp(ref atom)         => atom.header(),
+            Atom::padb(ref atom)         => atom.header(),
+            Atom::moof(ref atom)         => atom.header(),
+            Atom::mfhd(ref atom)         => atom.header(),
+            Atom::traf(ref atom)         => atom.header(),
+            Atom::tfhd(ref atom)         => atom.header(),
+            Atom::tfdt(ref atom)         => atom.header(),
+            Atom::trun(ref atom)         => atom.header(),
+            Atom::mfra(ref atom)         => atom.header(),
+            Atom::tfra(ref atom)         => atom.header(),
+            Atom::mfro(ref atom)         => atom.header(),
+            Atom::meta(ref atom)         => atom.header(),
+            Atom::xml(ref atom)          => atom.header(),
+            Atom::bxml(ref atom)         => atom.header(),
+            Atom::meco(ref atom)         => atom.header(),
+            Atom::mere(ref atom)         => atom.header(),
+            Atom::ignore(ref atom)       => atom.header(),
+            Atom::unrecognized(ref atom) => atom.header(),
+        }
     }
-    
-    pub fn parse(f: &mut Mp4File) -> Result<Self, &'static str> {
-        let mut header = Header::parse(f).unwrap();
+    // Serialize this box and its children.
+    // A parsed `mdat` only holds its header, use `Mp4File::write_to` to copy its payload;
+    // the same goes for top level boxes that are not parsed, see `source_range`.
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error> {
+        match *self {
+            Atom::ftyp(ref atom)         => atom.write(w),
+            Atom::free(ref atom)         => atom.write(w),
+            Atom::skip(ref atom)         => atom.write(w),
+            Atom::mdat(ref atom)         => atom.write(w),
+            Atom::pdin(ref atom)         => atom.write(w),
+            Atom::uuid(ref atom)         => atom.write(w),
+            Atom::moov(ref atom)         => atom.write(w),
+            Atom::mvhd(ref atom)         => atom.write(w),
+            Atom::trak(ref atom)         => atom.write(w),
+            Atom::edts(ref atom)         => atom.write(w),
+            Atom::elst(ref atom)         => atom.write(w),
+            Atom::tkhd(ref atom)         => atom.write(w),
+            Atom::tref(ref atom)         => atom.write(w),
+            Atom::mdia(ref atom)         => atom.write(w),
+            Atom::mdhd(ref atom)         => atom.write(w),
+            Atom::hdlr(ref atom)         => atom.write(w),
+            Atom::minf(ref atom)         => atom.write(w),
+            Atom::vmhd(ref atom)         => atom.write(w),
+            Atom::smhd(ref atom)         => atom.write(w),
+            Atom::hmhd(ref atom)         => atom.write(w),
+            Atom::nmhd(ref atom)         => atom.write(w),
+            Atom::mvex(ref atom)         => atom.write(w),
+            Atom::mehd(ref atom)         => atom.write(w),
+            Atom::trex(ref atom)         => atom.write(w),
+            Atom::stbl(ref atom)         => atom.write(w),
+            Atom::stsc(ref atom)         => atom.write(w),
+            Atom::stsz(ref atom)         => atom.write(w),
+            Atom::stz2(ref atom)         => atom.write(w),
+            Atom::stco(ref atom)         => atom.write(w),
+            Atom::co64(ref atom)         => atom.write(w),
+            Atom::stsd(ref atom)         => atom.write(w),
+            Atom::avcC(ref atom)         => atom.write(w),
+            Atom::hvcC(ref atom)         => atom.write(w),
+            Atom::av1C(ref atom)         => atom.write(w),
+            Atom::vpcC(ref atom)         => atom.write(w),
+            Atom::esds(ref atom)         => atom.write(w),
+            Atom::stdp(ref atom)         => atom.write(w),
+            Atom::stts(ref atom)         => atom.write(w),
+            Atom::ctts(ref atom)         => atom.write(w),
+            Atom::cslg(ref atom)         => atom.write(w),
+            Atom::stss(ref atom)         => atom.write(w),
+            Atom::stsh(ref atom)         => atom.write(w),
+            Atom::sdtp(ref atom)         => atom.write(w),
+            Atom::padb(ref atom)         => atom.write(w),
+            Atom::moof(ref atom)         => atom.write(w),
+            Atom::mfhd(ref atom)         => atom.write(w),
+            Atom::traf(ref atom)         => atom.write(w),
+            Atom::tfhd(ref atom)         => atom.write(w),
+            Atom::tfdt(ref atom)         => atom.write(w),
+            Atom::trun(ref atom)         => atom.write(w),
+            Atom::mfra(ref atom)         => atom.write(w),
+            Atom::tfra(ref atom)         => atom.write(w),
+            Atom::mfro(ref atom)         => atom.write(w),
+            Atom::meta(ref atom)         => atom.write(w),
+            Atom::xml(ref atom)          => atom.write(w),
+            Atom::bxml(ref atom)         => atom.write(w),
+            Atom::meco(ref atom)         => atom.write(w),
+            Atom::mere(ref atom)         => atom.write(w),
+            Atom::ignore(ref atom)       => atom.write(w),
+            Atom::unrecognized(ref atom) => atom.write(w),
+        }
+    }
+    // File range of a payload that stays in the source file.
+    pub fn source_range(&self) -> Option<(u64, u64)> {
+        match *self {
+            Atom::mdat(ref atom)         => Some((atom.data_offset(), atom.data_size())),
+            Atom::free(ref atom)         => atom.source_range(),
+            Atom::skip(ref atom)         => atom.source_range(),
+            Atom::ignore(ref atom)       => atom.source_range(),
+            Atom::unrecognized(ref atom) => atom.source_range(),
+            _ => None
+        }
+    }
+    pub fn write_children<W: Write>(children: &[Atom], w: &mut W) -> Result<(), Mp4Error> {
+        for atom in children {
+            atom.write(w)?;
+        }
+        Ok(())
+    }
+    pub fn kind(&self) -> &Kind {
+        &self.header().kind
+    }
+    // Child boxes of a container box, `None` for leaf boxes.
+    pub fn children(&self) -> Option<&Vec<Atom>> {
+        match *self {
+            Atom::moov(ref atom) => Some(atom.children()),
+            Atom::trak(ref atom) => Some(atom.children()),
+            Atom::edts(ref atom) => Some(atom.children()),
+            Atom::mdia(ref atom) => Some(atom.children()),
+            Atom::minf(ref atom) => Some(atom.children()),
+            Atom::stbl(ref atom) => Some(atom.children()),
+            Atom::mvex(ref atom) => Some(atom.children()),
+            Atom::moof(ref atom) => Some(atom.children()),
+            Atom::traf(ref atom) => Some(atom.children()),
+            Atom::mfra(ref atom) => Some(atom.children()),
+            Atom::meco(ref atom) => Some(atom.children()),
+            _ => None
+        }
+    }
+    pub fn children_mut(&mut self) -> Option<&mut Vec<Atom>> {
+        match *self {
+            Atom::moov(ref mut atom) => Some(atom.children_mut()),
+            Atom::trak(ref mut atom) => Some(atom.children_mut()),
+            Atom::edts(ref mut atom) => Some(atom.children_mut()),
+            Atom::mdia(ref mut atom) => Some(atom.children_mut()),
+            Atom::minf(ref mut atom) => Some(atom.children_mut()),
+            Atom::stbl(ref mut atom) => Some(atom.children_mut()),
+            Atom::mvex(ref mut atom) => Some(atom.children_mut()),
+            Atom::moof(ref mut atom) => Some(atom.children_mut()),
+            Atom::traf(ref mut atom) => Some(atom.children_mut()),
+            Atom::mfra(ref mut atom) => Some(atom.children_mut()),
+            Atom::meco(ref mut atom) => Some(atom.children_mut()),
+            _ => None
+        }
+    }
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>) -> Result<Self, Mp4Error> {
+        let header = Header::parse(f)?;
+        Atom::parse_with_header(f, header, false)
+    }
+    // Boxes that are not parsed keep their payload in the source file at the top level,
+    // and in memory inside containers, which are written from memory.
+    fn parse_with_header<R: Read + Seek>(f: &mut Mp4File<R>, header: Header, top_level: bool) -> Result<Self, Mp4Error> {
+        let kind = header.kind.clone();
+        let offset = header.offset;
+        let end = header.end()?;
+        let size = header.atom_size;
+        let atom = Atom::parse_body(f, header, top_level).map_err(|e| e.at(&kind, offset))?;
+        // Leave the reader at the end of the box, whatever the body parser consumed.
+        let curr_offset = f.offset();
+        if curr_offset > end {
+            return Err(Mp4Error::InvalidSize { kind, offset, size });
+        } else if curr_offset < end {
+            f.offset_inc(end - curr_offset);
+        }
+        f.seek(end)?;
+        Ok(atom)
+    }
+    fn parse_body<R: Read + Seek>(f: &mut Mp4File<R>, header: Header, top_level: bool) -> Result<Self, Mp4Error> {
         // println!("DO: \n{:?}", header);
         let data = match header.kind {
-            Kind::bxml => Ok(Atom::bxml(Bxml::parse(f, header).unwrap())),
-            Kind::co64 => Ok(Atom::co64(Co64::parse(f, header).unwrap())),
-            Kind::cslg => Ok(Atom::cslg(Cslg::parse(f, header).unwrap())),
+            Kind::av1C => Atom::av1C(Av1c::parse(f, header)?),
+            Kind::avcC => Atom::avcC(Avcc::parse(f, header)?),
+            Kind::bxml => Atom::bxml(Bxml::parse(f, header)?),
+            Kind::co64 => Atom::co64(Co64::parse(f, header)?),
+            Kind::cslg => Atom::cslg(Cslg::parse(f, header)?),
             // Kind::cprt => ,
-            Kind::ctts => Ok(Atom::ctts(Ctts::parse(f, header).unwrap())),
+            Kind::ctts => Atom::ctts(Ctts::parse(f, header)?),
             // Kind::dinf => ,
             // Kind::dref => ,
-            // Kind::edts => ,
-            // Kind::elst => ,
+            Kind::edts => Atom::edts(Edts::parse(f, header)?),
+            Kind::elst => Atom::elst(Elst::parse(f, header)?),
+            Kind::esds => Atom::esds(Esds::parse(f, header)?),
             // Kind::fecr => ,
             // Kind::fiin => ,
             // Kind::fpar => ,
-            Kind::free => Ok(Atom::free(Free::parse(f, header).unwrap())),
+            Kind::free => Atom::free(Free::parse(f, header, top_level)?),
             // Kind::frma => ,
-            Kind::ftyp => Ok(Atom::ftyp(Ftyp::parse(f, header).unwrap())),
-            Kind::hdlr => Ok(Atom::hdlr(Hdlr::parse(f, header).unwrap())),
-            Kind::hmhd => Ok(Atom::hmhd(Hmhd::parse(f, header).unwrap())),
+            Kind::ftyp => Atom::ftyp(Ftyp::parse(f, header)?),
+            Kind::hdlr => Atom::hdlr(Hdlr::parse(f, header)?),
+            Kind::hmhd => Atom::hmhd(Hmhd::parse(f, header)?),
+            Kind::hvcC => Atom::hvcC(Hvcc::parse(f, header)?),
             // Kind::iinf => ,
             // Kind::iloc => ,
             // Kind::imif => ,
             // Kind::ipmc => ,
             // Kind::ipro => ,
             // Kind::itn  => ,
-            Kind::mdat => Ok(Atom::mdat(Mdat::parse(f, header).unwrap())),
-            Kind::mdhd => Ok(Atom::mdhd(Mdhd::parse(f, header).unwrap())),
-            Kind::mdia => Ok(Atom::mdia(Mdia::parse(f, header).unwrap())),
-            Kind::meco => Ok(Atom::meco(Meco::parse(f, header).unwrap())),
-            Kind::mehd => Ok(Atom::mehd(Mehd::parse(f, header).unwrap())),
-            Kind::mere => Ok(Atom::mere(Mere::parse(f, header).unwrap())),
-            Kind::meta => Ok(Atom::meta(Meta::parse(f, header).unwrap())),
-            Kind::mfhd => Ok(Atom::mfhd(Mfhd::parse(f, header).unwrap())),
-            Kind::mfra => Ok(Atom::mfra(Mfra::parse(f, header).unwrap())),
-            Kind::mfro => Ok(Atom::mfro(Mfro::parse(f, header).unwrap())),
-            Kind::minf => Ok(Atom::minf(Minf::parse(f, header).unwrap())),
-            Kind::moof => Ok(Atom::moof(Moof::parse(f, header).unwrap())),
-            Kind::moov => Ok(Atom::moov(Moov::parse(f, header).unwrap())),
-            Kind::mvex => Ok(Atom::mvex(Mvex::parse(f, header).unwrap())),
-            Kind::mvhd => Ok(Atom::mvhd(Mvhd::parse(f, header).unwrap())),
-            Kind::nmhd => Ok(Atom::nmhd(Nmhd::parse(f, header).unwrap())),
-            Kind::padb => Ok(Atom::padb(Padb::parse(f, header).unwrap())),
+            Kind::mdat => Atom::mdat(Mdat::parse(f, header)?),
+            Kind::mdhd => Atom::mdhd(Mdhd::parse(f, header)?),
+            Kind::mdia => Atom::mdia(Mdia::parse(f, header)?),
+            Kind::meco => Atom::meco(Meco::parse(f, header)?),
+            Kind::mehd => Atom::mehd(Mehd::parse(f, header)?),
+            Kind::mere => Atom::mere(Mere::parse(f, header)?),
+            Kind::meta => Atom::meta(Meta::parse(f, header)?),
+            Kind::mfhd => Atom::mfhd(Mfhd::parse(f, header)?),
+            Kind::mfra => Atom::mfra(Mfra::parse(f, header)?),
+            Kind::mfro => Atom::mfro(Mfro::parse(f, header)?),
+            Kind::minf => Atom::minf(Minf::parse(f, header)?),
+            Kind::moof => Atom::moof(Moof::parse(f, header)?),
+            Kind::moov => Atom::moov(Moov::parse(f, header)?),
+            Kind::mvex => Atom::mvex(Mvex::parse(f, header)?),
+            Kind::mvhd => Atom::mvhd(Mvhd::parse(f, header)?),
+            Kind::nmhd => Atom::nmhd(Nmhd::parse(f, header)?),
+            Kind::padb => Atom::padb(Padb::parse(f, header)?),
             // Kind::paen => ,
-            Kind::pdin => Ok(Atom::pdin(Pdin::parse(f, header).unwrap())),
+            Kind::pdin => Atom::pdin(Pdin::parse(f, header)?),
             // Kind::pitm => ,
             // Kind::sbgp => ,
             // Kind::schi => ,
             // Kind::schm => ,
-            Kind::sdtp => Ok(Atom::sdtp(Sdtp::parse(f, header).unwrap())),
+            Kind::sdtp => Atom::sdtp(Sdtp::parse(f, header)?),
             // Kind::sgpd => ,
             // Kind::sinf => ,
-            Kind::skip => Ok(Atom::skip(Skip::parse(f, header).unwrap())),
-            Kind::smhd => Ok(Atom::smhd(Smhd::parse(f, header).unwrap())),
-            Kind::stbl => Ok(Atom::stbl(Stbl::parse(f, header).unwrap())),
-            Kind::stco => Ok(Atom::stco(Stco::parse(f, header).unwrap())),
-            Kind::stdp => Ok(Atom::stdp(Stdp::parse(f, header).unwrap())),
-            Kind::stsc => Ok(Atom::stsc(Stsc::parse(f, header).unwrap())),
-            Kind::stsd => Ok(Atom::stsd(Stsd::parse(f, header).unwrap())),
-            Kind::stsh => Ok(Atom::stsh(Stsh::parse(f, header).unwrap())),
-            Kind::stss => Ok(Atom::stss(Stss::parse(f, header).unwrap())),
-            Kind::stsz => Ok(Atom::stsz(Stsz::parse(f, header).unwrap())),
-            Kind::stts => Ok(Atom::stts(Stts::parse(f, header).unwrap())),
-            Kind::stz2 => Ok(Atom::stz2(Stz2::parse(f, header).unwrap())),
+            Kind::skip => Atom::skip(Skip::parse(f, header, top_level)?),
+            Kind::smhd => Atom::smhd(Smhd::parse(f, header)?),
+            Kind::stbl => Atom::stbl(Stbl::parse(f, header)?),
+            Kind::stco => Atom::stco(Stco::parse(f, header)?),
+            Kind::stdp => Atom::stdp(Stdp::parse(f, header)?),
+            Kind::stsc => Atom::stsc(Stsc::parse(f, header)?),
+            Kind::stsd => Atom::stsd(Stsd::parse(f, header)?),
+            Kind::stsh => Atom::stsh(Stsh::parse(f, header)?),
+            Kind::stss => Atom::stss(Stss::parse(f, header)?),
+            Kind::stsz => Atom::stsz(Stsz::parse(f, header)?),
+            Kind::stts => Atom::stts(Stts::parse(f, header)?),
+            Kind::stz2 => Atom::stz2(Stz2::parse(f, header)?),
             // Kind::subs => ,
-            Kind::tfhd => Ok(Atom::tfhd(Tfhd::parse(f, header).unwrap())),
-            Kind::tfra => Ok(Atom::tfra(Tfra::parse(f, header).unwrap())),
-            Kind::tkhd => Ok(Atom::tkhd(Tkhd::parse(f, header).unwrap())),
-            Kind::traf => Ok(Atom::traf(Traf::parse(f, header).unwrap())),
-            Kind::trak => Ok(Atom::trak(Trak::parse(f, header).unwrap())),
-            Kind::tref => Ok(Atom::tref(Tref::parse(f, header).unwrap())),
-            Kind::trex => Ok(Atom::trex(Trex::parse(f, header).unwrap())),
-            Kind::trun => Ok(Atom::trun(Trun::parse(f, header).unwrap())),
+            Kind::tfhd => Atom::tfhd(Tfhd::parse(f, header)?),
+            Kind::tfdt => Atom::tfdt(Tfdt::parse(f, header)?),
+            Kind::tfra => Atom::tfra(Tfra::parse(f, header)?),
+            Kind::tkhd => Atom::tkhd(Tkhd::parse(f, header)?),
+            Kind::traf => Atom::traf(Traf::parse(f, header)?),
+            Kind::trak => Atom::trak(Trak::parse(f, header)?),
+            Kind::tref => Atom::tref(Tref::parse(f, header)?),
+            Kind::trex => Atom::trex(Trex::parse(f, header)?),
+            Kind::trun => Atom::trun(Trun::parse(f, header)?),
             // Kind::tsel => ,
             // Kind::udta => ,
-            Kind::uuid => Ok(Atom::uuid(Uuid::parse(f, header).unwrap())),
-            Kind::vmhd => Ok(Atom::vmhd(Vmhd::parse(f, header).unwrap())),
-            Kind::xml  => Ok(Atom::xml(Xml::parse(f, header).unwrap())),
+            Kind::uuid => Atom::uuid(Uuid::parse(f, header)?),
+            Kind::vmhd => Atom::vmhd(Vmhd::parse(f, header)?),
+            Kind::vpcC => Atom::vpcC(Vpcc::parse(f, header)?),
+            Kind::xml  => Atom::xml(Xml::parse(f, header)?),
             // Kind::strk => ,
             // Kind::stri => ,
             // Kind::strd => 
 
-            Kind::Unrecognized(_) => Ok(Atom::unrecognized(Unrecognized::parse(f, header).unwrap())),
-            _ => Ok(Atom::ignore(Ignore::parse(f, header).unwrap()))
+            Kind::Unrecognized(_) => Atom::unrecognized(Unrecognized::parse(f, header, top_level)?),
+            _ => Atom::ignore(Ignore::parse(f, header, top_level)?)
         };
-        data
+        Ok(data)
+    }
+    // Parse the boxes contained in `parent`, stopping at the end of the parent box.
+    pub fn parse_children<R: Read + Seek>(f: &mut Mp4File<R>, parent: &Header) -> Result<Vec<Atom>, Mp4Error> {
+        let end = parent.end()?;
+        let mut atoms: Vec<Atom> = Vec::new();
+        while f.offset() < end {
+            let remaining = end - f.offset();
+            if remaining < 8 {
+                return Err(Mp4Error::Underrun {
+                    kind: Some(parent.kind.clone()),
+                    offset: f.offset(),
+                    remaining
+                });
+            }
+            let header = Header::parse(f)?;
+            if header.end()? > end {
+                return Err(Mp4Error::Overrun {
+                    kind  : header.kind.clone(),
+                    offset: header.offset,
+                    size  : header.atom_size,
+                    limit : end
+                });
+            }
+            atoms.push(Atom::parse_with_header(f, header, false)?);
+        }
+        if f.offset() > end {
+            return Err(parent.invalid_size());
+        }
+        Ok(atoms)
     }
-    pub fn parse_children(f: &mut Mp4File) -> Vec<Atom> {
+    // Parse the top level boxes of the file.
+    pub fn parse_top_level<R: Read + Seek>(f: &mut Mp4File<R>) -> Result<Vec<Atom>, Mp4Error> {
+        let end = f.file_size();
         let mut atoms: Vec<Atom> = Vec::new();
-        loop {
-            if f.offset() == f.file_size() {
-                break;
+        while f.offset() < end {
+            let remaining = end - f.offset();
+            if remaining < 8 {
+                return Err(Mp4Error::Underrun {
+                    kind: None,
+                    offset: f.offset(),
+                    remaining
+                });
             }
-            match Atom::parse(f) {
-                Ok(atom) => {
-                    atoms.push(atom);
-                },
-                Err(e) => {
-                    println!("[ERROR] ATOM parse error ({:?})", e);
-                    break;
-                }
+            let header = Header::parse(f)?;
+            if header.end()? > end {
+                return Err(Mp4Error::Truncated {
+                    kind  : header.kind.clone(),
+                    offset: header.offset
+                });
             }
+            atoms.push(Atom::parse_with_header(f, header, true)?);
         }
-        atoms
+        Ok(atoms)
     }
 }
-
@@ -1,4 +1,4 @@
-/**
+/*
 
 moof
     mfhd
@@ -11,7 +11,9 @@ moof
 
 **/
 
-use super::{Mp4File, Kind, Header, Atom, Sample};
+use super::{Mp4File, Kind, Header, Atom, Sample, Mp4Error};
+use std::io::{Read, Seek, Write};
+use byteorder::{BigEndian, WriteBytesExt};
 
 #[derive(Debug, Clone)]
 pub struct Moof {
@@ -20,13 +22,45 @@ pub struct Moof {
 }
 
 impl Moof {
-    pub fn parse(f: &mut Mp4File, header: Header) -> Result<Self, &'static str>{
-        let children: Vec<Atom> = Atom::parse_children(f);
+    pub fn new(children: Vec<Atom>) -> Moof {
+        Moof{
+            header: Header::new(Kind::moof),
+            children
+        }
+    }
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, header: Header) -> Result<Self, Mp4Error>{
+        let children: Vec<Atom> = Atom::parse_children(f, &header)?;
         Ok(Moof{
-            header: header,
-            children: children
+            header,
+            children
         })
     }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn children(&self) -> &Vec<Atom> {
+        &self.children
+    }
+    pub fn children_mut(&mut self) -> &mut Vec<Atom> {
+        &mut self.children
+    }
+    pub fn mfhd(&self) -> Option<&Mfhd> {
+        self.children.iter().filter_map(|atom| match *atom {
+            Atom::mfhd(ref atom) => Some(atom),
+            _ => None
+        }).next()
+    }
+    pub fn trafs(&self) -> Vec<&Traf> {
+        self.children.iter().filter_map(|atom| match *atom {
+            Atom::traf(ref atom) => Some(atom),
+            _ => None
+        }).collect()
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        let mut data: Vec<u8> = Vec::new();
+        Atom::write_children(&self.children, &mut data)?;
+        self.header.write(w, &data)
+    }
 }
 
 /**
@@ -45,18 +79,35 @@ pub struct Mfhd {
 }
 
 impl Mfhd {
-    pub fn parse(f: &mut Mp4File, mut header: Header) -> Result<Self, &'static str>{
-        header.parse_version(f);
-        header.parse_flags(f);
+    pub fn new(sequence_number: u32) -> Mfhd {
+        Mfhd{
+            header: Header::new_full(Kind::mfhd, 0, 0),
+            sequence_number
+        }
+    }
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, mut header: Header) -> Result<Self, Mp4Error>{
+        header.parse_version(f)?;
+        header.parse_flags(f)?;
         let curr_offset = f.offset();
         // f.seek(curr_offset+header.data_size);
-        let sequence_number: u32 = f.read_u32().unwrap();
+        let sequence_number: u32 = f.read_u32()?;
         f.offset_inc(header.data_size);
         Ok(Mfhd{
-            header: header,
-            sequence_number: sequence_number
+            header,
+            sequence_number
         })
     }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn sequence_number(&self) -> u32 {
+        self.sequence_number
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        let mut data: Vec<u8> = Vec::new();
+        data.write_u32::<BigEndian>(self.sequence_number)?;
+        self.header.write(w, &data)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -66,16 +117,54 @@ pub struct Traf {
 }
 
 impl Traf {
-    pub fn parse(f: &mut Mp4File, header: Header) -> Result<Self, &'static str>{
-        let children: Vec<Atom> = Atom::parse_children(f);
+    pub fn new(children: Vec<Atom>) -> Traf {
+        Traf{
+            header: Header::new(Kind::traf),
+            children
+        }
+    }
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, header: Header) -> Result<Self, Mp4Error>{
+        let children: Vec<Atom> = Atom::parse_children(f, &header)?;
         Ok(Traf{
-            header: header,
-            children: children
+            header,
+            children
         })
     }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn children(&self) -> &Vec<Atom> {
+        &self.children
+    }
+    pub fn children_mut(&mut self) -> &mut Vec<Atom> {
+        &mut self.children
+    }
+    pub fn tfhd(&self) -> Option<&Tfhd> {
+        self.children.iter().filter_map(|atom| match *atom {
+            Atom::tfhd(ref atom) => Some(atom),
+            _ => None
+        }).next()
+    }
+    pub fn truns(&self) -> Vec<&Trun> {
+        self.children.iter().filter_map(|atom| match *atom {
+            Atom::trun(ref atom) => Some(atom),
+            _ => None
+        }).collect()
+    }
+    pub fn tfdt(&self) -> Option<&Tfdt> {
+        self.children.iter().filter_map(|atom| match *atom {
+            Atom::tfdt(ref atom) => Some(atom),
+            _ => None
+        }).next()
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        let mut data: Vec<u8> = Vec::new();
+        Atom::write_children(&self.children, &mut data)?;
+        self.header.write(w, &data)
+    }
 }
 
-/**
+/*
 8.8.7 Track Fragment Header Box
 8.8.7.1 Definition
 Box Type : ‘tfhd’
@@ -129,9 +218,40 @@ aligned(8) class TrackFragmentHeaderBox extends FullBox(‘tfhd’, 0, tf_flags)
 `base_data_offset` the base offset to use when calculating data offsets
 **/
 
+/**
+    tf_flags of the Track Fragment Header Box.
+**/
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TfFlags(u32);
+
+impl TfFlags {
+    pub const BASE_DATA_OFFSET_PRESENT        : TfFlags = TfFlags(0x000001);
+    pub const SAMPLE_DESCRIPTION_INDEX_PRESENT: TfFlags = TfFlags(0x000002);
+    pub const DEFAULT_SAMPLE_DURATION_PRESENT : TfFlags = TfFlags(0x000008);
+    pub const DEFAULT_SAMPLE_SIZE_PRESENT     : TfFlags = TfFlags(0x000010);
+    pub const DEFAULT_SAMPLE_FLAGS_PRESENT    : TfFlags = TfFlags(0x000020);
+    pub const DURATION_IS_EMPTY               : TfFlags = TfFlags(0x010000);
+    pub const DEFAULT_BASE_IS_MOOF            : TfFlags = TfFlags(0x020000);
+
+    pub fn from_bits(bits: u32) -> TfFlags {
+        TfFlags(bits & 0x00FFFFFF)
+    }
+    pub fn bits(&self) -> u32 {
+        self.0
+    }
+    pub fn contains(&self, other: TfFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+    pub fn insert(&mut self, other: TfFlags) {
+        self.0 |= other.0;
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Tfhd {
     header  : Header,
+    tf_flags: TfFlags,
     track_id: u32,
     // all the following are optional fields
     base_data_offset: Option<u64>,
@@ -139,54 +259,70 @@ pub struct Tfhd {
 }
 
 impl Tfhd {
-    pub fn parse(f: &mut Mp4File, mut header: Header) -> Result<Self, &'static str>{
-        header.parse_version(f);
-        header.parse_flags(f);
-
-        let base_data_offset_present: [u8; 3] = [0x00, 0x00, 0x01]; // 0x000001
-        let sample_description_index_present: [u8; 3] = [0x00, 0x00, 0x02]; // 0x000002
-        let default_sample_duration_present: [u8; 3] = [0x00, 0x00, 0x08]; // 0x000008
-        
-        let default_sample_size_present: [u8; 3] = [0x00, 0x00, 0x10]; // 0x000010
-        let default_sample_flags_present: [u8; 3] = [0x00, 0x00, 0x20]; // 0x000020
-        let duration_is_empty: [u8; 3] = [0x01, 0x00, 0x00]; // 0x010000
-        let default_base_is_moof: [u8; 3] = [0x20, 0x00, 0x00]; //0x020000
-        
-        // let curr_offset = f.offset();
-        // f.seek(curr_offset+header.data_size);
-        let track_id: u32 = f.read_u32().unwrap();
-
-        let mut base_data_offset: Option<u64> = None;
-        let mut sample_description_index: Option<u32> = None;
-        let mut default_sample_duration: Option<u32> = None;
-        let mut default_sample_size: Option<u32> = None;
-        let mut default_sample_flags: Option<u32> = None;
-
-        if header.flags.is_some() {
-            let flags = header.flags.unwrap();
-            if flags == base_data_offset_present {
-                base_data_offset = Some(f.read_u64().unwrap());
-            } else if flags == sample_description_index_present {
-                sample_description_index = Some(f.read_u32().unwrap());
-            } else if flags == default_sample_duration_present {
-                default_sample_duration = Some(f.read_u32().unwrap());
-            } else if flags == default_sample_size_present {
-                default_sample_size = Some(f.read_u32().unwrap());
-            } else if flags == default_sample_flags_present {
-                default_sample_flags = Some(f.read_u32().unwrap());
-            } else if flags == duration_is_empty {
-
-            } else if flags == default_base_is_moof {
-
-            } else {
-                // unknow.
+    // Data offsets of a new track fragment are relative to its `moof` (default-base-is-moof);
+    // a flag is set for each default present in `defaults`.
+    pub fn new(track_id: u32, sample_description_index: Option<u32>, defaults: Sample) -> Tfhd {
+        let mut tf_flags = TfFlags::DEFAULT_BASE_IS_MOOF;
+        for &(value, flag) in &[(sample_description_index, TfFlags::SAMPLE_DESCRIPTION_INDEX_PRESENT),
+                                (defaults.duration, TfFlags::DEFAULT_SAMPLE_DURATION_PRESENT),
+                                (defaults.size, TfFlags::DEFAULT_SAMPLE_SIZE_PRESENT),
+                                (defaults.flags, TfFlags::DEFAULT_SAMPLE_FLAGS_PRESENT)] {
+            if value.is_some() {
+                tf_flags.insert(flag);
             }
         }
+        Tfhd{
+            header  : Header::new_full(Kind::tfhd, 0, tf_flags.bits()),
+            tf_flags,
+            track_id,
+            base_data_offset: None,
+            sample  : Sample {
+                composition_time_offset: None,
+                description_index      : sample_description_index,
+                ..defaults
+            }
+        }
+    }
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, mut header: Header) -> Result<Self, Mp4Error>{
+        header.parse_version(f)?;
+        header.parse_flags(f)?;
+
+        let tf_flags = TfFlags::from_bits(header.flag_bits());
+        let track_id: u32 = f.read_u32()?;
+
+        // Optional fields appear in this order, each only when its flag is set.
+        let base_data_offset = if tf_flags.contains(TfFlags::BASE_DATA_OFFSET_PRESENT) {
+            Some(f.read_u64()?)
+        } else {
+            None
+        };
+        let sample_description_index = if tf_flags.contains(TfFlags::SAMPLE_DESCRIPTION_INDEX_PRESENT) {
+            Some(f.read_u32()?)
+        } else {
+            None
+        };
+        let default_sample_duration = if tf_flags.contains(TfFlags::DEFAULT_SAMPLE_DURATION_PRESENT) {
+            Some(f.read_u32()?)
+        } else {
+            None
+        };
+        let default_sample_size = if tf_flags.contains(TfFlags::DEFAULT_SAMPLE_SIZE_PRESENT) {
+            Some(f.read_u32()?)
+        } else {
+            None
+        };
+        let default_sample_flags = if tf_flags.contains(TfFlags::DEFAULT_SAMPLE_FLAGS_PRESENT) {
+            Some(f.read_u32()?)
+        } else {
+            None
+        };
+
         f.offset_inc(header.data_size);
         Ok(Tfhd{
-            header  : header,
-            track_id: track_id,
-            base_data_offset: base_data_offset,
+            header,
+            tf_flags,
+            track_id,
+            base_data_offset,
             sample  : Sample {
                 duration: default_sample_duration,
                 size    : default_sample_size,
@@ -196,10 +332,113 @@ impl Tfhd {
             }
         })
     }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn tf_flags(&self) -> TfFlags {
+        self.tf_flags
+    }
+    pub fn track_id(&self) -> u32 {
+        self.track_id
+    }
+    pub fn base_data_offset(&self) -> Option<u64> {
+        self.base_data_offset
+    }
+    // Defaults for the samples of this track fragment; `composition_time_offset` is always `None`.
+    pub fn defaults(&self) -> &Sample {
+        &self.sample
+    }
+    // No samples for the default duration of this fragment.
+    pub fn duration_is_empty(&self) -> bool {
+        self.tf_flags.contains(TfFlags::DURATION_IS_EMPTY)
+    }
+    // Without an explicit base_data_offset, data offsets are relative to the enclosing `moof`.
+    pub fn default_base_is_moof(&self) -> bool {
+        self.tf_flags.contains(TfFlags::DEFAULT_BASE_IS_MOOF)
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        let mut data: Vec<u8> = Vec::new();
+        data.write_u32::<BigEndian>(self.track_id)?;
+        if let Some(base_data_offset) = self.base_data_offset {
+            data.write_u64::<BigEndian>(base_data_offset)?;
+        }
+        for value in &[self.sample.description_index, self.sample.duration, self.sample.size, self.sample.flags] {
+            if let Some(value) = *value {
+                data.write_u32::<BigEndian>(value)?;
+            }
+        }
+        self.header.write(w, &data)
+    }
 }
 
-
 /**
+8.8.12 Track fragment decode time
+Box Type : ‘tfdt’
+Container: Track Fragment box (‘traf’)
+Mandatory: No
+Quantity : Zero or one
+
+The Track Fragment Base Media Decode Time Box provides the absolute decode time, 
+measured on the media timeline, of the first sample in decode order in the track fragment. 
+This can be useful, for example, when performing random access in a file; 
+it is not necessary to sum the sample durations of all preceding samples in 
+previous fragments to find this value (where the sample durations are the deltas 
+in the Decoding Time to Sample Box and the sample_durations in the preceding track runs).
+
+aligned(8) class TrackFragmentBaseMediaDecodeTimeBox extends FullBox(‘tfdt’, version, 0) {
+    if (version==1) {
+        unsigned int(64) baseMediaDecodeTime;
+    } else { // version==0
+        unsigned int(32) baseMediaDecodeTime;
+    }
+}
+**/
+
+#[derive(Debug, Clone)]
+pub struct Tfdt {
+    header: Header,
+    base_media_decode_time: u64
+}
+
+impl Tfdt {
+    pub fn new(base_media_decode_time: u64) -> Tfdt {
+        Tfdt{
+            header: Header::new_full(Kind::tfdt, (base_media_decode_time > u32::MAX as u64) as u8, 0),
+            base_media_decode_time
+        }
+    }
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, mut header: Header) -> Result<Self, Mp4Error>{
+        header.parse_version(f)?;
+        header.parse_flags(f)?;
+        let base_media_decode_time: u64 = if header.check_version(1)? == 1u8 {
+            f.read_u64()?
+        } else {
+            f.read_u32()? as u64
+        };
+        f.offset_inc(header.data_size);
+        Ok(Tfdt{
+            header,
+            base_media_decode_time
+        })
+    }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn base_media_decode_time(&self) -> u64 {
+        self.base_media_decode_time
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        let mut data: Vec<u8> = Vec::new();
+        if self.header.version() == Some(1u8) {
+            data.write_u64::<BigEndian>(self.base_media_decode_time)?;
+        } else {
+            data.write_u32::<BigEndian>(self.base_media_decode_time as u32)?;
+        }
+        self.header.write(w, &data)
+    }
+}
+
+/*
 
 8.8.8.1 Definition
 Box Type : ‘trun’
@@ -264,9 +503,39 @@ aligned(8) class TrackRunBox extends FullBox(‘trun’, version, tr_flags) {
 **/
 
 
+/**
+    tr_flags of the Track Run Box.
+**/
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrFlags(u32);
+
+impl TrFlags {
+    pub const DATA_OFFSET_PRESENT                    : TrFlags = TrFlags(0x000001);
+    pub const FIRST_SAMPLE_FLAGS_PRESENT             : TrFlags = TrFlags(0x000004);
+    pub const SAMPLE_DURATION_PRESENT                : TrFlags = TrFlags(0x000100);
+    pub const SAMPLE_SIZE_PRESENT                    : TrFlags = TrFlags(0x000200);
+    pub const SAMPLE_FLAGS_PRESENT                   : TrFlags = TrFlags(0x000400);
+    pub const SAMPLE_COMPOSITION_TIME_OFFSETS_PRESENT: TrFlags = TrFlags(0x000800);
+
+    pub fn from_bits(bits: u32) -> TrFlags {
+        TrFlags(bits & 0x00FFFFFF)
+    }
+    pub fn bits(&self) -> u32 {
+        self.0
+    }
+    pub fn contains(&self, other: TrFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+    pub fn insert(&mut self, other: TrFlags) {
+        self.0 |= other.0;
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Trun {
     header: Header,
+    tr_flags: TrFlags,
     sample_count: u32,
     // the following are optional fields
     data_offset: Option<i32>,
@@ -276,76 +545,154 @@ pub struct Trun {
 }
 
 impl Trun {
-    pub fn parse(f: &mut Mp4File, mut header: Header) -> Result<Self, &'static str>{
-        header.parse_version(f);
-        header.parse_flags(f);
-        // let curr_offset = f.offset();
-
-        let data_offset_present: [u8; 3] = [0x00, 0x00, 0x01]; // 0x000001
-        let first_sample_flags_present: [u8; 3] = [0x00, 0x00, 0x04]; // 0x000004
-        let sample_duration_present: [u8; 3] = [0x00, 0x01, 0x00]; // 0x000100
-        let sample_size_present: [u8; 3] = [0x00, 0x02, 0x00]; // 0x000200
-        let sample_flags_present: [u8; 3] = [0x00, 0x04, 0x00]; // 0x000400
-        let sample_composition_time_offsets_present: [u8; 3] = [0x00, 0x08, 0x00]; // 0x000800
-
-        let sample_count: u32 = f.read_u32().unwrap();
-        let mut data_offset: Option<i32> = None;
-        let mut first_sample_flags: Option<u32> = None;
-        let mut samples: Vec<Sample> = Vec::with_capacity(sample_count as usize);
-
-        assert!(header.flags.is_some());
-
-        let flags = header.flags.unwrap();
-        if flags == data_offset_present {
-            data_offset = Some(f.read_i32().unwrap());
-        } else if flags == first_sample_flags_present {
-            first_sample_flags = Some(f.read_u32().unwrap());
+    // Every sample carries the fields present in the first one. Version 1 (signed offsets)
+    // is used only when a composition time offset is negative.
+    pub fn new(data_offset: Option<i32>, samples: Vec<Sample>) -> Trun {
+        let mut tr_flags = TrFlags::from_bits(0);
+        if data_offset.is_some() {
+            tr_flags.insert(TrFlags::DATA_OFFSET_PRESENT);
+        }
+        if let Some(first) = samples.first() {
+            for &(present, flag) in &[(first.duration.is_some(), TrFlags::SAMPLE_DURATION_PRESENT),
+                                      (first.size.is_some(), TrFlags::SAMPLE_SIZE_PRESENT),
+                                      (first.flags.is_some(), TrFlags::SAMPLE_FLAGS_PRESENT),
+                                      (first.composition_time_offset.is_some(), TrFlags::SAMPLE_COMPOSITION_TIME_OFFSETS_PRESENT)] {
+                if present {
+                    tr_flags.insert(flag);
+                }
+            }
+        }
+        let signed = samples.iter().any(|sample| sample.composition_time_offset.is_some_and(|offset| offset < 0));
+        Trun{
+            header: Header::new_full(Kind::trun, signed as u8, tr_flags.bits()),
+            tr_flags,
+            sample_count: samples.len() as u32,
+            data_offset,
+            first_sample_flags: None,
+            samples
+        }
+    }
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, mut header: Header) -> Result<Self, Mp4Error>{
+        header.parse_version(f)?;
+        header.parse_flags(f)?;
+
+        let version = header.check_version(1)?;
+        let tr_flags = TrFlags::from_bits(header.flag_bits());
+
+        let sample_count: u32 = f.read_u32()?;
+        let data_offset = if tr_flags.contains(TrFlags::DATA_OFFSET_PRESENT) {
+            Some(f.read_i32()?)
+        } else {
+            None
+        };
+        let first_sample_flags = if tr_flags.contains(TrFlags::FIRST_SAMPLE_FLAGS_PRESENT) {
+            Some(f.read_u32()?)
+        } else {
+            None
+        };
+
+        let mut record_size = 0u64;
+        for flag in &[TrFlags::SAMPLE_DURATION_PRESENT, TrFlags::SAMPLE_SIZE_PRESENT,
+                      TrFlags::SAMPLE_FLAGS_PRESENT, TrFlags::SAMPLE_COMPOSITION_TIME_OFFSETS_PRESENT] {
+            if tr_flags.contains(*flag) {
+                record_size += 4;
+            }
+        }
+        let fixed_size = 4 + 4 * (data_offset.is_some() as u64 + first_sample_flags.is_some() as u64);
+        if fixed_size + record_size * sample_count as u64 > header.data_size {
+            return Err(header.invalid_size());
+        }
+        // Without per-sample fields the box does not bound the count, so bound it
+        // by the file instead.
+        if record_size == 0 && sample_count as u64 > f.file_size() {
+            return Err(header.invalid_size());
         }
+
         // parse samples
+        let mut samples: Vec<Sample> = Vec::new();
         for _ in 0..sample_count {
-            let sample_duration = if flags == sample_duration_present {
-                Some(f.read_u32().unwrap())
+            let sample_duration = if tr_flags.contains(TrFlags::SAMPLE_DURATION_PRESENT) {
+                Some(f.read_u32()?)
             } else {
                 None
             };
-            let sample_flags = if flags == sample_flags_present {
-                Some(f.read_u32().unwrap())
+            let sample_size = if tr_flags.contains(TrFlags::SAMPLE_SIZE_PRESENT) {
+                Some(f.read_u32()?)
             } else {
                 None
             };
-            let sample_size  = if flags == sample_size_present {
-                Some(f.read_u32().unwrap())
+            let sample_flags = if tr_flags.contains(TrFlags::SAMPLE_FLAGS_PRESENT) {
+                Some(f.read_u32()?)
             } else {
                 None
             };
-            let sample_composition_time_offset = if flags == sample_composition_time_offsets_present {
-                if header.version.unwrap() == 0u8 { 
-                    Some(f.read_u32().unwrap() as i32)
+            let sample_composition_time_offset = if tr_flags.contains(TrFlags::SAMPLE_COMPOSITION_TIME_OFFSETS_PRESENT) {
+                if version == 0u8 {
+                    Some(f.read_u32()? as i32)
                 } else {
-                    Some(f.read_i32().unwrap())
+                    Some(f.read_i32()?)
                 }
             } else {
                 None
             };
-            let sample_description_index = None;
             samples.push(Sample{
                 duration: sample_duration,
-                size    : sample_flags,
+                size    : sample_size,
                 flags   : sample_flags,
                 composition_time_offset: sample_composition_time_offset,
-                description_index      : sample_description_index
+                description_index      : None
             });
         }
-        // f.seek(curr_offset+header.data_size);
-        
+
         f.offset_inc(header.data_size);
         Ok(Trun{
-            header: header,
-            sample_count: sample_count,
-            data_offset : data_offset,
+            header,
+            tr_flags,
+            sample_count,
+            data_offset,
 
-            first_sample_flags: first_sample_flags,
-            samples: samples
+            first_sample_flags,
+            samples
         })
     }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn tr_flags(&self) -> TrFlags {
+        self.tr_flags
+    }
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+    pub fn data_offset(&self) -> Option<i32> {
+        self.data_offset
+    }
+    pub fn first_sample_flags(&self) -> Option<u32> {
+        self.first_sample_flags
+    }
+    // Per-sample fields; a field is `None` when the run does not carry it.
+    pub fn samples(&self) -> &Vec<Sample> {
+        &self.samples
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        let mut data: Vec<u8> = Vec::new();
+        data.write_u32::<BigEndian>(self.samples.len() as u32)?;
+        if let Some(data_offset) = self.data_offset {
+            data.write_i32::<BigEndian>(data_offset)?;
+        }
+        if let Some(first_sample_flags) = self.first_sample_flags {
+            data.write_u32::<BigEndian>(first_sample_flags)?;
+        }
+        for sample in &self.samples {
+            for value in &[sample.duration, sample.size, sample.flags] {
+                if let Some(value) = *value {
+                    data.write_u32::<BigEndian>(value)?;
+                }
+            }
+            if let Some(offset) = sample.composition_time_offset {
+                data.write_i32::<BigEndian>(offset)?;
+            }
+        }
+        self.header.write(w, &data)
+    }
 }
@@ -2,7 +2,7 @@
 
 // Metadata container
 
-/**
+/*
 
 moov
     mvhd
@@ -111,7 +111,12 @@ moov
 use std::string::String;
 use std::mem;
 use ::Matrix;
-use super::{Mp4File, Kind, Header, Atom, Entry};
+use fixed::{Fixed16_16, UFixed16_16, Fixed8_8};
+use super::{Mp4File, Kind, Header, Atom, Entry, Stsd, Mp4Error};
+use sample_table::{SampleTable, SampleInfo};
+use timeline::Timeline;
+use std::io::{Read, Seek, Write};
+use byteorder::{BigEndian, ByteOrder, WriteBytesExt};
 
 /**
 
@@ -135,13 +140,45 @@ pub struct Moov {
 }
 
 impl Moov {
-    pub fn parse(f: &mut Mp4File, mut header: Header) -> Result<Self, &'static str>{
-        let children: Vec<Atom> = Atom::parse_children(f);
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, mut header: Header) -> Result<Self, Mp4Error>{
+        let children: Vec<Atom> = Atom::parse_children(f, &header)?;
         Ok(Moov{
-            header: header,
-            children: children
+            header,
+            children
         })
     }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn children(&self) -> &Vec<Atom> {
+        &self.children
+    }
+    pub fn children_mut(&mut self) -> &mut Vec<Atom> {
+        &mut self.children
+    }
+    pub fn mvhd(&self) -> Option<&Mvhd> {
+        self.children.iter().filter_map(|atom| match *atom {
+            Atom::mvhd(ref atom) => Some(atom),
+            _ => None
+        }).next()
+    }
+    pub fn traks(&self) -> Vec<&Trak> {
+        self.children.iter().filter_map(|atom| match *atom {
+            Atom::trak(ref atom) => Some(atom),
+            _ => None
+        }).collect()
+    }
+    pub fn mvex(&self) -> Option<&Mvex> {
+        self.children.iter().filter_map(|atom| match *atom {
+            Atom::mvex(ref atom) => Some(atom),
+            _ => None
+        }).next()
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        let mut data: Vec<u8> = Vec::new();
+        Atom::write_children(&self.children, &mut data)?;
+        self.header.write(w, &data)
+    }
 }
 
 
@@ -214,16 +251,18 @@ pub struct Mvhd {
     timescale: u32,
     duration: u64,
 
-    rate: f64,
-    volume: f64,
+    rate: Fixed16_16,
+    volume: Fixed8_8,
+    reserved: [u8; 10],
     matrix: Matrix,
+    pre_defined: [u8; 24],
     next_track_id: u32
 }
 
 impl Mvhd {
-    pub fn parse(f: &mut Mp4File, mut header: Header) -> Result<Self, &'static str>{
-        header.parse_version(f);
-        header.parse_flags(f);
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, mut header: Header) -> Result<Self, Mp4Error>{
+        header.parse_version(f)?;
+        header.parse_flags(f)?;
 
         let curr_offset = f.offset();
 
@@ -233,61 +272,113 @@ impl Mvhd {
         let mut modification_time = 0u64;
         let mut timescale = 0u32;
         let mut duration = 0u64;
-        assert!(header.version.is_some());
-
-        if header.version.unwrap() == 1u8 {
-            creation_time = f.read_u64().unwrap();
-            modification_time = f.read_u64().unwrap();
-            timescale = f.read_u32().unwrap();
-            duration = f.read_u64().unwrap();
+        if header.check_version(1)? == 1u8 {
+            creation_time = f.read_u64()?;
+            modification_time = f.read_u64()?;
+            timescale = f.read_u32()?;
+            duration = f.read_u64()?;
             length += 28;
         } else {
             // header version == 0
-            creation_time = f.read_u32().unwrap() as u64;
-            modification_time = f.read_u32().unwrap() as u64;
-            timescale = f.read_u32().unwrap();
-            duration = f.read_u32().unwrap() as u64;
+            creation_time = f.read_u32()? as u64;
+            modification_time = f.read_u32()? as u64;
+            timescale = f.read_u32()?;
+            duration = f.read_u32()? as u64;
             length += 16;
         }
-        // fixed point 16.16 number
-        let rate = f.read_fixed_point(16, 16).unwrap(); // u32
+        let rate = Fixed16_16::from_bits(f.read_i32()?);
         length += 4;
 
-        // fixed point 8.8 number
-        let volume = f.read_fixed_point(8, 8).unwrap(); // u16
+        let volume = Fixed8_8::from_bits(f.read_i16()?);
         length += 2;
 
         // 10 Bytes reserved
+        let mut reserved = [0u8; 10];
+        for byte in reserved.iter_mut() {
+            *byte = f.read_u8()?;
+        }
         length += 10;
 
-        f.seek(curr_offset+length);
         // matrix
-        let matrix: Matrix = f.read_matrix().unwrap(); // 36 Bytes
+        let matrix: Matrix = f.read_matrix()?; // 36 Bytes
         length += 36;
 
         // 24 Bytes
+        let mut pre_defined = [0u8; 24];
+        for byte in pre_defined.iter_mut() {
+            *byte = f.read_u8()?;
+        }
         length += 24;
-        f.seek(curr_offset+length);
 
-        let next_track_id = f.read_u32().unwrap();
+        let next_track_id = f.read_u32()?;
         length += 4;
 
         f.offset_inc(length);
 
         Ok(Mvhd{
-            header: header,
-            creation_time: creation_time,
-            modification_time: modification_time,
-            timescale: timescale,
-            duration: duration,
-
-            rate: rate,
-            volume: volume,
-            matrix: matrix,
-            next_track_id: next_track_id
+            header,
+            creation_time,
+            modification_time,
+            timescale,
+            duration,
+
+            rate,
+            volume,
+            reserved,
+            matrix,
+            pre_defined,
+            next_track_id
         })
     }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        let mut data: Vec<u8> = Vec::new();
+        if self.header.version() == Some(1u8) {
+            data.write_u64::<BigEndian>(self.creation_time)?;
+            data.write_u64::<BigEndian>(self.modification_time)?;
+            data.write_u32::<BigEndian>(self.timescale)?;
+            data.write_u64::<BigEndian>(self.duration)?;
+        } else {
+            data.write_u32::<BigEndian>(self.creation_time as u32)?;
+            data.write_u32::<BigEndian>(self.modification_time as u32)?;
+            data.write_u32::<BigEndian>(self.timescale)?;
+            data.write_u32::<BigEndian>(self.duration as u32)?;
+        }
+        data.write_i32::<BigEndian>(self.rate.to_bits())?;
+        data.write_i16::<BigEndian>(self.volume.to_bits())?;
+        data.write_all(&self.reserved)?;
+        self.matrix.write(&mut data)?;
+        data.write_all(&self.pre_defined)?;
+        data.write_u32::<BigEndian>(self.next_track_id)?;
+        self.header.write(w, &data)
+    }
 
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn timescale(&self) -> u32 {
+        self.timescale
+    }
+    pub fn duration(&self) -> u64 {
+        self.duration
+    }
+    pub fn set_duration(&mut self, duration: u64) {
+        if duration > u32::MAX as u64 {
+            self.header.version = Some(1);
+        }
+        self.duration = duration;
+    }
+    pub fn rate(&self) -> Fixed16_16 {
+        self.rate
+    }
+    pub fn volume(&self) -> Fixed8_8 {
+        self.volume
+    }
+    pub fn matrix(&self) -> &Matrix {
+        &self.matrix
+    }
+    pub fn next_track_id(&self) -> u32 {
+        self.next_track_id
+    }
 }
 
 /**
@@ -326,16 +417,89 @@ pub struct Trak {
 }
 
 impl Trak {
-    pub fn parse(f: &mut Mp4File, mut header: Header) -> Result<Self, &'static str>{
-        let children: Vec<Atom> = Atom::parse_children(f);
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, mut header: Header) -> Result<Self, Mp4Error>{
+        let children: Vec<Atom> = Atom::parse_children(f, &header)?;
         Ok(Trak{
-            header: header,
-            children: children
+            header,
+            children
         })
     }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn children(&self) -> &Vec<Atom> {
+        &self.children
+    }
+    pub fn children_mut(&mut self) -> &mut Vec<Atom> {
+        &mut self.children
+    }
+    pub fn tkhd(&self) -> Option<&Tkhd> {
+        self.children.iter().filter_map(|atom| match *atom {
+            Atom::tkhd(ref atom) => Some(atom),
+            _ => None
+        }).next()
+    }
+    pub fn mdia(&self) -> Option<&Mdia> {
+        self.children.iter().filter_map(|atom| match *atom {
+            Atom::mdia(ref atom) => Some(atom),
+            _ => None
+        }).next()
+    }
+    pub fn tref(&self) -> Option<&Tref> {
+        self.children.iter().filter_map(|atom| match *atom {
+            Atom::tref(ref atom) => Some(atom),
+            _ => None
+        }).next()
+    }
+    pub fn edts(&self) -> Option<&Edts> {
+        self.children.iter().filter_map(|atom| match *atom {
+            Atom::edts(ref atom) => Some(atom),
+            _ => None
+        }).next()
+    }
+    pub fn elst(&self) -> Option<&Elst> {
+        self.edts().and_then(|edts| edts.elst())
+    }
+    pub fn stbl(&self) -> Option<&Stbl> {
+        self.mdia().and_then(|mdia| mdia.minf()).and_then(|minf| minf.stbl())
+    }
+    // Presentation timeline of the track; `movie_timescale` comes from `mvhd`.
+    pub fn timeline(&self, movie_timescale: u32) -> Result<Timeline, Mp4Error> {
+        match self.mdia().and_then(|mdia| mdia.mdhd()) {
+            Some(mdhd) => Ok(Timeline::new(self.elst(), movie_timescale, mdhd.timescale())),
+            None => Err(self.header.invalid_value("trak has no media header"))
+        }
+    }
+    pub fn sample_table(&self) -> Result<SampleTable, Mp4Error> {
+        match self.stbl() {
+            Some(stbl) => SampleTable::new(stbl),
+            None => Err(self.header.invalid_value("trak has no sample table"))
+        }
+    }
+    // Sync sample to start decoding from to show the track at `seconds` of presentation time,
+    // following the edit list; `movie_timescale` comes from `mvhd`.
+    pub fn seek(&self, seconds: f64, movie_timescale: u32) -> Result<Option<SampleInfo>, Mp4Error> {
+        let timeline = self.timeline(movie_timescale)?;
+        let time = (seconds.max(0.0) * timeline.media_timescale() as f64).round() as u64;
+        let media_time = match timeline.presentation_to_media(time) {
+            Some(media_time) => media_time,
+            // Inside an empty edit the next edit's media is shown first; past the end, the last sample.
+            None => timeline.segments().iter()
+                .filter(|segment| segment.presentation_start > time)
+                .filter_map(|segment| segment.media_time)
+                .next()
+                .unwrap_or(i64::MAX)
+        };
+        Ok(self.sample_table()?.seek(media_time))
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        let mut data: Vec<u8> = Vec::new();
+        Atom::write_children(&self.children, &mut data)?;
+        self.header.write(w, &data)
+    }
 }
 
-/**
+/*
 8.3.2.1
 
 Box Type : ‘tkhd’
@@ -445,37 +609,177 @@ extends FullBox(‘tkhd’, version, flags){
     The pixel dimensions of the images are the default values.
 **/
 
+pub const TRACK_ENABLED   : u32 = 0x000001;
+pub const TRACK_IN_MOVIE  : u32 = 0x000002;
+pub const TRACK_IN_PREVIEW: u32 = 0x000004;
+
 #[derive(Debug, Clone)]
 pub struct Tkhd {
-    header: Header
-    // creation_time: u64,
-    // modification_time: u64,
-    // track_id: u32,
-    // duration: u64, 
-
-    // layer: i16,
-    // alternate_group: i16,
-    // // fixed 8.8 value
-    // volume: f64, // {if track_is_audio 0x0100 else 0};
+    header: Header,
+    creation_time: u64,
+    modification_time: u64,
+    track_id: u32,
+    reserved: u32,
+    duration: u64,
 
-    // matrix: Matrix,
-    // // fixed-point 16.16 values
-    // width: f64,
-    // height: f64
+    reserved2: [u8; 8],
+    layer: i16,
+    alternate_group: i16,
+    volume: Fixed8_8,
+    reserved3: u16,
+    matrix: Matrix,
+    width : UFixed16_16,
+    height: UFixed16_16
 }
 
 impl Tkhd {
-    pub fn parse(f: &mut Mp4File, mut header: Header) -> Result<Self, &'static str>{
-        header.parse_version(f);
-        header.parse_flags(f);
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, mut header: Header) -> Result<Self, Mp4Error>{
+        header.parse_version(f)?;
+        header.parse_flags(f)?;
+
+        let version = header.check_version(1)?;
+        if header.data_size < if version == 1 { 92 } else { 80 } {
+            return Err(header.invalid_size());
+        }
+        let creation_time;
+        let modification_time;
+        let track_id;
+        let reserved;
+        let duration;
+        if version == 1 {
+            creation_time = f.read_u64()?;
+            modification_time = f.read_u64()?;
+            track_id = f.read_u32()?;
+            reserved = f.read_u32()?;
+            duration = f.read_u64()?;
+        } else {
+            creation_time = f.read_u32()? as u64;
+            modification_time = f.read_u32()? as u64;
+            track_id = f.read_u32()?;
+            reserved = f.read_u32()?;
+            duration = f.read_u32()? as u64;
+        }
+
+        let mut reserved2 = [0u8; 8];
+        for byte in reserved2.iter_mut() {
+            *byte = f.read_u8()?;
+        }
+        let layer = f.read_i16()?;
+        let alternate_group = f.read_i16()?;
+        let volume = Fixed8_8::from_bits(f.read_i16()?);
+        let reserved3 = f.read_u16()?;
+        let matrix: Matrix = f.read_matrix()?;
+        let width = UFixed16_16::from_bits(f.read_u32()?);
+        let height = UFixed16_16::from_bits(f.read_u32()?);
 
-        let curr_offset = f.offset();
-        f.seek(curr_offset+header.data_size);
         f.offset_inc(header.data_size);
         Ok(Tkhd{
-            header: header
+            header,
+            creation_time,
+            modification_time,
+            track_id,
+            reserved,
+            duration,
+
+            reserved2,
+            layer,
+            alternate_group,
+            volume,
+            reserved3,
+            matrix,
+            width,
+            height
         })
     }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        let mut data: Vec<u8> = Vec::new();
+        if self.header.version() == Some(1u8) {
+            data.write_u64::<BigEndian>(self.creation_time)?;
+            data.write_u64::<BigEndian>(self.modification_time)?;
+            data.write_u32::<BigEndian>(self.track_id)?;
+            data.write_u32::<BigEndian>(self.reserved)?;
+            data.write_u64::<BigEndian>(self.duration)?;
+        } else {
+            data.write_u32::<BigEndian>(self.creation_time as u32)?;
+            data.write_u32::<BigEndian>(self.modification_time as u32)?;
+            data.write_u32::<BigEndian>(self.track_id)?;
+            data.write_u32::<BigEndian>(self.reserved)?;
+            data.write_u32::<BigEndian>(self.duration as u32)?;
+        }
+        data.write_all(&self.reserved2)?;
+        data.write_i16::<BigEndian>(self.layer)?;
+        data.write_i16::<BigEndian>(self.alternate_group)?;
+        data.write_i16::<BigEndian>(self.volume.to_bits())?;
+        data.write_u16::<BigEndian>(self.reserved3)?;
+        self.matrix.write(&mut data)?;
+        data.write_u32::<BigEndian>(self.width.to_bits())?;
+        data.write_u32::<BigEndian>(self.height.to_bits())?;
+        self.header.write(w, &data)
+    }
+    pub fn creation_time(&self) -> u64 {
+        self.creation_time
+    }
+    pub fn modification_time(&self) -> u64 {
+        self.modification_time
+    }
+    pub fn track_id(&self) -> u32 {
+        self.track_id
+    }
+    // Movie timescale units.
+    pub fn duration(&self) -> u64 {
+        self.duration
+    }
+    pub fn set_duration(&mut self, duration: u64) {
+        if duration > u32::MAX as u64 {
+            self.header.version = Some(1);
+        }
+        self.duration = duration;
+    }
+    pub fn layer(&self) -> i16 {
+        self.layer
+    }
+    pub fn alternate_group(&self) -> i16 {
+        self.alternate_group
+    }
+    pub fn volume(&self) -> Fixed8_8 {
+        self.volume
+    }
+    pub fn matrix(&self) -> &Matrix {
+        &self.matrix
+    }
+    pub fn width(&self) -> UFixed16_16 {
+        self.width
+    }
+    pub fn height(&self) -> UFixed16_16 {
+        self.height
+    }
+    pub fn is_enabled(&self) -> bool {
+        self.header.flag_bits() & TRACK_ENABLED != 0
+    }
+    pub fn is_in_movie(&self) -> bool {
+        self.header.flag_bits() & TRACK_IN_MOVIE != 0
+    }
+    pub fn is_in_preview(&self) -> bool {
+        self.header.flag_bits() & TRACK_IN_PREVIEW != 0
+    }
+    // Clockwise rotation in degrees (0, 90, 180 or 270) from the matrix.
+    pub fn rotation(&self) -> u16 {
+        self.matrix.rotation()
+    }
+    pub fn is_flipped(&self) -> bool {
+        self.matrix.is_flipped()
+    }
+    // Width and height as displayed, i.e. swapped when the track is rotated by 90 or 270 degrees.
+    pub fn display_size(&self) -> (f64, f64) {
+        let (width, height) = (self.width.to_f64(), self.height.to_f64());
+        match self.rotation() {
+            90 | 270 => (height, width),
+            _ => (width, height)
+        }
+    }
 }
 
 /**
@@ -510,20 +814,81 @@ The `reference_type` shall be set to one of the following values, or a value reg
 
 **/
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrackReference {
+    reference_type: String,
+    track_ids: Vec<u32>
+}
+
+impl TrackReference {
+    pub fn reference_type(&self) -> &str {
+        &self.reference_type
+    }
+    pub fn track_ids(&self) -> &[u32] {
+        &self.track_ids
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Tref {
-    header: Header
+    header: Header,
+    references: Vec<TrackReference>
 }
 
 impl Tref {
-    pub fn parse(f: &mut Mp4File, header: Header) -> Result<Self, &'static str>{
-        let curr_offset = f.offset();
-        f.seek(curr_offset+header.data_size);
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, header: Header) -> Result<Self, Mp4Error>{
+        let data = f.read_bytes(header.data_size)?;
+        let mut references = Vec::new();
+        let mut rest = &data[..];
+        while !rest.is_empty() {
+            if rest.len() < 8 {
+                return Err(header.invalid_size());
+            }
+            let size = BigEndian::read_u32(rest) as usize;
+            if size < 8 || size > rest.len() || !(size - 8).is_multiple_of(4) {
+                return Err(header.invalid_size());
+            }
+            let reference_type = String::from_utf8(rest[4..8].to_vec())
+                .map_err(|_| header.invalid_value("reference_type is not valid UTF-8"))?;
+            references.push(TrackReference {
+                reference_type,
+                track_ids: rest[8..size].chunks(4).map(BigEndian::read_u32).collect()
+            });
+            rest = &rest[size..];
+        }
         f.offset_inc(header.data_size);
         Ok(Tref{
-            header: header
+            header,
+            references
         })
     }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn references(&self) -> &[TrackReference] {
+        &self.references
+    }
+    // Drop `track_id` from every reference, and the references left without a track.
+    pub fn remove_track(&mut self, track_id: u32) {
+        for reference in self.references.iter_mut() {
+            reference.track_ids.retain(|id| *id != track_id);
+        }
+        self.references.retain(|reference| !reference.track_ids.is_empty());
+    }
+    pub fn is_empty(&self) -> bool {
+        self.references.is_empty()
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        let mut data: Vec<u8> = Vec::new();
+        for reference in &self.references {
+            data.write_u32::<BigEndian>(8 + 4 * reference.track_ids.len() as u32)?;
+            data.write_all(reference.reference_type.as_bytes())?;
+            for track_id in &reference.track_ids {
+                data.write_u32::<BigEndian>(*track_id)?;
+            }
+        }
+        self.header.write(w, &data)
+    }
 }
 
 /**
@@ -536,18 +901,241 @@ Quantity : Zero or one
 
 #[derive(Debug, Clone)]
 pub struct Trgr {
-    header: Header
+    header: Header,
+    data  : Vec<u8>
 }
 
 impl Trgr {
-    pub fn parse(f: &mut Mp4File, header: Header) -> Result<Self, &'static str>{
-        let curr_offset = f.offset();
-        f.seek(curr_offset+header.data_size);
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, header: Header) -> Result<Self, Mp4Error>{
+        let data = f.read_bytes(header.data_size)?;
         f.offset_inc(header.data_size);
         Ok(Trgr{
-            header: header
+            header,
+            data
         })
     }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        self.header.write(w, &self.data)
+    }
+}
+
+/**
+8.6.5 Edit Box
+
+Box Type : ‘edts’
+Container: Track Box (‘trak’)
+Mandatory: No
+Quantity : Zero or one
+
+An Edit Box maps the presentation time-line to the media time-line as it is stored in the file.
+The Edit Box is a container for the edit lists.
+
+**/
+
+#[derive(Debug, Clone)]
+pub struct Edts {
+    header: Header,
+    children: Vec<Atom>
+}
+
+impl Edts {
+    pub fn new(elst: Elst) -> Edts {
+        Edts{
+            header: Header::new(Kind::edts),
+            children: vec![Atom::elst(elst)]
+        }
+    }
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, header: Header) -> Result<Self, Mp4Error>{
+        let children: Vec<Atom> = Atom::parse_children(f, &header)?;
+        Ok(Edts{
+            header,
+            children
+        })
+    }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn children(&self) -> &Vec<Atom> {
+        &self.children
+    }
+    pub fn children_mut(&mut self) -> &mut Vec<Atom> {
+        &mut self.children
+    }
+    pub fn elst(&self) -> Option<&Elst> {
+        self.children.iter().filter_map(|atom| match *atom {
+            Atom::elst(ref atom) => Some(atom),
+            _ => None
+        }).next()
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        let mut data: Vec<u8> = Vec::new();
+        Atom::write_children(&self.children, &mut data)?;
+        self.header.write(w, &data)
+    }
+}
+
+/**
+8.6.6 Edit List Box
+
+Box Type : ‘elst’
+Container: Edit Box (‘edts’)
+Mandatory: No
+Quantity : Zero or one
+
+8.6.6.2 Syntax
+
+aligned(8) class EditListBox extends FullBox(‘elst’, version, 0) {
+    unsigned int(32) entry_count;
+    for (i=1; i <= entry_count; i++) {
+        if (version==1) {
+            unsigned int(64) segment_duration;
+            int(64) media_time;
+        } else { // version==0
+            unsigned int(32) segment_duration;
+            int(32) media_time;
+        }
+        int(16) media_rate_integer;
+        int(16) media_rate_fraction = 0;
+    }
+}
+
+8.6.6.3 Semantics
+
+`segment_duration` is an integer that specifies the duration of this edit segment in units of
+    the timescale in the Movie Header Box
+`media_time` is an integer containing the starting time within the media of this edit segment
+    (in media time scale units, in composition time). If this field is set to –1, it is an empty
+    edit. The last edit in a track shall never be an empty edit. Any difference between the
+    duration in the Movie Header Box, and the track’s duration is expressed as an implicit empty
+    edit at the end.
+`media_rate` specifies the relative rate at which to play the media corresponding to this edit
+    segment. If this value is 0, then the edit is specifying a ‘dwell’: the media at media-time
+    is presented for the segment-duration. Otherwise this field shall contain the value 1.
+
+**/
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ELST_Entry {
+    segment_duration   : u64,
+    media_time         : i64,
+    media_rate_integer : i16,
+    media_rate_fraction: i16
+}
+
+impl ELST_Entry {
+    pub fn new(segment_duration: u64, media_time: i64) -> ELST_Entry {
+        ELST_Entry{
+            segment_duration,
+            media_time,
+            media_rate_integer : 1,
+            media_rate_fraction: 0
+        }
+    }
+    // Movie timescale units.
+    pub fn segment_duration(&self) -> u64 {
+        self.segment_duration
+    }
+    // Media timescale units, -1 for an empty edit.
+    pub fn media_time(&self) -> i64 {
+        self.media_time
+    }
+    pub fn set_segment_duration(&mut self, segment_duration: u64) {
+        self.segment_duration = segment_duration;
+    }
+    pub fn set_media_time(&mut self, media_time: i64) {
+        self.media_time = media_time;
+    }
+    pub fn media_rate_integer(&self) -> i16 {
+        self.media_rate_integer
+    }
+    pub fn media_rate_fraction(&self) -> i16 {
+        self.media_rate_fraction
+    }
+    pub fn media_rate(&self) -> f64 {
+        self.media_rate_integer as f64 + self.media_rate_fraction as f64 / 65536.0
+    }
+    pub fn is_empty(&self) -> bool {
+        self.media_time == -1
+    }
+    pub fn is_dwell(&self) -> bool {
+        self.media_rate_integer == 0 && self.media_rate_fraction == 0
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Elst {
+    header: Header,
+    entries: Vec<ELST_Entry>
+}
+
+impl Elst {
+    pub fn new(entries: Vec<ELST_Entry>) -> Elst {
+        // Version 1 only when a field does not fit in 32 bits.
+        let large = entries.iter().any(|entry| {
+            entry.segment_duration > u32::MAX as u64
+                || entry.media_time > i32::MAX as i64 || entry.media_time < i32::MIN as i64
+        });
+        Elst{
+            header : Header::new_full(Kind::elst, large as u8, 0),
+            entries
+        }
+    }
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, mut header: Header) -> Result<Self, Mp4Error>{
+        header.parse_version(f)?;
+        header.parse_flags(f)?;
+        let version = header.check_version(1)?;
+        let entry_size = if version == 1 { 20 } else { 12 };
+
+        let entry_count = f.read_u32()? as u64;
+        if header.data_size < 4 + entry_count * entry_size {
+            return Err(header.invalid_size());
+        }
+        let mut entries: Vec<ELST_Entry> = Vec::new();
+        for _ in 0..entry_count {
+            let (segment_duration, media_time) = if version == 1 {
+                (f.read_u64()?, f.read_i64()?)
+            } else {
+                (f.read_u32()? as u64, f.read_i32()? as i64)
+            };
+            entries.push(ELST_Entry{
+                segment_duration,
+                media_time,
+                media_rate_integer : f.read_i16()?,
+                media_rate_fraction: f.read_i16()?
+            });
+        }
+
+        f.offset_inc(header.data_size);
+        Ok(Elst{
+            header,
+            entries
+        })
+    }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn entries(&self) -> &Vec<ELST_Entry> {
+        &self.entries
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        let mut data: Vec<u8> = Vec::new();
+        data.write_u32::<BigEndian>(self.entries.len() as u32)?;
+        for entry in &self.entries {
+            if self.header.version() == Some(1u8) {
+                data.write_u64::<BigEndian>(entry.segment_duration)?;
+                data.write_i64::<BigEndian>(entry.media_time)?;
+            } else {
+                data.write_u32::<BigEndian>(entry.segment_duration as u32)?;
+                data.write_i32::<BigEndian>(entry.media_time as i32)?;
+            }
+            data.write_i16::<BigEndian>(entry.media_rate_integer)?;
+            data.write_i16::<BigEndian>(entry.media_rate_fraction)?;
+        }
+        self.header.write(w, &data)
+    }
 }
 
 /**
@@ -566,13 +1154,45 @@ pub struct Mdia {
 }
 
 impl Mdia {
-    pub fn parse(f: &mut Mp4File, header: Header) -> Result<Self, &'static str>{
-        let children: Vec<Atom> = Atom::parse_children(f);
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, header: Header) -> Result<Self, Mp4Error>{
+        let children: Vec<Atom> = Atom::parse_children(f, &header)?;
         Ok(Mdia{
-            header: header,
-            children: children
+            header,
+            children
         })
     }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn children(&self) -> &Vec<Atom> {
+        &self.children
+    }
+    pub fn children_mut(&mut self) -> &mut Vec<Atom> {
+        &mut self.children
+    }
+    pub fn mdhd(&self) -> Option<&Mdhd> {
+        self.children.iter().filter_map(|atom| match *atom {
+            Atom::mdhd(ref atom) => Some(atom),
+            _ => None
+        }).next()
+    }
+    pub fn hdlr(&self) -> Option<&Hdlr> {
+        self.children.iter().filter_map(|atom| match *atom {
+            Atom::hdlr(ref atom) => Some(atom),
+            _ => None
+        }).next()
+    }
+    pub fn minf(&self) -> Option<&Minf> {
+        self.children.iter().filter_map(|atom| match *atom {
+            Atom::minf(ref atom) => Some(atom),
+            _ => None
+        }).next()
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        let mut data: Vec<u8> = Vec::new();
+        Atom::write_children(&self.children, &mut data)?;
+        self.header.write(w, &data)
+    }
 }
 
 /**
@@ -609,13 +1229,15 @@ pub struct Mdhd {
     timescale: u32,
     duration: u64,
 
-    language: String
+    pad: bool,
+    language: String,
+    pre_defined: u16
 }
 
 impl Mdhd {
-    pub fn parse(f: &mut Mp4File, mut header: Header) -> Result<Self, &'static str>{
-        header.parse_version(f);
-        header.parse_flags(f);
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, mut header: Header) -> Result<Self, Mp4Error>{
+        header.parse_version(f)?;
+        header.parse_flags(f)?;
 
         let curr_offset = f.offset();
 
@@ -625,43 +1247,88 @@ impl Mdhd {
         let mut modification_time = 0u64;
         let mut timescale = 0u32;
         let mut duration = 0u64;
-        assert!(header.version.is_some());
-
-        if header.version.unwrap() == 1u8 {
-            creation_time = f.read_u64().unwrap();
-            modification_time = f.read_u64().unwrap();
-            timescale = f.read_u32().unwrap();
-            duration = f.read_u64().unwrap();
+        if header.check_version(1)? == 1u8 {
+            creation_time = f.read_u64()?;
+            modification_time = f.read_u64()?;
+            timescale = f.read_u32()?;
+            duration = f.read_u64()?;
             length += 28;
         } else {
             // header version == 0
-            creation_time = f.read_u32().unwrap() as u64;
-            modification_time = f.read_u32().unwrap() as u64;
-            timescale = f.read_u32().unwrap();
-            duration = f.read_u32().unwrap() as u64;
+            creation_time = f.read_u32()? as u64;
+            modification_time = f.read_u32()? as u64;
+            timescale = f.read_u32()?;
+            duration = f.read_u32()? as u64;
             length += 16;
         }
 
         // 16 Bytes
         // pad: 1 Bit
         // language: 15 Bit;
-        let language = f.read_iso639_code().unwrap(); // 2 Bytes, u16
+        let code = f.read_u16()?;
+        let pad = code & 0x8000 != 0;
+        let language = [10, 5, 0].iter().map(|shift| (((code >> shift) & 0x1F) as u8 + 0x60) as char).collect();
         length += 2;
 
         // unsigned int(16) pre_defined = 0;
+        let pre_defined = f.read_u16()?;
         length += 2;
-        f.seek(curr_offset+length);
         f.offset_inc(length);
 
         Ok(Mdhd{
-            header: header,
-            creation_time: creation_time,
-            modification_time: modification_time,
-            timescale: timescale,
-            duration: duration,
-            language: language
+            header,
+            creation_time,
+            modification_time,
+            timescale,
+            duration,
+            pad,
+            language,
+            pre_defined
         })
     }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        let mut data: Vec<u8> = Vec::new();
+        if self.header.version() == Some(1u8) {
+            data.write_u64::<BigEndian>(self.creation_time)?;
+            data.write_u64::<BigEndian>(self.modification_time)?;
+            data.write_u32::<BigEndian>(self.timescale)?;
+            data.write_u64::<BigEndian>(self.duration)?;
+        } else {
+            data.write_u32::<BigEndian>(self.creation_time as u32)?;
+            data.write_u32::<BigEndian>(self.modification_time as u32)?;
+            data.write_u32::<BigEndian>(self.timescale)?;
+            data.write_u32::<BigEndian>(self.duration as u32)?;
+        }
+        // pad: 1 Bit, language: 3 * 5 Bit
+        let mut language = 0u16;
+        for c in self.language.bytes().take(3) {
+            language = (language << 5) | (c.wrapping_sub(0x60) as u16 & 0x1F);
+        }
+        if self.pad {
+            language |= 0x8000;
+        }
+        data.write_u16::<BigEndian>(language)?;
+        data.write_u16::<BigEndian>(self.pre_defined)?;
+        self.header.write(w, &data)
+    }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn timescale(&self) -> u32 {
+        self.timescale
+    }
+    pub fn duration(&self) -> u64 {
+        self.duration
+    }
+    pub fn set_duration(&mut self, duration: u64) {
+        if duration > u32::MAX as u64 {
+            self.header.version = Some(1);
+        }
+        self.duration = duration;
+    }
+    pub fn language(&self) -> &str {
+        &self.language
+    }
 }
 
 /**
@@ -695,44 +1362,73 @@ aligned(8) class HandlerBox extends FullBox(‘hdlr’, version = 0, 0) {
 #[derive(Debug, Clone)]
 pub struct Hdlr {
     header: Header,
+    pre_defined: u32,
     handler_type: String,
+    reserved: [u32; 3],
     name: String
 }
 
 impl Hdlr {
-    pub fn parse(f: &mut Mp4File, mut header: Header) -> Result<Self, &'static str>{
-        header.parse_version(f);
-        header.parse_flags(f);
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, mut header: Header) -> Result<Self, Mp4Error>{
+        header.parse_version(f)?;
+        header.parse_flags(f)?;
 
         // let curr_offset = f.offset();
         // f.seek(curr_offset+header.data_size);
         
-        let pre_defined = f.read_u32().unwrap();
+        let pre_defined = f.read_u32()?;
         // u32 = [u8, u8, u8, u8]
         let handler_type_bytes: [u8; 4] = [
-            f.read_u8().unwrap(), f.read_u8().unwrap(),
-            f.read_u8().unwrap(), f.read_u8().unwrap()
+            f.read_u8()?, f.read_u8()?,
+            f.read_u8()?, f.read_u8()?
         ];
-        let handler_type = String::from_utf8(handler_type_bytes.to_vec()).unwrap();
+        let handler_type = String::from_utf8(handler_type_bytes.to_vec())
+                .map_err(|_| header.invalid_value("handler_type is not valid UTF-8"))?;
         // reserved
-        f.read_u32().unwrap();
-        f.read_u32().unwrap();
-        f.read_u32().unwrap();
+        let reserved: [u32; 3] = [
+            f.read_u32()?, f.read_u32()?,
+            f.read_u32()?
+        ];
 
+        if header.data_size < 20 {
+            return Err(header.invalid_size());
+        }
         let name_length = header.data_size - 20;
         let mut name_bytes = Vec::new();
         for _ in 0..name_length {
-            name_bytes.push(f.read_u8().unwrap());
+            name_bytes.push(f.read_u8()?);
         }
-        let name = String::from_utf8(name_bytes).unwrap();
+        let name = String::from_utf8(name_bytes)
+                .map_err(|_| header.invalid_value("handler name is not valid UTF-8"))?;
 
         f.offset_inc(header.data_size);
         Ok(Hdlr{
-            header: header,
-            handler_type: handler_type,
-            name: name
+            header,
+            pre_defined,
+            handler_type,
+            reserved,
+            name
         })
     }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        let mut data: Vec<u8> = Vec::new();
+        data.write_u32::<BigEndian>(self.pre_defined)?;
+        data.write_all(self.handler_type.as_bytes())?;
+        for value in &self.reserved {
+            data.write_u32::<BigEndian>(*value)?;
+        }
+        data.write_all(self.name.as_bytes())?;
+        self.header.write(w, &data)
+    }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn handler_type(&self) -> &str {
+        &self.handler_type
+    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -742,13 +1438,39 @@ pub struct Minf {
 }
 
 impl Minf {
-    pub fn parse(f: &mut Mp4File, header: Header) -> Result<Self, &'static str>{
-        let children: Vec<Atom> = Atom::parse_children(f);
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, header: Header) -> Result<Self, Mp4Error>{
+        let children: Vec<Atom> = Atom::parse_children(f, &header)?;
         Ok(Minf{
-            header: header,
-            children: children
+            header,
+            children
         })
     }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn children(&self) -> &Vec<Atom> {
+        &self.children
+    }
+    pub fn children_mut(&mut self) -> &mut Vec<Atom> {
+        &mut self.children
+    }
+    pub fn smhd(&self) -> Option<&Smhd> {
+        self.children.iter().filter_map(|atom| match *atom {
+            Atom::smhd(ref atom) => Some(atom),
+            _ => None
+        }).next()
+    }
+    pub fn stbl(&self) -> Option<&Stbl> {
+        self.children.iter().filter_map(|atom| match *atom {
+            Atom::stbl(ref atom) => Some(atom),
+            _ => None
+        }).next()
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        let mut data: Vec<u8> = Vec::new();
+        Atom::write_children(&self.children, &mut data)?;
+        self.header.write(w, &data)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -759,53 +1481,78 @@ pub struct Vmhd {
 }
 
 impl Vmhd {
-    pub fn parse(f: &mut Mp4File, mut header: Header) -> Result<Self, &'static str>{
-        header.parse_version(f);
-        header.parse_flags(f);
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, mut header: Header) -> Result<Self, Mp4Error>{
+        header.parse_version(f)?;
+        header.parse_flags(f)?;
 
         let curr_offset = f.offset();
 
-        let graphicsmode = f.read_u16().unwrap();
+        let graphicsmode = f.read_u16()?;
         // red, greenm blue
         let opcolor: [u16; 3] = [
-            f.read_u16().unwrap(), f.read_u16().unwrap(),
-            f.read_u16().unwrap()
+            f.read_u16()?, f.read_u16()?,
+            f.read_u16()?
         ];
 
         f.offset_inc(8);
 
         Ok(Vmhd{
-            header: header,
-            graphicsmode: graphicsmode,
-            opcolor: opcolor
+            header,
+            graphicsmode,
+            opcolor
         })
     }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        let mut data: Vec<u8> = Vec::new();
+        data.write_u16::<BigEndian>(self.graphicsmode)?;
+        for value in &self.opcolor {
+            data.write_u16::<BigEndian>(*value)?;
+        }
+        self.header.write(w, &data)
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Smhd {
     header: Header,
-    balance: f64  // fixed-point 8.8 number
+    balance: Fixed8_8,
+    reserved: u16
 }
 
 impl Smhd {
-    pub fn parse(f: &mut Mp4File, mut header: Header) -> Result<Self, &'static str>{
-        header.parse_version(f);
-        header.parse_flags(f);
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, mut header: Header) -> Result<Self, Mp4Error>{
+        header.parse_version(f)?;
+        header.parse_flags(f)?;
 
         let curr_offset = f.offset();
 
-        let balance = f.read_fixed_point(8, 8).unwrap(); // 2 Bytes
-        // reserved
-        f.read_u16().unwrap();
+        let balance = Fixed8_8::from_bits(f.read_i16()?); // 2 Bytes
+        let reserved = f.read_u16()?;
 
         f.offset_inc(4);
 
         Ok(Smhd{
-            header: header,
-            balance: balance
+            header,
+            balance,
+            reserved
         })
     }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    // Stereo balance, -1.0 full left to 1.0 full right.
+    pub fn balance(&self) -> Fixed8_8 {
+        self.balance
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        let mut data: Vec<u8> = Vec::new();
+        data.write_i16::<BigEndian>(self.balance.to_bits())?;
+        data.write_u16::<BigEndian>(self.reserved)?;
+        self.header.write(w, &data)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -814,33 +1561,46 @@ pub struct Hmhd {
     max_pdu_size: u16,
     avg_pdu_size: u16,
     max_bitrate : u32,
-    avg_bitrate : u32
+    avg_bitrate : u32,
+    reserved: u32
 }
 
 impl Hmhd {
-    pub fn parse(f: &mut Mp4File, mut header: Header) -> Result<Self, &'static str>{
-        header.parse_version(f);
-        header.parse_flags(f);
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, mut header: Header) -> Result<Self, Mp4Error>{
+        header.parse_version(f)?;
+        header.parse_flags(f)?;
 
         let curr_offset = f.offset();
 
-        let max_pdu_size = f.read_u16().unwrap();
-        let avg_pdu_size = f.read_u16().unwrap();
-        let max_bitrate = f.read_u32().unwrap();
-        let avg_bitrate = f.read_u32().unwrap();
-        // reserved
-        f.read_u32().unwrap();
+        let max_pdu_size = f.read_u16()?;
+        let avg_pdu_size = f.read_u16()?;
+        let max_bitrate = f.read_u32()?;
+        let avg_bitrate = f.read_u32()?;
+        let reserved = f.read_u32()?;
 
         f.offset_inc(16);
 
         Ok(Hmhd{
-            header: header,
-            max_pdu_size: max_pdu_size,
-            avg_pdu_size: avg_pdu_size,
-            max_bitrate: max_bitrate,
-            avg_bitrate: avg_bitrate
+            header,
+            max_pdu_size,
+            avg_pdu_size,
+            max_bitrate,
+            avg_bitrate,
+            reserved
         })
     }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        let mut data: Vec<u8> = Vec::new();
+        data.write_u16::<BigEndian>(self.max_pdu_size)?;
+        data.write_u16::<BigEndian>(self.avg_pdu_size)?;
+        data.write_u32::<BigEndian>(self.max_bitrate)?;
+        data.write_u32::<BigEndian>(self.avg_bitrate)?;
+        data.write_u32::<BigEndian>(self.reserved)?;
+        self.header.write(w, &data)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -849,14 +1609,20 @@ pub struct Nmhd {
 }
 
 impl Nmhd {
-    pub fn parse(f: &mut Mp4File, mut header: Header) -> Result<Self, &'static str>{
-        header.parse_version(f);
-        header.parse_flags(f);
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, mut header: Header) -> Result<Self, Mp4Error>{
+        header.parse_version(f)?;
+        header.parse_flags(f)?;
 
         Ok(Nmhd{
-            header: header
+            header
         })
     }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        self.header.write(w, &[])
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -866,13 +1632,81 @@ pub struct Stbl {
 }
 
 impl Stbl {
-    pub fn parse(f: &mut Mp4File, header: Header) -> Result<Self, &'static str>{
-        let children: Vec<Atom> = Atom::parse_children(f);
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, header: Header) -> Result<Self, Mp4Error>{
+        let children: Vec<Atom> = Atom::parse_children(f, &header)?;
         Ok(Stbl{
-            header: header,
-            children: children
+            header,
+            children
         })
     }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn children(&self) -> &Vec<Atom> {
+        &self.children
+    }
+    pub fn children_mut(&mut self) -> &mut Vec<Atom> {
+        &mut self.children
+    }
+    pub fn stsd(&self) -> Option<&Stsd> {
+        self.children.iter().filter_map(|atom| match *atom {
+            Atom::stsd(ref atom) => Some(atom),
+            _ => None
+        }).next()
+    }
+    pub fn stts(&self) -> Option<&Stts> {
+        self.children.iter().filter_map(|atom| match *atom {
+            Atom::stts(ref atom) => Some(atom),
+            _ => None
+        }).next()
+    }
+    pub fn ctts(&self) -> Option<&Ctts> {
+        self.children.iter().filter_map(|atom| match *atom {
+            Atom::ctts(ref atom) => Some(atom),
+            _ => None
+        }).next()
+    }
+    pub fn stsc(&self) -> Option<&Stsc> {
+        self.children.iter().filter_map(|atom| match *atom {
+            Atom::stsc(ref atom) => Some(atom),
+            _ => None
+        }).next()
+    }
+    pub fn stsz(&self) -> Option<&Stsz> {
+        self.children.iter().filter_map(|atom| match *atom {
+            Atom::stsz(ref atom) => Some(atom),
+            _ => None
+        }).next()
+    }
+    pub fn stz2(&self) -> Option<&Stz2> {
+        self.children.iter().filter_map(|atom| match *atom {
+            Atom::stz2(ref atom) => Some(atom),
+            _ => None
+        }).next()
+    }
+    pub fn stco(&self) -> Option<&Stco> {
+        self.children.iter().filter_map(|atom| match *atom {
+            Atom::stco(ref atom) => Some(atom),
+            _ => None
+        }).next()
+    }
+    pub fn co64(&self) -> Option<&Co64> {
+        self.children.iter().filter_map(|atom| match *atom {
+            Atom::co64(ref atom) => Some(atom),
+            _ => None
+        }).next()
+    }
+    pub fn stss(&self) -> Option<&Stss> {
+        self.children.iter().filter_map(|atom| match *atom {
+            Atom::stss(ref atom) => Some(atom),
+            _ => None
+        }).next()
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        let mut data: Vec<u8> = Vec::new();
+        Atom::write_children(&self.children, &mut data)?;
+        self.header.write(w, &data)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -884,20 +1718,36 @@ pub struct Stsz {
 }
 
 impl Stsz {
-    pub fn parse(f: &mut Mp4File, mut header: Header) -> Result<Self, &'static str>{
-        header.parse_version(f);
-        header.parse_flags(f);
+    // Sizes are written as a single sample_size when they are all the same.
+    pub fn new(sizes: Vec<u32>) -> Stsz {
+        let constant = match sizes.first() {
+            Some(&size) if sizes.iter().all(|other| *other == size) => size,
+            _ => 0
+        };
+        Stsz{
+            header: Header::new_full(Kind::stsz, 0, 0),
+            sample_size: constant,
+            sample_count: sizes.len() as u32,
+            entry_size: if constant == 0 { Some(sizes) } else { None }
+        }
+    }
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, mut header: Header) -> Result<Self, Mp4Error>{
+        header.parse_version(f)?;
+        header.parse_flags(f)?;
 
         // let curr_offset = f.offset();
         // f.seek(curr_offset+header.data_size);
-        let sample_size : u32 = f.read_u32().unwrap();
-        let sample_count: u32 = f.read_u32().unwrap();
+        let sample_size : u32 = f.read_u32()?;
+        let sample_count: u32 = f.read_u32()?;
+        if sample_size == 0 && header.data_size < 8 + sample_count as u64 * 4 {
+            return Err(header.invalid_size());
+        }
         let mut entry_size = None;
 
         if sample_size == 0u32 {
             let mut _entry_size: Vec<u32> = Vec::new();
             for _ in 0..sample_count {
-                _entry_size.push(f.read_u32().unwrap());
+                _entry_size.push(f.read_u32()?);
             }
             entry_size = Some(_entry_size);
         }
@@ -905,12 +1755,35 @@ impl Stsz {
         f.offset_inc(header.data_size);
 
         Ok(Stsz{
-            header: header,
-            sample_size: sample_size,
-            sample_count: sample_count,
-            entry_size: entry_size
+            header,
+            sample_size,
+            sample_count,
+            entry_size
         })
     }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn sample_size(&self) -> u32 {
+        self.sample_size
+    }
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+    pub fn entry_size(&self) -> Option<&Vec<u32>> {
+        self.entry_size.as_ref()
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        let mut data: Vec<u8> = Vec::new();
+        data.write_u32::<BigEndian>(self.sample_size)?;
+        data.write_u32::<BigEndian>(self.sample_count)?;
+        if let Some(ref entry_size) = self.entry_size {
+            for size in entry_size {
+                data.write_u32::<BigEndian>(*size)?;
+            }
+        }
+        self.header.write(w, &data)
+    }
 }
 
 /**
@@ -944,16 +1817,23 @@ pub struct Stz2 {
 }
 
 impl Stz2 {
-    pub fn parse(f: &mut Mp4File, mut header: Header) -> Result<Self, &'static str>{
-        header.parse_version(f);
-        header.parse_flags(f);
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, mut header: Header) -> Result<Self, Mp4Error>{
+        header.parse_version(f)?;
+        header.parse_flags(f)?;
         // let curr_offset = f.offset();
         // f.seek(curr_offset+header.data_size);
-        let _ = f.read_u32().unwrap();
-        let field_size = f.read_u8().unwrap();
-        let sample_count = f.read_u32().unwrap();
+        // unsigned int(24) reserved = 0;
+        let _ = f.read_u16()?;
+        let _ = f.read_u8()?;
+        let field_size = f.read_u8()?;
+        let sample_count = f.read_u32()?;
         // value 4, 8 or 16.
-        assert!(field_size == 4u8 || field_size == 8u8 || field_size == 16u8);
+        if field_size != 4u8 && field_size != 8u8 && field_size != 16u8 {
+            return Err(header.invalid_value("stz2 field_size must be 4, 8 or 16"));
+        }
+        if header.data_size < 8 + (sample_count as u64 * field_size as u64).div_ceil(8) {
+            return Err(header.invalid_size());
+        }
 
         let mut entry_size: Vec<u32> = Vec::new();
 
@@ -961,32 +1841,64 @@ impl Stz2 {
 
         for _ in 0..sample_count {
             if field_size == 4u8 {
-                if next_val.is_some() {
-                    entry_size.push(next_val.unwrap());
+                if let Some(val) = next_val {
+                    entry_size.push(val);
                     next_val = None;
                 } else {
-                    let bits = format!("{:08b}", f.read_u8().unwrap());
-                    entry_size.push(u32::from_str_radix(&bits[0..4], 2).unwrap());
-                    next_val = Some(u32::from_str_radix(&bits[4..8], 2).unwrap());
+                    let byte = f.read_u8()?;
+                    entry_size.push((byte >> 4) as u32);
+                    next_val = Some((byte & 0x0F) as u32);
                 }
             } else if field_size == 8u8 {
-                entry_size.push(f.read_u8().unwrap() as u32);
-            } else if field_size == 16u8 {
-                entry_size.push(f.read_u16().unwrap() as u32);
+                entry_size.push(f.read_u8()? as u32);
             } else {
-                panic!("STZ2 parse error.");
+                entry_size.push(f.read_u16()? as u32);
             }
         }
 
 
         f.offset_inc(header.data_size);
         Ok(Stz2{
-            header: header,
-            field_size  : field_size,
-            sample_count: sample_count,
-            entry_size  : entry_size
+            header,
+            field_size,
+            sample_count,
+            entry_size
         })
     }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn field_size(&self) -> u8 {
+        self.field_size
+    }
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+    pub fn entry_size(&self) -> &Vec<u32> {
+        &self.entry_size
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        let mut data: Vec<u8> = Vec::new();
+        // reserved
+        data.write_all(&[0u8; 3])?;
+        data.write_u8(self.field_size)?;
+        data.write_u32::<BigEndian>(self.entry_size.len() as u32)?;
+        if self.field_size == 4u8 {
+            for pair in self.entry_size.chunks(2) {
+                let low = pair.get(1).cloned().unwrap_or(0);
+                data.write_u8(((pair[0] as u8 & 0x0F) << 4) | (low as u8 & 0x0F))?;
+            }
+        } else if self.field_size == 8u8 {
+            for size in &self.entry_size {
+                data.write_u8(*size as u8)?;
+            }
+        } else {
+            for size in &self.entry_size {
+                data.write_u16::<BigEndian>(*size as u16)?;
+            }
+        }
+        self.header.write(w, &data)
+    }
 }
 
 /**
@@ -1024,30 +1936,56 @@ pub struct Stsc {
 }
 
 impl Stsc {
-    pub fn parse(f: &mut Mp4File, mut header: Header) -> Result<Self, &'static str>{
-        header.parse_version(f);
-        header.parse_flags(f);
+    pub fn new(entries: Vec<Entry>) -> Stsc {
+        Stsc{
+            header     : Header::new_full(Kind::stsc, 0, 0),
+            entry_count: entries.len() as u32,
+            entries
+        }
+    }
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, mut header: Header) -> Result<Self, Mp4Error>{
+        header.parse_version(f)?;
+        header.parse_flags(f)?;
         // let curr_offset = f.offset();
         // f.seek(curr_offset+header.data_size);
 
-        let entry_count = f.read_u32().unwrap();
+        let entry_count = f.read_u32()?;
+        if header.data_size < 4 + entry_count as u64 * 12 {
+            return Err(header.invalid_size());
+        }
         let mut entries: Vec<Entry> = Vec::new();
         for _ in 0..entry_count {
             let entry = Entry {
-                first_chunk: f.read_u32().unwrap(),
-                samples_per_chunk: f.read_u32().unwrap(),
-                sample_description_index: f.read_u32().unwrap(),
+                first_chunk: f.read_u32()?,
+                samples_per_chunk: f.read_u32()?,
+                sample_description_index: f.read_u32()?,
             };
             entries.push(entry);
         }
 
         f.offset_inc(header.data_size);
         Ok(Stsc{
-            header     : header,
-            entry_count: entry_count,
-            entries    : entries
+            header,
+            entry_count,
+            entries
         })
     }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn entries(&self) -> &Vec<Entry> {
+        &self.entries
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        let mut data: Vec<u8> = Vec::new();
+        data.write_u32::<BigEndian>(self.entries.len() as u32)?;
+        for entry in &self.entries {
+            data.write_u32::<BigEndian>(entry.first_chunk)?;
+            data.write_u32::<BigEndian>(entry.samples_per_chunk)?;
+            data.write_u32::<BigEndian>(entry.sample_description_index)?;
+        }
+        self.header.write(w, &data)
+    }
 }
 
 /**
@@ -1102,26 +2040,53 @@ pub struct Stco {
 }
 
 impl Stco {
-    pub fn parse(f: &mut Mp4File, mut header: Header) -> Result<Self, &'static str>{
-        header.parse_version(f);
-        header.parse_flags(f);
+    pub fn new(chunks: Vec<u32>) -> Stco {
+        Stco{
+            header     : Header::new_full(Kind::stco, 0, 0),
+            entry_count: chunks.len() as u32,
+            chunks
+        }
+    }
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, mut header: Header) -> Result<Self, Mp4Error>{
+        header.parse_version(f)?;
+        header.parse_flags(f)?;
         // let curr_offset = f.offset();
         // f.seek(curr_offset+header.data_size);
 
-        let entry_count = f.read_u32().unwrap();
+        let entry_count = f.read_u32()?;
+        if header.data_size < 4 + entry_count as u64 * 4 {
+            return Err(header.invalid_size());
+        }
         let mut chunks: Vec<u32> = Vec::new();
 
         for _ in 0..entry_count {
-            chunks.push(f.read_u32().unwrap());
+            chunks.push(f.read_u32()?);
         }
 
         f.offset_inc(header.data_size);
         Ok(Stco{
-            header     : header,
-            entry_count: entry_count,
-            chunks     : chunks
+            header,
+            entry_count,
+            chunks
         })
     }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn chunks(&self) -> &Vec<u32> {
+        &self.chunks
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        let mut data: Vec<u8> = Vec::new();
+        data.write_u32::<BigEndian>(self.chunks.len() as u32)?;
+        for offset in &self.chunks {
+            data.write_u32::<BigEndian>(*offset)?;
+        }
+        self.header.write(w, &data)
+    }
+    pub fn chunks_mut(&mut self) -> &mut Vec<u32> {
+        &mut self.chunks
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -1132,26 +2097,53 @@ pub struct Co64 {
 }
 
 impl Co64 {
-    pub fn parse(f: &mut Mp4File, mut header: Header) -> Result<Self, &'static str>{
-        header.parse_version(f);
-        header.parse_flags(f);
+    pub fn new(chunks: Vec<u64>) -> Co64 {
+        Co64{
+            header     : Header::new_full(Kind::co64, 0, 0),
+            entry_count: chunks.len() as u32,
+            chunks
+        }
+    }
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, mut header: Header) -> Result<Self, Mp4Error>{
+        header.parse_version(f)?;
+        header.parse_flags(f)?;
         // let curr_offset = f.offset();
         // f.seek(curr_offset+header.data_size);
         
-        let entry_count = f.read_u32().unwrap();
+        let entry_count = f.read_u32()?;
+        if header.data_size < 4 + entry_count as u64 * 8 {
+            return Err(header.invalid_size());
+        }
         let mut chunks: Vec<u64> = Vec::new();
 
         for _ in 0..entry_count {
-            chunks.push(f.read_u64().unwrap());
+            chunks.push(f.read_u64()?);
         }
 
         f.offset_inc(header.data_size);
         Ok(Co64{
-            header     : header,
-            entry_count: entry_count,
-            chunks     : chunks
+            header,
+            entry_count,
+            chunks
         })
     }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn chunks(&self) -> &Vec<u64> {
+        &self.chunks
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        let mut data: Vec<u8> = Vec::new();
+        data.write_u32::<BigEndian>(self.chunks.len() as u32)?;
+        for offset in &self.chunks {
+            data.write_u64::<BigEndian>(*offset)?;
+        }
+        self.header.write(w, &data)
+    }
+    pub fn chunks_mut(&mut self) -> &mut Vec<u64> {
+        &mut self.chunks
+    }
 }
 
 /**
@@ -1192,66 +2184,69 @@ aligned(8) class PaddingBitsBox extends FullBox(‘padb’, version = 0, 0) {
 #[derive(Debug, Clone)]
 pub struct Padb {
     header: Header,
-    sample_count: u32
+    sample_count: u32,
+    data: Vec<u8>  // packed pad1/pad2 bits
 }
 
 impl Padb {
-    pub fn parse(f: &mut Mp4File, mut header: Header) -> Result<Self, &'static str>{
-        header.parse_version(f);
-        header.parse_flags(f);
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, mut header: Header) -> Result<Self, Mp4Error>{
+        header.parse_version(f)?;
+        header.parse_flags(f)?;
         let curr_offset = f.offset();
         
-        let sample_count = f.read_u32().unwrap();
+        let sample_count = f.read_u32()?;
         // f.offset_inc(4);
         // for i in 0..((sample_count+1)/2) {
-        //     let bits = format!("{:08b}", f.read_u8().unwrap());
+        //     let bits = format!("{:08b}", f.read_u8()?);
         //     let pad1 = u32::from_str_radix(&bits[1..4], 2).unwrap();
         //     let pad2 = u32::from_str_radix(&bits[5..8], 2).unwrap();
         // }
+        if header.data_size < 4 {
+            return Err(header.invalid_size());
+        }
+        let data = f.read_bytes(header.data_size - 4)?;
 
-        f.seek(curr_offset+header.data_size);
         f.offset_inc(header.data_size);
         Ok(Padb{
-            header      : header,
-            sample_count: sample_count
+            header,
+            sample_count,
+            data
         })
     }
-}
-
-#[derive(Debug, Clone)]
-pub struct Stsd {
-    header: Header
-}
-
-impl Stsd {
-    pub fn parse(f: &mut Mp4File, mut header: Header) -> Result<Self, &'static str>{
-        header.parse_version(f);
-        header.parse_flags(f);
-        let curr_offset = f.offset();
-        f.seek(curr_offset+header.data_size);
-        f.offset_inc(header.data_size);
-        Ok(Stsd{
-            header: header
-        })
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        let mut data: Vec<u8> = Vec::new();
+        data.write_u32::<BigEndian>(self.sample_count)?;
+        data.write_all(&self.data)?;
+        self.header.write(w, &data)
+    }
+    pub fn header(&self) -> &Header {
+        &self.header
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct Stdp {
-    header: Header
+    header: Header,
+    data  : Vec<u8>
 }
 
 impl Stdp {
-    pub fn parse(f: &mut Mp4File, mut header: Header) -> Result<Self, &'static str>{
-        header.parse_version(f);
-        header.parse_flags(f);
-        let curr_offset = f.offset();
-        f.seek(curr_offset+header.data_size);
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, mut header: Header) -> Result<Self, Mp4Error>{
+        header.parse_version(f)?;
+        header.parse_flags(f)?;
+        let data = f.read_bytes(header.data_size)?;
         f.offset_inc(header.data_size);
         Ok(Stdp{
-            header: header
+            header,
+            data
         })
     }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        self.header.write(w, &self.data)
+    }
 }
 
 /**
@@ -1316,6 +2311,21 @@ pub struct STTS_Entry {
     sample_delta: u32
 }
 
+impl STTS_Entry {
+    pub fn new(sample_count: u32, sample_delta: u32) -> STTS_Entry {
+        STTS_Entry{
+            sample_count,
+            sample_delta
+        }
+    }
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+    pub fn sample_delta(&self) -> u32 {
+        self.sample_delta
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Stts {
     header: Header,
@@ -1324,30 +2334,55 @@ pub struct Stts {
 }
 
 impl Stts {
-    pub fn parse(f: &mut Mp4File, mut header: Header) -> Result<Self, &'static str>{
-        header.parse_version(f);
-        header.parse_flags(f);
+    pub fn new(entries: Vec<STTS_Entry>) -> Stts {
+        Stts{
+            header: Header::new_full(Kind::stts, 0, 0),
+            entry_count: entries.len() as u32,
+            entries
+        }
+    }
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, mut header: Header) -> Result<Self, Mp4Error>{
+        header.parse_version(f)?;
+        header.parse_flags(f)?;
         // let curr_offset = f.offset();
         // f.seek(curr_offset+header.data_size);
-        let entry_count = f.read_u32().unwrap();
+        let entry_count = f.read_u32()?;
+        if header.data_size < 4 + entry_count as u64 * 8 {
+            return Err(header.invalid_size());
+        }
         let mut entries = Vec::new();
         
         for _ in 0..entry_count {
-            let sample_count: u32 = f.read_u32().unwrap();
-            let sample_delta: u32 = f.read_u32().unwrap();
+            let sample_count: u32 = f.read_u32()?;
+            let sample_delta: u32 = f.read_u32()?;
             entries.push(STTS_Entry{
-                sample_count: sample_count,
-                sample_delta: sample_delta
+                sample_count,
+                sample_delta
             });
         }
 
         f.offset_inc(header.data_size);
         Ok(Stts{
-            header: header,
-            entry_count: entry_count,
-            entries: entries
+            header,
+            entry_count,
+            entries
         })
     }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn entries(&self) -> &Vec<STTS_Entry> {
+        &self.entries
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        let mut data: Vec<u8> = Vec::new();
+        data.write_u32::<BigEndian>(self.entries.len() as u32)?;
+        for entry in &self.entries {
+            data.write_u32::<BigEndian>(entry.sample_count)?;
+            data.write_u32::<BigEndian>(entry.sample_delta)?;
+        }
+        self.header.write(w, &data)
+    }
 }
 
 /**
@@ -1449,6 +2484,21 @@ pub struct CTTS_Entry_Offset {
     sample_offset: i32
 }
 
+impl CTTS_Entry_Offset {
+    pub fn new(sample_count: u32, sample_offset: i32) -> CTTS_Entry_Offset {
+        CTTS_Entry_Offset{
+            sample_count,
+            sample_offset
+        }
+    }
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+    pub fn sample_offset(&self) -> i32 {
+        self.sample_offset
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Ctts {
     header: Header,
@@ -1457,112 +2507,224 @@ pub struct Ctts {
 }
 
 impl Ctts {
-    pub fn parse(f: &mut Mp4File, mut header: Header) -> Result<Self, &'static str>{
-        header.parse_version(f);
-        header.parse_flags(f);
+    // Version 1 (signed offsets) only when an offset is negative.
+    pub fn new(entries: Vec<CTTS_Entry_Offset>) -> Ctts {
+        let signed = entries.iter().any(|entry| entry.sample_offset < 0);
+        Ctts{
+            header: Header::new_full(Kind::ctts, signed as u8, 0),
+            entry_count: entries.len() as u32,
+            entries
+        }
+    }
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, mut header: Header) -> Result<Self, Mp4Error>{
+        header.parse_version(f)?;
+        header.parse_flags(f)?;
         // let curr_offset = f.offset();
         // f.seek(curr_offset+header.data_size);
 
-        let version: u8 = header.version.unwrap();
+        let version: u8 = header.check_version(1)?;
 
-        let entry_count = f.read_u32().unwrap();
+        let entry_count = f.read_u32()?;
+        if header.data_size < 4 + entry_count as u64 * 8 {
+            return Err(header.invalid_size());
+        }
         let mut entries = Vec::new();
 
         for _ in 0..entry_count {
-            let sample_count: u32 = f.read_u32().unwrap();
+            let sample_count: u32 = f.read_u32()?;
             let mut sample_offset: i32 = 0;
 
             if version == 0u8 {
-                sample_offset = f.read_u32().unwrap() as i32;
+                sample_offset = f.read_u32()? as i32;
             } else {
-                sample_offset = f.read_i32().unwrap();
+                sample_offset = f.read_i32()?;
             }
 
             entries.push(CTTS_Entry_Offset{
-                sample_count: sample_count,
-                sample_offset: sample_offset
+                sample_count,
+                sample_offset
             });
         }
 
         f.offset_inc(header.data_size);
         Ok(Ctts{
-            header: header,
-            entry_count: entry_count,
-            entries: entries
+            header,
+            entry_count,
+            entries
         })
     }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn entries(&self) -> &Vec<CTTS_Entry_Offset> {
+        &self.entries
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        let mut data: Vec<u8> = Vec::new();
+        data.write_u32::<BigEndian>(self.entries.len() as u32)?;
+        for entry in &self.entries {
+            data.write_u32::<BigEndian>(entry.sample_count)?;
+            // unsigned in version 0, signed in version 1; the bits are the same.
+            data.write_i32::<BigEndian>(entry.sample_offset)?;
+        }
+        self.header.write(w, &data)
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Cslg {
-    header: Header
+    header: Header,
+    data  : Vec<u8>
 }
 
 impl Cslg {
-    pub fn parse(f: &mut Mp4File, mut header: Header) -> Result<Self, &'static str>{
-        header.parse_version(f);
-        header.parse_flags(f);
-        let curr_offset = f.offset();
-        f.seek(curr_offset+header.data_size);
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, mut header: Header) -> Result<Self, Mp4Error>{
+        header.parse_version(f)?;
+        header.parse_flags(f)?;
+        let data = f.read_bytes(header.data_size)?;
         f.offset_inc(header.data_size);
         Ok(Cslg{
-            header: header
+            header,
+            data
         })
     }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        self.header.write(w, &self.data)
+    }
 }
 
+/**
+8.6.2 Sync Sample Box
+Box Type : `stss`
+Container: Sample Table Box (‘stbl’)
+Mandatory: No
+Quantity : Zero or one
+
+This box provides a compact marking of the sync samples within the stream. 
+The table is arranged in strictly increasing order of sample number.
+If the sync sample box is not present, every sample is a sync sample.
+
+8.6.2.2 Syntax
+
+aligned(8) class SyncSampleBox extends FullBox(‘stss’, version = 0, 0) {
+    unsigned int(32)  entry_count;
+    int i;
+    for (i=0; i < entry_count; i++) {
+        unsigned int(32)  sample_number;
+    }
+}
+
+8.6.2.3 Semantics
+
+`entry_count` is an integer that gives the number of entries in the following table. 
+    If entry_count is zero, there are no sync samples within the stream 
+    and the following table is empty.
+`sample_number` gives the numbers of the samples that are sync samples in the stream.
+
+**/
+
 #[derive(Debug, Clone)]
 pub struct Stss {
-    header: Header
+    header: Header,
+    entry_count: u32,
+    entries: Vec<u32>
 }
 
 impl Stss {
-    pub fn parse(f: &mut Mp4File, mut header: Header) -> Result<Self, &'static str>{
-        header.parse_version(f);
-        header.parse_flags(f);
-        let curr_offset = f.offset();
-        f.seek(curr_offset+header.data_size);
+    pub fn new(entries: Vec<u32>) -> Stss {
+        Stss{
+            header     : Header::new_full(Kind::stss, 0, 0),
+            entry_count: entries.len() as u32,
+            entries
+        }
+    }
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, mut header: Header) -> Result<Self, Mp4Error>{
+        header.parse_version(f)?;
+        header.parse_flags(f)?;
+
+        let entry_count = f.read_u32()?;
+        if header.data_size < 4 + entry_count as u64 * 4 {
+            return Err(header.invalid_size());
+        }
+        let mut entries: Vec<u32> = Vec::new();
+        for _ in 0..entry_count {
+            entries.push(f.read_u32()?);
+        }
+
         f.offset_inc(header.data_size);
         Ok(Stss{
-            header: header
+            header,
+            entry_count,
+            entries
         })
     }
+    pub fn entries(&self) -> &Vec<u32> {
+        &self.entries
+    }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        let mut data: Vec<u8> = Vec::new();
+        data.write_u32::<BigEndian>(self.entries.len() as u32)?;
+        for number in &self.entries {
+            data.write_u32::<BigEndian>(*number)?;
+        }
+        self.header.write(w, &data)
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Stsh {
-    header: Header
+    header: Header,
+    data  : Vec<u8>
 }
 
 impl Stsh {
-    pub fn parse(f: &mut Mp4File, mut header: Header) -> Result<Self, &'static str>{
-        header.parse_version(f);
-        header.parse_flags(f);
-        let curr_offset = f.offset();
-        f.seek(curr_offset+header.data_size);
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, mut header: Header) -> Result<Self, Mp4Error>{
+        header.parse_version(f)?;
+        header.parse_flags(f)?;
+        let data = f.read_bytes(header.data_size)?;
         f.offset_inc(header.data_size);
         Ok(Stsh{
-            header: header
+            header,
+            data
         })
     }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        self.header.write(w, &self.data)
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Sdtp {
-    header: Header
+    header: Header,
+    data  : Vec<u8>
 }
 
 impl Sdtp {
-    pub fn parse(f: &mut Mp4File, mut header: Header) -> Result<Self, &'static str>{
-        header.parse_version(f);
-        header.parse_flags(f);
-        let curr_offset = f.offset();
-        f.seek(curr_offset+header.data_size);
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, mut header: Header) -> Result<Self, Mp4Error>{
+        header.parse_version(f)?;
+        header.parse_flags(f)?;
+        let data = f.read_bytes(header.data_size)?;
         f.offset_inc(header.data_size);
         Ok(Sdtp{
-            header: header
+            header,
+            data
         })
     }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        self.header.write(w, &self.data)
+    }
 }
 
 
@@ -1573,13 +2735,48 @@ pub struct Mvex {
 }
 
 impl Mvex {
-    pub fn parse(f: &mut Mp4File, header: Header) -> Result<Self, &'static str>{
-        let children: Vec<Atom> = Atom::parse_children(f);
+    pub fn new(children: Vec<Atom>) -> Mvex {
+        Mvex{
+            header: Header::new(Kind::mvex),
+            children
+        }
+    }
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, header: Header) -> Result<Self, Mp4Error>{
+        let children: Vec<Atom> = Atom::parse_children(f, &header)?;
         Ok(Mvex{
-            header: header,
-            children: children
+            header,
+            children
         })
     }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn children(&self) -> &Vec<Atom> {
+        &self.children
+    }
+    pub fn children_mut(&mut self) -> &mut Vec<Atom> {
+        &mut self.children
+    }
+    pub fn mehd(&self) -> Option<&Mehd> {
+        self.children.iter().filter_map(|atom| match *atom {
+            Atom::mehd(ref atom) => Some(atom),
+            _ => None
+        }).next()
+    }
+    pub fn trexs(&self) -> Vec<&Trex> {
+        self.children.iter().filter_map(|atom| match *atom {
+            Atom::trex(ref atom) => Some(atom),
+            _ => None
+        }).collect()
+    }
+    pub fn trex(&self, track_id: u32) -> Option<&Trex> {
+        self.trexs().into_iter().find(|trex| trex.track_id() == track_id)
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        let mut data: Vec<u8> = Vec::new();
+        Atom::write_children(&self.children, &mut data)?;
+        self.header.write(w, &data)
+    }
 }
 
 /**
@@ -1608,40 +2805,131 @@ pub struct Mehd {
 }
 
 impl Mehd {
-    pub fn parse(f: &mut Mp4File, mut header: Header) -> Result<Self, &'static str>{
-        header.parse_version(f);
-        header.parse_flags(f);
+    pub fn new(fragment_duration: u64) -> Mehd {
+        Mehd{
+            header: Header::new_full(Kind::mehd, (fragment_duration > u32::MAX as u64) as u8, 0),
+            fragment_duration
+        }
+    }
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, mut header: Header) -> Result<Self, Mp4Error>{
+        header.parse_version(f)?;
+        header.parse_flags(f)?;
         // let curr_offset = f.offset();
         let mut fragment_duration: u64 = 0;
-        if header.version.unwrap() == 1u8 {
-            fragment_duration = f.read_u64().unwrap();
+        if header.check_version(1)? == 1u8 {
+            fragment_duration = f.read_u64()?;
         } else {
-            fragment_duration = f.read_u32().unwrap() as u64;
+            fragment_duration = f.read_u32()? as u64;
         }
         // f.seek(curr_offset+header.data_size);
         f.offset_inc(header.data_size);
         Ok(Mehd{
-            header: header,
-            fragment_duration: fragment_duration
+            header,
+            fragment_duration
         })
     }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn fragment_duration(&self) -> u64 {
+        self.fragment_duration
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        let mut data: Vec<u8> = Vec::new();
+        if self.header.version() == Some(1u8) {
+            data.write_u64::<BigEndian>(self.fragment_duration)?;
+        } else {
+            data.write_u32::<BigEndian>(self.fragment_duration as u32)?;
+        }
+        self.header.write(w, &data)
+    }
 }
 
+/**
+8.8.3 Track Extends Box
+Box Type : ‘trex’
+Container: Movie Extends Box (‘mvex’)
+Mandatory: Yes
+Quantity : Exactly one for each track in the Movie Box
+
+This sets up default values used by the movie fragments. 
+By setting defaults in this way, space and complexity can be saved in each Track Fragment Box.
+
+aligned(8) class TrackExtendsBox extends FullBox(‘trex’, 0, 0){
+    unsigned int(32) track_ID;
+    unsigned int(32) default_sample_description_index;
+    unsigned int(32) default_sample_duration;
+    unsigned int(32) default_sample_size;
+    unsigned int(32) default_sample_flags;
+}
+**/
+
 #[derive(Debug, Clone)]
 pub struct Trex {
-    header: Header
+    header: Header,
+    track_id: u32,
+    default_sample_description_index: u32,
+    default_sample_duration: u32,
+    default_sample_size    : u32,
+    default_sample_flags   : u32
 }
 
 impl Trex {
-    pub fn parse(f: &mut Mp4File, mut header: Header) -> Result<Self, &'static str>{
-        header.parse_version(f);
-        header.parse_flags(f);
-        let curr_offset = f.offset();
-        f.seek(curr_offset+header.data_size);
+    pub fn new(track_id: u32, default_sample_description_index: u32, default_sample_duration: u32,
+               default_sample_size: u32, default_sample_flags: u32) -> Trex {
+        Trex{
+            header: Header::new_full(Kind::trex, 0, 0),
+            track_id,
+            default_sample_description_index,
+            default_sample_duration,
+            default_sample_size,
+            default_sample_flags
+        }
+    }
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, mut header: Header) -> Result<Self, Mp4Error>{
+        header.parse_version(f)?;
+        header.parse_flags(f)?;
+        let track_id: u32 = f.read_u32()?;
+        let default_sample_description_index: u32 = f.read_u32()?;
+        let default_sample_duration: u32 = f.read_u32()?;
+        let default_sample_size: u32 = f.read_u32()?;
+        let default_sample_flags: u32 = f.read_u32()?;
         f.offset_inc(header.data_size);
         Ok(Trex{
-            header: header
+            header,
+            track_id,
+            default_sample_description_index,
+            default_sample_duration,
+            default_sample_size,
+            default_sample_flags
         })
     }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn track_id(&self) -> u32 {
+        self.track_id
+    }
+    pub fn default_sample_description_index(&self) -> u32 {
+        self.default_sample_description_index
+    }
+    pub fn default_sample_duration(&self) -> u32 {
+        self.default_sample_duration
+    }
+    pub fn default_sample_size(&self) -> u32 {
+        self.default_sample_size
+    }
+    pub fn default_sample_flags(&self) -> u32 {
+        self.default_sample_flags
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        let mut data: Vec<u8> = Vec::new();
+        data.write_u32::<BigEndian>(self.track_id)?;
+        data.write_u32::<BigEndian>(self.default_sample_description_index)?;
+        data.write_u32::<BigEndian>(self.default_sample_duration)?;
+        data.write_u32::<BigEndian>(self.default_sample_size)?;
+        data.write_u32::<BigEndian>(self.default_sample_flags)?;
+        self.header.write(w, &data)
+    }
 }
 
@@ -1,6 +1,8 @@
 
 
-use super::{Mp4File, Kind, Header, Atom};
+use super::{Mp4File, Kind, Header, Atom, Mp4Error};
+use std::io::{Read, Seek, Write};
+use byteorder::{BigEndian, WriteBytesExt};
 
 /**
 BoxTypes : `pdin`
@@ -11,40 +13,56 @@ Quantity : Zero or One
 8.1.3.2 Syntax
 
 aligned(8) class ProgressiveDownloadInfoBox extends FullBox(‘pdin’, version = 0, 0) {
-	for(i=0;;i++){ //to end of box
-		unsigned int(32) rate;
-		unsigned int(32) initial_delay;
-	}
+    for(i=0;;i++){ //to end of box
+        unsigned int(32) rate;
+        unsigned int(32) initial_delay;
+    }
 }
 
 8.1.3.3 Semantics
 
 `rate` is a download rate expressed in bytes/second
 `initial_delay` is the suggested delay to use when playing the file, 
-	such that if download continues at the given rate, 
-	all data within the file will arrive in time for its use and playback should not need to stall.
+    such that if download continues at the given rate, 
+    all data within the file will arrive in time for its use and playback should not need to stall.
 
 **/
 
 #[derive(Debug, Clone)]
 pub struct Pdin {
-    header: Header,
-    rate: u32,
-    initial_delay: u32
+    header : Header,
+    entries: Vec<(u32, u32)>  // (rate, initial_delay)
 }
 
 impl Pdin {
-    pub fn parse(f: &mut Mp4File, header: Header) -> Result<Self, &'static str>{
-        let curr_offset = f.offset();
-        let rate = f.read_u32().unwrap();
-        let initial_delay = f.read_u32().unwrap();
-        // f.seek(curr_offset+header.data_size);
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, mut header: Header) -> Result<Self, Mp4Error>{
+        header.parse_version(f)?;
+        header.parse_flags(f)?;
+        let mut entries: Vec<(u32, u32)> = Vec::new();
+        for _ in 0..(header.data_size / 8) {
+            let rate = f.read_u32()?;
+            let initial_delay = f.read_u32()?;
+            entries.push((rate, initial_delay));
+        }
         f.offset_inc(header.data_size);
         Ok(Pdin{
-            header: header,
-            rate: rate,
-            initial_delay: initial_delay
+            header,
+            entries
         })
     }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        let mut data: Vec<u8> = Vec::new();
+        for &(rate, initial_delay) in &self.entries {
+            data.write_u32::<BigEndian>(rate)?;
+            data.write_u32::<BigEndian>(initial_delay)?;
+        }
+        self.header.write(w, &data)
+    }
 
-}
\ No newline at end of file
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn entries(&self) -> &Vec<(u32, u32)> {
+        &self.entries
+    }
+}
@@ -0,0 +1,686 @@
+/*
+
+stbl
+    stsd
+        avc1, hvc1, mp4v, ...       VisualSampleEntry
+            avcC, hvcC, pasp, ...
+        mp4a, ac-3, sowt, ...       AudioSampleEntry
+            esds, dac3, chan, ...
+
+8.5.2.2 Syntax
+
+aligned(8) abstract class SampleEntry (unsigned int(32) format) extends Box(format){
+    const unsigned int(8)[6] reserved = 0;
+    unsigned int(16) data_reference_index;
+}
+
+class VisualSampleEntry(codingname) extends SampleEntry (codingname){
+    unsigned int(16) pre_defined = 0;
+    const unsigned int(16) reserved = 0;
+    unsigned int(32)[3] pre_defined = 0;
+    unsigned int(16) width;
+    unsigned int(16) height;
+    template unsigned int(32) horizresolution = 0x00480000; // 72 dpi
+    template unsigned int(32) vertresolution = 0x00480000; // 72 dpi
+    const unsigned int(32) reserved = 0;
+    template unsigned int(16) frame_count = 1;
+    string[32] compressorname;
+    template unsigned int(16) depth = 0x0018;
+    int(16) pre_defined = -1;
+    // other boxes from derived specifications
+    CleanApertureBox clap; // optional
+    PixelAspectRatioBox pasp; // optional
+}
+
+class AudioSampleEntry(codingname) extends SampleEntry (codingname){
+    const unsigned int(32)[2] reserved = 0;
+    template unsigned int(16) channelcount = 2;
+    template unsigned int(16) samplesize = 16;
+    unsigned int(16) pre_defined = 0;
+    const unsigned int(16) reserved = 0 ;
+    template unsigned int(32) samplerate = { default samplerate of media}<<16;
+}
+
+aligned(8) class SampleDescriptionBox (unsigned int(32) handler_type)
+    extends FullBox('stsd', version, 0){
+    int i ;
+    unsigned int(32) entry_count;
+    for (i = 1 ; i <= entry_count ; i++){
+        SampleEntry(); // an instance of a class derived from SampleEntry
+    }
+}
+
+8.5.2.3 Semantics
+
+`entry_count` is an integer that gives the number of entries in the following table
+`data_reference_index` is an integer that contains the index of the data reference
+    to use to retrieve data associated with samples that use this sample description.
+`width` and `height` are the maximum visual width and height of the stream described
+    by this sample description, in pixels
+`horizresolution` and `vertresolution` fields give the resolution of the image in
+    pixels-per-inch, as a fixed 16.16 number
+`frame_count` indicates how many frames of compressed video are stored in each sample.
+`compressorname` is a name, for informative purposes. It is formatted in a fixed 32-byte field,
+    with the first byte set to the number of bytes to be displayed, followed by that number of
+    bytes of displayable data, and then padding to complete 32 bytes total (including the size byte).
+`depth` takes one of the following values
+    0x0018 – images are in colour with no alpha
+`channelcount` is the number of channels such as 1 (mono) or 2 (stereo)
+`samplesize` is in bits, and takes the default value of 16
+`samplerate` is the sampling rate expressed as a 16.16 fixed-point number (hi.lo)
+
+QuickTime sound descriptions use the first reserved u32 pair as
+`version`(u16), `revision_level`(u16) and `vendor`(u32). With `stsd` version 0:
+
+    version 1 appends
+        unsigned int(32) samples_per_packet;
+        unsigned int(32) bytes_per_packet;
+        unsigned int(32) bytes_per_frame;
+        unsigned int(32) bytes_per_sample;
+
+    version 2 sets channelcount = 3, samplesize = 16, pre_defined = -2,
+    samplerate = 0x00010000 and appends
+        unsigned int(32) size_of_struct_only;
+        float(64)        audio_sample_rate;
+        unsigned int(32) num_audio_channels;
+        unsigned int(32) always_7F000000;
+        unsigned int(32) const_bits_per_channel;
+        unsigned int(32) format_specific_flags;
+        unsigned int(32) const_bytes_per_audio_packet;
+        unsigned int(32) const_lpcm_frames_per_audio_packet;
+
+With `stsd` version 1 an audio entry of version 1 is the ISO AudioSampleEntryV1,
+which keeps the version 0 layout.
+
+Entries whose format is not a known visual or audio coding, or which are too
+short for their fields, are kept as raw bytes.
+
+**/
+
+use super::{Mp4File, Kind, Header, Atom, Avcc, Hvcc, Av1c, Vpcc, Esds, Mp4Error};
+use std::io::{Read, Seek, Write};
+use byteorder::{BigEndian, WriteBytesExt};
+
+const VISUAL_FORMATS: [&str; 32] = [
+    "avc1", "avc2", "avc3", "avc4", "dva1", "dvav",
+    "hvc1", "hev1", "dvh1", "dvhe", "vvc1", "vvi1",
+    "av01", "vp08", "vp09", "mp4v", "s263", "encv",
+    "jpeg", "mjpa", "mjpb", "mjp2",
+    "apcn", "apch", "apcs", "apco", "ap4h", "ap4x",
+    "2vuy", "yuv2", "v210", "rle "
+];
+
+const AUDIO_FORMATS: [&str; 31] = [
+    "mp4a", "enca", "ac-3", "ec-3", "ac-4", "Opus", "fLaC", "alac",
+    "samr", "sawb", "mha1", "mhm1", "dtsc", "dtsh", "dtsl", "dtse", ".mp3",
+    "lpcm", "ipcm", "fpcm", "sowt", "twos", "in24", "in32", "fl32", "fl64",
+    "ulaw", "alaw", "ima4", "NONE", "raw "
+];
+
+// Bytes of the fixed fields, from `reserved` up to the child boxes.
+const VISUAL_FIELDS_SIZE: u64 = 78;
+const AUDIO_FIELDS_SIZE : u64 = 28;
+
+#[derive(Debug, Clone)]
+pub struct Stsd {
+    header : Header,
+    entries: Vec<SampleEntry>
+}
+
+impl Stsd {
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, mut header: Header) -> Result<Self, Mp4Error>{
+        header.parse_version(f)?;
+        header.parse_flags(f)?;
+        let version = header.version().unwrap_or(0);
+
+        let end = header.offset() + header.atom_size();
+        let entry_count = f.read_u32()?;
+        f.offset_inc(4);
+
+        let mut entries: Vec<SampleEntry> = Vec::new();
+        for _ in 0..entry_count {
+            let remaining = end.saturating_sub(f.offset());
+            if remaining < 8 {
+                return Err(Mp4Error::Underrun {
+                    kind: Some(header.kind().clone()),
+                    offset: f.offset(),
+                    remaining
+                });
+            }
+            let entry_header = Header::parse(f)?;
+            if entry_header.offset() + entry_header.atom_size() > end {
+                return Err(Mp4Error::Overrun {
+                    kind  : entry_header.kind().clone(),
+                    offset: entry_header.offset(),
+                    size  : entry_header.atom_size(),
+                    limit : end
+                });
+            }
+            entries.push(SampleEntry::parse(f, entry_header, version)?);
+        }
+
+        Ok(Stsd{
+            header,
+            entries
+        })
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        let mut data: Vec<u8> = Vec::new();
+        data.write_u32::<BigEndian>(self.entries.len() as u32)?;
+        for entry in &self.entries {
+            entry.write(&mut data)?;
+        }
+        self.header.write(w, &data)
+    }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn entries(&self) -> &[SampleEntry] {
+        &self.entries
+    }
+    // `index` is a 1-based sample description index, as used by `stsc` and `tfhd`.
+    pub fn entry(&self, index: u32) -> Option<&SampleEntry> {
+        if index == 0 {
+            return None;
+        }
+        self.entries.get(index as usize - 1)
+    }
+    // Append `entry`; returns its sample description index.
+    pub fn add_entry(&mut self, entry: SampleEntry) -> u32 {
+        self.entries.push(entry);
+        self.entries.len() as u32
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum SampleEntry {
+    Visual(VisualSampleEntry),
+    Audio(AudioSampleEntry),
+    Unknown(UnknownSampleEntry)
+}
+
+impl SampleEntry {
+    fn parse<R: Read + Seek>(f: &mut Mp4File<R>, header: Header, stsd_version: u8) -> Result<Self, Mp4Error>{
+        let format = header.kind().to_string();
+        let data_size = header.data_size();
+
+        if VISUAL_FORMATS.contains(&format.as_str()) && data_size >= VISUAL_FIELDS_SIZE {
+            return Ok(SampleEntry::Visual(VisualSampleEntry::parse(f, header)?));
+        }
+        if AUDIO_FORMATS.contains(&format.as_str()) && data_size >= AUDIO_FIELDS_SIZE {
+            // Peek the sound description version to know how many fields follow.
+            let data_offset = header.offset() + header.header_size();
+            f.seek(data_offset + 8)?;
+            let version = f.read_u16()?;
+            f.seek(data_offset)?;
+            if data_size >= AUDIO_FIELDS_SIZE + QuickTimeSound::size(stsd_version, version) {
+                return Ok(SampleEntry::Audio(AudioSampleEntry::parse(f, header, stsd_version)?));
+            }
+        }
+        Ok(SampleEntry::Unknown(UnknownSampleEntry::parse(f, header)?))
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        match *self {
+            SampleEntry::Visual(ref entry)  => entry.write(w),
+            SampleEntry::Audio(ref entry)   => entry.write(w),
+            SampleEntry::Unknown(ref entry) => entry.write(w)
+        }
+    }
+    pub fn header(&self) -> &Header {
+        match *self {
+            SampleEntry::Visual(ref entry)  => entry.header(),
+            SampleEntry::Audio(ref entry)   => entry.header(),
+            SampleEntry::Unknown(ref entry) => entry.header()
+        }
+    }
+    // The coding name, e.g. `avc1` or `mp4a`.
+    pub fn format(&self) -> String {
+        self.header().kind().to_string()
+    }
+    pub fn data_reference_index(&self) -> u16 {
+        match *self {
+            SampleEntry::Visual(ref entry)  => entry.data_reference_index(),
+            SampleEntry::Audio(ref entry)   => entry.data_reference_index(),
+            SampleEntry::Unknown(ref entry) => entry.data_reference_index()
+        }
+    }
+    // Configuration boxes (`avcC`, `esds`, `pasp`, ...); none for unknown entries.
+    pub fn children(&self) -> &[Atom] {
+        match *self {
+            SampleEntry::Visual(ref entry)  => entry.children(),
+            SampleEntry::Audio(ref entry)   => entry.children(),
+            SampleEntry::Unknown(_)         => &[]
+        }
+    }
+    // RFC 6381 codec string for the HLS `CODECS` and DASH `codecs` attributes, e.g. `avc1.64001F`.
+    pub fn codec_string(&self) -> Result<String, Mp4Error> {
+        let format = self.format();
+        let missing = |reason| Err(self.header().invalid_value(reason));
+        match (format.as_str(), self) {
+            ("avc1" | "avc3", SampleEntry::Visual(entry)) => match entry.avcc() {
+                Some(avcc) => avcc.codec_string(&format),
+                None => missing("sample entry has no avcC")
+            },
+            ("hvc1" | "hev1", SampleEntry::Visual(entry)) => match entry.hvcc() {
+                Some(hvcc) => Ok(hvcc.codec_string(&format)),
+                None => missing("sample entry has no hvcC")
+            },
+            ("av01", SampleEntry::Visual(entry)) => match entry.av1c() {
+                Some(av1c) => Ok(av1c.codec_string()),
+                None => missing("sample entry has no av1C")
+            },
+            ("vp08" | "vp09", SampleEntry::Visual(entry)) => match entry.vpcc() {
+                Some(vpcc) => vpcc.codec_string(&format),
+                None => missing("sample entry has no vpcC")
+            },
+            ("mp4a", SampleEntry::Audio(entry)) => match entry.esds() {
+                Some(esds) => esds.codec_string(),
+                None => missing("sample entry has no esds")
+            },
+            ("ac-3", _) | ("ec-3", _) => Ok(format.clone()),
+            ("Opus", _) => Ok("opus".to_owned()),
+            ("fLaC", _) => Ok("flac".to_owned()),
+            _ => Err(Mp4Error::UnsupportedCodec(format.clone()))
+        }
+    }
+    pub fn visual(&self) -> Option<&VisualSampleEntry> {
+        match *self {
+            SampleEntry::Visual(ref entry) => Some(entry),
+            _ => None
+        }
+    }
+    pub fn audio(&self) -> Option<&AudioSampleEntry> {
+        match *self {
+            SampleEntry::Audio(ref entry) => Some(entry),
+            _ => None
+        }
+    }
+}
+
+// Parse the boxes that follow the fixed fields of a sample entry.
+// Some writers end an entry with a few zero bytes, they are kept as `trailing`.
+fn parse_entry_children<R: Read + Seek>(f: &mut Mp4File<R>, header: &Header) -> Result<(Vec<Atom>, Vec<u8>), Mp4Error>{
+    let end = header.offset() + header.atom_size();
+    let mut children: Vec<Atom> = Vec::new();
+    while end - f.offset() >= 8 {
+        let child = Header::parse(f)?;
+        if child.offset() + child.atom_size() > end {
+            return Err(Mp4Error::Overrun {
+                kind  : child.kind().clone(),
+                offset: child.offset(),
+                size  : child.atom_size(),
+                limit : end
+            });
+        }
+        children.push(Atom::parse_with_header(f, child, false)?);
+    }
+    let remaining = end - f.offset();
+    let trailing = f.read_bytes(remaining)?;
+    f.offset_inc(remaining);
+    Ok((children, trailing))
+}
+
+fn read_array<R: Read + Seek>(f: &mut Mp4File<R>, bytes: &mut [u8]) -> Result<(), Mp4Error>{
+    for byte in bytes.iter_mut() {
+        *byte = f.read_u8()?;
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+pub struct VisualSampleEntry {
+    header: Header,
+    reserved: [u8; 6],
+    data_reference_index: u16,
+    pre_defined: [u8; 16],
+    width : u16,
+    height: u16,
+    horizresolution: u32,
+    vertresolution : u32,
+    reserved2  : u32,
+    frame_count: u16,
+    compressorname: [u8; 32],
+    depth: u16,
+    pre_defined2: i16,
+    children: Vec<Atom>,
+    trailing: Vec<u8>
+}
+
+impl VisualSampleEntry {
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, header: Header) -> Result<Self, Mp4Error>{
+        let mut reserved = [0u8; 6];
+        read_array(f, &mut reserved)?;
+        let data_reference_index = f.read_u16()?;
+        let mut pre_defined = [0u8; 16];
+        read_array(f, &mut pre_defined)?;
+        let width = f.read_u16()?;
+        let height = f.read_u16()?;
+        let horizresolution = f.read_u32()?;
+        let vertresolution = f.read_u32()?;
+        let reserved2 = f.read_u32()?;
+        let frame_count = f.read_u16()?;
+        let mut compressorname = [0u8; 32];
+        read_array(f, &mut compressorname)?;
+        let depth = f.read_u16()?;
+        let pre_defined2 = f.read_i16()?;
+        f.offset_inc(VISUAL_FIELDS_SIZE);
+
+        let (children, trailing) = parse_entry_children(f, &header)?;
+
+        Ok(VisualSampleEntry{
+            header,
+            reserved,
+            data_reference_index,
+            pre_defined,
+            width,
+            height,
+            horizresolution,
+            vertresolution,
+            reserved2,
+            frame_count,
+            compressorname,
+            depth,
+            pre_defined2,
+            children,
+            trailing
+        })
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        let mut data: Vec<u8> = Vec::new();
+        data.write_all(&self.reserved)?;
+        data.write_u16::<BigEndian>(self.data_reference_index)?;
+        data.write_all(&self.pre_defined)?;
+        data.write_u16::<BigEndian>(self.width)?;
+        data.write_u16::<BigEndian>(self.height)?;
+        data.write_u32::<BigEndian>(self.horizresolution)?;
+        data.write_u32::<BigEndian>(self.vertresolution)?;
+        data.write_u32::<BigEndian>(self.reserved2)?;
+        data.write_u16::<BigEndian>(self.frame_count)?;
+        data.write_all(&self.compressorname)?;
+        data.write_u16::<BigEndian>(self.depth)?;
+        data.write_i16::<BigEndian>(self.pre_defined2)?;
+        Atom::write_children(&self.children, &mut data)?;
+        data.write_all(&self.trailing)?;
+        self.header.write(w, &data)
+    }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn data_reference_index(&self) -> u16 {
+        self.data_reference_index
+    }
+    pub fn width(&self) -> u16 {
+        self.width
+    }
+    pub fn height(&self) -> u16 {
+        self.height
+    }
+    // Pixels per inch.
+    pub fn horizresolution(&self) -> f64 {
+        self.horizresolution as f64 / 65536.0
+    }
+    pub fn vertresolution(&self) -> f64 {
+        self.vertresolution as f64 / 65536.0
+    }
+    pub fn frame_count(&self) -> u16 {
+        self.frame_count
+    }
+    // The displayable part of `compressorname`.
+    pub fn compressorname(&self) -> String {
+        let len = ::std::cmp::min(self.compressorname[0] as usize, 31);
+        String::from_utf8_lossy(&self.compressorname[1..1 + len]).into_owned()
+    }
+    pub fn depth(&self) -> u16 {
+        self.depth
+    }
+    pub fn children(&self) -> &[Atom] {
+        &self.children
+    }
+    pub fn avcc(&self) -> Option<&Avcc> {
+        self.children.iter().filter_map(|atom| match *atom {
+            Atom::avcC(ref atom) => Some(atom),
+            _ => None
+        }).next()
+    }
+    pub fn hvcc(&self) -> Option<&Hvcc> {
+        self.children.iter().filter_map(|atom| match *atom {
+            Atom::hvcC(ref atom) => Some(atom),
+            _ => None
+        }).next()
+    }
+    pub fn av1c(&self) -> Option<&Av1c> {
+        self.children.iter().filter_map(|atom| match *atom {
+            Atom::av1C(ref atom) => Some(atom),
+            _ => None
+        }).next()
+    }
+    pub fn vpcc(&self) -> Option<&Vpcc> {
+        self.children.iter().filter_map(|atom| match *atom {
+            Atom::vpcC(ref atom) => Some(atom),
+            _ => None
+        }).next()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QuickTimeSound {
+    V1 {
+        samples_per_packet: u32,
+        bytes_per_packet  : u32,
+        bytes_per_frame   : u32,
+        bytes_per_sample  : u32
+    },
+    V2 {
+        size_of_struct_only: u32,
+        audio_sample_rate  : f64,
+        num_audio_channels : u32,
+        always_7f000000    : u32,
+        const_bits_per_channel: u32,
+        format_specific_flags : u32,
+        const_bytes_per_audio_packet: u32,
+        const_lpcm_frames_per_audio_packet: u32
+    }
+}
+
+impl QuickTimeSound {
+    // Bytes following the fixed fields for an audio entry of `version` in a `stsd` of `stsd_version`.
+    fn size(stsd_version: u8, version: u16) -> u64 {
+        match (stsd_version, version) {
+            (0, 1) => 16,
+            (0, 2) => 36,
+            _ => 0
+        }
+    }
+    fn parse<R: Read + Seek>(f: &mut Mp4File<R>, stsd_version: u8, version: u16) -> Result<Option<Self>, Mp4Error>{
+        let sound = match (stsd_version, version) {
+            (0, 1) => QuickTimeSound::V1 {
+                samples_per_packet: f.read_u32()?,
+                bytes_per_packet  : f.read_u32()?,
+                bytes_per_frame   : f.read_u32()?,
+                bytes_per_sample  : f.read_u32()?
+            },
+            (0, 2) => QuickTimeSound::V2 {
+                size_of_struct_only: f.read_u32()?,
+                audio_sample_rate  : f.read_f64()?,
+                num_audio_channels : f.read_u32()?,
+                always_7f000000    : f.read_u32()?,
+                const_bits_per_channel: f.read_u32()?,
+                format_specific_flags : f.read_u32()?,
+                const_bytes_per_audio_packet: f.read_u32()?,
+                const_lpcm_frames_per_audio_packet: f.read_u32()?
+            },
+            _ => return Ok(None)
+        };
+        f.offset_inc(QuickTimeSound::size(stsd_version, version));
+        Ok(Some(sound))
+    }
+    fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        match *self {
+            QuickTimeSound::V1 { samples_per_packet, bytes_per_packet, bytes_per_frame, bytes_per_sample } => {
+                for value in &[samples_per_packet, bytes_per_packet, bytes_per_frame, bytes_per_sample] {
+                    w.write_u32::<BigEndian>(*value)?;
+                }
+            },
+            QuickTimeSound::V2 { size_of_struct_only, audio_sample_rate, num_audio_channels, always_7f000000,
+                                 const_bits_per_channel, format_specific_flags,
+                                 const_bytes_per_audio_packet, const_lpcm_frames_per_audio_packet } => {
+                w.write_u32::<BigEndian>(size_of_struct_only)?;
+                w.write_f64::<BigEndian>(audio_sample_rate)?;
+                for value in &[num_audio_channels, always_7f000000, const_bits_per_channel, format_specific_flags,
+                               const_bytes_per_audio_packet, const_lpcm_frames_per_audio_packet] {
+                    w.write_u32::<BigEndian>(*value)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AudioSampleEntry {
+    header: Header,
+    reserved: [u8; 6],
+    data_reference_index: u16,
+    version       : u16,
+    revision_level: u16,
+    vendor        : u32,
+    channelcount: u16,
+    samplesize  : u16,
+    pre_defined : i16,  // QuickTime `compression_id`
+    reserved2   : u16,  // QuickTime `packet_size`
+    samplerate  : u32,
+    quicktime: Option<QuickTimeSound>,
+    children : Vec<Atom>,
+    trailing : Vec<u8>
+}
+
+impl AudioSampleEntry {
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, header: Header, stsd_version: u8) -> Result<Self, Mp4Error>{
+        let mut reserved = [0u8; 6];
+        read_array(f, &mut reserved)?;
+        let data_reference_index = f.read_u16()?;
+        let version = f.read_u16()?;
+        let revision_level = f.read_u16()?;
+        let vendor = f.read_u32()?;
+        let channelcount = f.read_u16()?;
+        let samplesize = f.read_u16()?;
+        let pre_defined = f.read_i16()?;
+        let reserved2 = f.read_u16()?;
+        let samplerate = f.read_u32()?;
+        f.offset_inc(AUDIO_FIELDS_SIZE);
+
+        let quicktime = QuickTimeSound::parse(f, stsd_version, version)?;
+        let (children, trailing) = parse_entry_children(f, &header)?;
+
+        Ok(AudioSampleEntry{
+            header,
+            reserved,
+            data_reference_index,
+            version,
+            revision_level,
+            vendor,
+            channelcount,
+            samplesize,
+            pre_defined,
+            reserved2,
+            samplerate,
+            quicktime,
+            children,
+            trailing
+        })
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        let mut data: Vec<u8> = Vec::new();
+        data.write_all(&self.reserved)?;
+        data.write_u16::<BigEndian>(self.data_reference_index)?;
+        data.write_u16::<BigEndian>(self.version)?;
+        data.write_u16::<BigEndian>(self.revision_level)?;
+        data.write_u32::<BigEndian>(self.vendor)?;
+        data.write_u16::<BigEndian>(self.channelcount)?;
+        data.write_u16::<BigEndian>(self.samplesize)?;
+        data.write_i16::<BigEndian>(self.pre_defined)?;
+        data.write_u16::<BigEndian>(self.reserved2)?;
+        data.write_u32::<BigEndian>(self.samplerate)?;
+        if let Some(ref sound) = self.quicktime {
+            sound.write(&mut data)?;
+        }
+        Atom::write_children(&self.children, &mut data)?;
+        data.write_all(&self.trailing)?;
+        self.header.write(w, &data)
+    }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn data_reference_index(&self) -> u16 {
+        self.data_reference_index
+    }
+    // Sound description version; 0 for plain ISO entries.
+    pub fn version(&self) -> u16 {
+        self.version
+    }
+    pub fn quicktime(&self) -> Option<&QuickTimeSound> {
+        self.quicktime.as_ref()
+    }
+    pub fn channel_count(&self) -> u32 {
+        match self.quicktime {
+            Some(QuickTimeSound::V2 { num_audio_channels, .. }) => num_audio_channels,
+            _ => self.channelcount as u32
+        }
+    }
+    // Bits per sample.
+    pub fn sample_size(&self) -> u32 {
+        match self.quicktime {
+            Some(QuickTimeSound::V2 { const_bits_per_channel, .. }) => const_bits_per_channel,
+            _ => self.samplesize as u32
+        }
+    }
+    // Samples per second.
+    pub fn sample_rate(&self) -> f64 {
+        match self.quicktime {
+            Some(QuickTimeSound::V2 { audio_sample_rate, .. }) => audio_sample_rate,
+            _ => self.samplerate as f64 / 65536.0
+        }
+    }
+    pub fn children(&self) -> &[Atom] {
+        &self.children
+    }
+    pub fn esds(&self) -> Option<&Esds> {
+        self.children.iter().filter_map(|atom| match *atom {
+            Atom::esds(ref atom) => Some(atom),
+            _ => None
+        }).next()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UnknownSampleEntry {
+    header: Header,
+    data  : Vec<u8>
+}
+
+impl UnknownSampleEntry {
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, header: Header) -> Result<Self, Mp4Error>{
+        let data = f.read_bytes(header.data_size())?;
+        f.offset_inc(header.data_size());
+        Ok(UnknownSampleEntry{
+            header,
+            data
+        })
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        self.header.write(w, &self.data)
+    }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    // 0 when the entry is too short to hold one.
+    pub fn data_reference_index(&self) -> u16 {
+        if self.data.len() < 8 {
+            return 0;
+        }
+        (self.data[6] as u16) << 8 | self.data[7] as u16
+    }
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
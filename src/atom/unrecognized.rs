@@ -1,18 +1,44 @@
 
-use super::{Mp4File, Kind, Header, Atom};
+use super::{Mp4File, Kind, Header, Atom, Mp4Error};
+use std::io::{Read, Seek, Write};
+use byteorder::{BigEndian, WriteBytesExt};
 
 #[derive(Debug, Clone)]
 pub struct Unrecognized {
-    header: Header
+    header: Header,
+    data  : Option<Vec<u8>>
 }
 
 impl Unrecognized {
-    pub fn parse(f: &mut Mp4File, header: Header) -> Result<Self, &'static str>{
-        let curr_offset = f.offset();
-        f.seek(curr_offset+header.data_size);
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, header: Header, top_level: bool) -> Result<Self, Mp4Error>{
+        let data = if top_level {
+            None
+        } else {
+            Some(f.read_bytes(header.data_size)?)
+        };
         f.offset_inc(header.data_size);
         Ok(Unrecognized{
-            header: header,
+            header,
+            data
         })
     }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    // File range of the payload when it stays in the source file; see `Mp4File::write_to`.
+    pub fn source_range(&self) -> Option<(u64, u64)> {
+        match self.data {
+            Some(_) => None,
+            None => Some((self.header.offset + self.header.header_size, self.header.data_size))
+        }
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        match self.data {
+            Some(ref data) => self.header.write(w, data),
+            None => Err(self.header.invalid_value("payload is not held in memory"))
+        }
+    }
+    pub fn data(&self) -> Option<&[u8]> {
+        self.data.as_deref()
+    }
 }
\ No newline at end of file
@@ -1,19 +1,31 @@
-use super::{Mp4File, Kind, Header, Atom};
+use super::{Mp4File, Kind, Header, Atom, Mp4Error};
+use std::io::{Read, Seek, Write};
+use byteorder::{BigEndian, WriteBytesExt};
 
 #[derive(Debug, Clone)]
 pub struct Uuid {
-    header: Header
+    header: Header,
+    data  : Vec<u8>
 }
 
 impl Uuid {
-    pub fn parse(f: &mut Mp4File, mut header: Header) -> Result<Self, &'static str>{
-        header.parse_usertype(f);
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, mut header: Header) -> Result<Self, Mp4Error>{
+        header.parse_usertype(f)?;
          
-        let curr_offset = f.offset();
-        f.seek(curr_offset+header.data_size);
+        let data = f.read_bytes(header.data_size)?;
         f.offset_inc(header.data_size);
         Ok(Uuid{
-            header: header
+            header,
+            data
         })
     }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        self.header.write(w, &self.data)
+    }
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
 }
\ No newline at end of file
@@ -0,0 +1,115 @@
+/*
+
+VP Codec ISO Media File Format Binding v1.0, 2.2 Syntax
+
+aligned (8) class VPCodecConfigurationRecord {
+    unsigned int (8)     profile;
+    unsigned int (8)     level;
+    unsigned int (4)     bitDepth;
+    unsigned int (3)     chromaSubsampling;
+    unsigned int (1)     videoFullRangeFlag;
+    unsigned int (8)     colourPrimaries;
+    unsigned int (8)     transferCharacteristics;
+    unsigned int (8)     matrixCoefficients;
+    unsigned int (16)    codecIntializationDataSize;
+    unsigned int (8)[codecIntializationDataSize] codecIntializationData;
+}
+
+class VPCodecConfigurationBox extends FullBox('vpcC', version = 1, 0) {
+    VPCodecConfigurationRecord() vpcConfig;
+}
+
+`level` is 10 times the VP9 level, e.g. 31 for level 3.1
+`chromaSubsampling` is
+    0   4:2:0, chroma vertically interpolated
+    1   4:2:0, chroma co-located with luma (0, 0)
+    2   4:2:2
+    3   4:4:4
+`colourPrimaries`, `transferCharacteristics` and `matrixCoefficients` take the
+    ISO/IEC 23001-8 values
+`codecIntializationDataSize` is 0 for VP8 and VP9
+
+Version 0 comes from a draft with a different layout and is not decoded; the box
+is kept as it is either way and the record is decoded on request.
+
+**/
+
+use super::{Mp4File, Kind, Header, Atom, Mp4Error};
+use std::io::{Read, Seek, Write};
+
+#[derive(Debug, Clone)]
+pub struct VpcConfig {
+    pub profile  : u8,
+    pub level    : u8,
+    pub bit_depth: u8,
+    pub chroma_subsampling   : u8,
+    pub video_full_range_flag: bool,
+    pub colour_primaries        : u8,
+    pub transfer_characteristics: u8,
+    pub matrix_coefficients     : u8,
+    pub codec_initialization_data: Vec<u8>
+}
+
+#[derive(Debug, Clone)]
+pub struct Vpcc {
+    header: Header,
+    data: Vec<u8>
+}
+
+impl Vpcc {
+    pub fn parse<R: Read + Seek>(f: &mut Mp4File<R>, mut header: Header) -> Result<Self, Mp4Error>{
+        header.parse_version(f)?;
+        header.parse_flags(f)?;
+        let data = f.read_bytes(header.data_size)?;
+
+        f.offset_inc(header.data_size);
+        Ok(Vpcc{
+            header,
+            data
+        })
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Mp4Error>{
+        self.header.write(w, &self.data)
+    }
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+    // Decode the VPCodecConfigurationRecord of a version 1 box.
+    pub fn config(&self) -> Result<VpcConfig, Mp4Error> {
+        let version = self.header.check_version(1)?;
+        if version != 1 {
+            return Err(Mp4Error::UnsupportedVersion {
+                kind   : self.header.kind().clone(),
+                offset : self.header.offset(),
+                version
+            });
+        }
+        let data = &self.data;
+        if data.len() < 8 {
+            return Err(self.header.invalid_size());
+        }
+        let size = (data[6] as usize) << 8 | data[7] as usize;
+        if 8 + size > data.len() {
+            return Err(self.header.invalid_size());
+        }
+        Ok(VpcConfig {
+            profile  : data[0],
+            level    : data[1],
+            bit_depth: data[2] >> 4,
+            chroma_subsampling   : (data[2] >> 1) & 0x07,
+            video_full_range_flag: data[2] & 1 == 1,
+            colour_primaries        : data[3],
+            transfer_characteristics: data[4],
+            matrix_coefficients     : data[5],
+            codec_initialization_data: data[8..8 + size].to_vec()
+        })
+    }
+    // Short form codec string `vp09.PP.LL.DD`, e.g. `vp09.00.10.08`; `format` is the sample entry format.
+    pub fn codec_string(&self, format: &str) -> Result<String, Mp4Error> {
+        let config = self.config()?;
+        Ok(format!("{}.{:02}.{:02}.{:02}", format, config.profile, config.level, config.bit_depth))
+    }
+}
@@ -0,0 +1,285 @@
+/*
+
+MPEG-4 Audio AudioSpecificConfig, ISO/IEC 14496-3 1.6.2.1.
+
+AudioSpecificConfig () {
+    audioObjectType = GetAudioObjectType();                 5 (+ 6)
+    samplingFrequencyIndex;                                 4
+    if ( samplingFrequencyIndex == 0xf )
+        samplingFrequency;                                  24
+    channelConfiguration;                                   4
+    sbrPresentFlag = -1;
+    psPresentFlag = -1;
+    if ( audioObjectType == 5 || audioObjectType == 29 ) {
+        extensionAudioObjectType = 5;
+        sbrPresentFlag = 1;
+        if ( audioObjectType == 29 )
+            psPresentFlag = 1;
+        extensionSamplingFrequencyIndex;                    4
+        if ( extensionSamplingFrequencyIndex == 0xf )
+            extensionSamplingFrequency;                     24
+        audioObjectType = GetAudioObjectType();
+        if ( audioObjectType == 22 )
+            extensionChannelConfiguration;                  4
+    } else {
+        extensionAudioObjectType = 0;
+    }
+    switch (audioObjectType) {
+        case 1: case 2: case 3: case 4: case 6: case 7:
+        case 17: case 19: case 20: case 21: case 22: case 23:
+            GASpecificConfig();
+            break;
+        ...
+    }
+    ...
+    if ( extensionAudioObjectType != 5 && bits_to_decode() >= 16 ) {
+        syncExtensionType;                                  11
+        if (syncExtensionType == 0x2b7) {
+            extensionAudioObjectType = GetAudioObjectType();
+            if ( extensionAudioObjectType == 5 ) {
+                sbrPresentFlag;                             1
+                if (sbrPresentFlag == 1) {
+                    extensionSamplingFrequencyIndex;        4
+                    if ( extensionSamplingFrequencyIndex == 0xf )
+                        extensionSamplingFrequency;         24
+                    if ( bits_to_decode() >= 12 ) {
+                        syncExtensionType;                  11
+                        if (syncExtensionType == 0x548)
+                            psPresentFlag;                  1
+                    }
+                }
+            }
+            ...
+        }
+    }
+}
+
+GetAudioObjectType() {
+    audioObjectType;                                        5
+    if (audioObjectType == 31)
+        audioObjectType = 32 + audioObjectTypeExt;          6
+}
+
+GASpecificConfig (samplingFrequencyIndex, channelConfiguration, audioObjectType) {
+    frameLengthFlag;                                        1
+    dependsOnCoreCoder;                                     1
+    if ( dependsOnCoreCoder )
+        coreCoderDelay;                                     14
+    extensionFlag;                                          1
+    if ( ! channelConfiguration )
+        program_config_element ();
+    ...
+}
+
+Audio object types: 1 AAC Main, 2 AAC LC, 3 AAC SSR, 4 AAC LTP, 5 SBR (HE-AAC),
+29 PS (HE-AAC v2), 32 / 33 / 34 MPEG-1/2 Layer 1 / 2 / 3.
+
+Only the fields needed to identify the stream are kept; the object type specific
+configuration after GASpecificConfig is not decoded.
+
+**/
+
+use super::bits::BitReader;
+use Mp4Error;
+
+pub const AOT_AAC_MAIN: u8 = 1;
+pub const AOT_AAC_LC  : u8 = 2;
+pub const AOT_SBR     : u8 = 5;
+pub const AOT_PS      : u8 = 29;
+
+// Table 1.18
+const SAMPLING_FREQUENCIES: [u32; 13] = [
+    96000, 88200, 64000, 48000, 44100, 32000, 24000, 22050, 16000, 12000, 11025, 8000, 7350
+];
+
+// Table 1.19, channelConfiguration 1..=7 (7 is 7.1 front)
+const CHANNELS: [u32; 8] = [0, 1, 2, 3, 4, 5, 6, 8];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AudioSpecificConfig {
+    pub audio_object_type          : u8,   // core coder, 2 for HE-AAC
+    pub extension_audio_object_type: u8,   // 5 when SBR is signalled, 0 otherwise
+    pub sbr_present: bool,
+    pub ps_present : bool,
+    pub sampling_frequency_index: u8,
+    pub sampling_frequency      : u32,
+    pub extension_sampling_frequency: Option<u32>,  // SBR output rate
+    pub channel_configuration   : u8,
+    pub channel_count           : u32,   // from program_config_element when channel_configuration is 0
+    pub frame_length_flag       : bool   // 960 instead of 1024 samples per frame
+}
+
+fn read_audio_object_type(r: &mut BitReader) -> Result<u8, Mp4Error> {
+    let audio_object_type = r.read_u8(5)?;
+    if audio_object_type == 31 {
+        return Ok(32 + r.read_u8(6)?);
+    }
+    Ok(audio_object_type)
+}
+
+// Returns the index and the frequency.
+fn read_sampling_frequency(r: &mut BitReader) -> Result<(u8, u32), Mp4Error> {
+    let index = r.read_u8(4)?;
+    let frequency = match index {
+        0x0F => r.read_u32(24)?,
+        _ => match SAMPLING_FREQUENCIES.get(index as usize) {
+            Some(frequency) => *frequency,
+            None => return Err(Mp4Error::InvalidBitstream("samplingFrequencyIndex is reserved"))
+        }
+    };
+    Ok((index, frequency))
+}
+
+// program_config_element (4.4.1.1), returns the number of output channels.
+fn read_program_config_element(r: &mut BitReader, start: usize) -> Result<u32, Mp4Error> {
+    // element_instance_tag, object_type, sampling_frequency_index
+    r.skip(4 + 2 + 4)?;
+    let num_front = r.read_u32(4)?;
+    let num_side = r.read_u32(4)?;
+    let num_back = r.read_u32(4)?;
+    let num_lfe = r.read_u32(2)?;
+    let num_assoc_data = r.read_u32(3)?;
+    let num_valid_cc = r.read_u32(4)?;
+    // mono_mixdown, stereo_mixdown, matrix_mixdown
+    if r.read_bit()? {
+        r.skip(4)?;
+    }
+    if r.read_bit()? {
+        r.skip(4)?;
+    }
+    if r.read_bit()? {
+        r.skip(3)?;
+    }
+    let mut channels = num_lfe;
+    for _ in 0..num_front + num_side + num_back {
+        // is_cpe, element_tag_select
+        channels += if r.read_bit()? { 2 } else { 1 };
+        r.skip(4)?;
+    }
+    r.skip((4 * (num_lfe + num_assoc_data) + 5 * num_valid_cc) as usize)?;
+    // byte_alignment() relative to the start of AudioSpecificConfig
+    let misaligned = (r.position() - start) % 8;
+    if misaligned != 0 {
+        r.skip(8 - misaligned)?;
+    }
+    let comment_field_bytes = r.read_u32(8)?;
+    r.skip(8 * comment_field_bytes as usize)?;
+    Ok(channels)
+}
+
+impl AudioSpecificConfig {
+    // `data` is the DecoderSpecificInfo payload of an `esds`.
+    pub fn parse(data: &[u8]) -> Result<AudioSpecificConfig, Mp4Error> {
+        let mut r = BitReader::new(data);
+        let start = r.position();
+
+        let mut audio_object_type = read_audio_object_type(&mut r)?;
+        let (sampling_frequency_index, sampling_frequency) = read_sampling_frequency(&mut r)?;
+        let channel_configuration = r.read_u8(4)?;
+        let mut extension_audio_object_type = 0;
+        let mut sbr_present = false;
+        let mut ps_present = false;
+        let mut extension_sampling_frequency = None;
+
+        if audio_object_type == AOT_SBR || audio_object_type == AOT_PS {
+            extension_audio_object_type = AOT_SBR;
+            sbr_present = true;
+            ps_present = audio_object_type == AOT_PS;
+            extension_sampling_frequency = Some(read_sampling_frequency(&mut r)?.1);
+            audio_object_type = read_audio_object_type(&mut r)?;
+            if audio_object_type == 22 {
+                // extensionChannelConfiguration
+                r.skip(4)?;
+            }
+        }
+
+        let mut channel_count = CHANNELS.get(channel_configuration as usize).cloned().unwrap_or(0);
+        let mut frame_length_flag = false;
+        let mut ga_specific_config = false;
+        match audio_object_type {
+            1 | 2 | 3 | 4 | 6 | 7 | 17 | 19 | 20 | 21 | 22 | 23 => {
+                ga_specific_config = true;
+                frame_length_flag = r.read_bit()?;
+                // dependsOnCoreCoder, coreCoderDelay
+                if r.read_bit()? {
+                    r.skip(14)?;
+                }
+                let extension_flag = r.read_bit()?;
+                if channel_configuration == 0 {
+                    channel_count = read_program_config_element(&mut r, start)?;
+                }
+                if audio_object_type == 6 || audio_object_type == 20 {
+                    // layerNr
+                    r.skip(3)?;
+                }
+                if extension_flag {
+                    if audio_object_type == 22 {
+                        // numOfSubFrame, layer_length
+                        r.skip(5 + 11)?;
+                    }
+                    if let 17 | 19 | 20 | 23 = audio_object_type {
+                        // aacSectionDataResilienceFlag, aacScalefactorDataResilienceFlag,
+                        // aacSpectralDataResilienceFlag
+                        r.skip(3)?;
+                    }
+                    // extensionFlag3
+                    r.skip(1)?;
+                }
+            },
+            _ => {}
+        }
+        if let 17 | 19 | 20 | 21 | 22 | 23 | 24 | 25 | 26 | 27 | 39 = audio_object_type {
+            // epConfig; only 0 and 1 carry no further fields
+            if r.read_u8(2)? > 1 {
+                ga_specific_config = false;
+            }
+        }
+
+        // Backward compatible SBR / PS signalling, only reliable after a known configuration.
+        if ga_specific_config && extension_audio_object_type != AOT_SBR && r.bits_left() >= 16 {
+            let sync_extension_type = r.read_u32(11)?;
+            if sync_extension_type == 0x2B7 {
+                let extension = read_audio_object_type(&mut r)?;
+                if extension == AOT_SBR {
+                    extension_audio_object_type = extension;
+                    sbr_present = r.read_bit()?;
+                    if sbr_present {
+                        extension_sampling_frequency = Some(read_sampling_frequency(&mut r)?.1);
+                        if r.bits_left() >= 12 && r.read_u32(11)? == 0x548 {
+                            ps_present = r.read_bit()?;
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(AudioSpecificConfig {
+            audio_object_type,
+            extension_audio_object_type,
+            sbr_present,
+            ps_present,
+            sampling_frequency_index,
+            sampling_frequency,
+            extension_sampling_frequency,
+            channel_configuration,
+            channel_count,
+            frame_length_flag
+        })
+    }
+    // HE-AAC (v1 or v2): AAC LC with SBR.
+    pub fn is_he_aac(&self) -> bool {
+        self.sbr_present
+    }
+    // HE-AAC v2: with parametric stereo as well.
+    pub fn is_he_aac_v2(&self) -> bool {
+        self.sbr_present && self.ps_present
+    }
+    // Sample rate of the decoded output, doubled by SBR.
+    pub fn output_sampling_frequency(&self) -> u32 {
+        self.extension_sampling_frequency.unwrap_or(self.sampling_frequency)
+    }
+    // Parametric stereo produces two channels from a mono core.
+    pub fn output_channel_count(&self) -> u32 {
+        if self.ps_present && self.channel_count == 1 { 2 } else { self.channel_count }
+    }
+}
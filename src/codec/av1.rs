@@ -0,0 +1,374 @@
+/*
+
+AV1 sequence header OBU, AV1 Bitstream & Decoding Process Specification 5.3 and 5.5.
+
+obu_header() {
+    obu_forbidden_bit                               f(1)
+    obu_type                                        f(4)
+    obu_extension_flag                              f(1)
+    obu_has_size_field                              f(1)
+    obu_reserved_1bit                               f(1)
+    if ( obu_extension_flag == 1 )
+        obu_extension_header()                      f(8)
+}
+    followed by obu_size leb128() when obu_has_size_field
+
+sequence_header_obu( ) {
+    seq_profile                                     f(3)
+    still_picture                                   f(1)
+    reduced_still_picture_header                    f(1)
+    if ( reduced_still_picture_header ) {
+        seq_level_idx[ 0 ]                          f(5)
+    } else {
+        timing_info_present_flag                    f(1)
+        if ( timing_info_present_flag ) {
+            timing_info( )
+            decoder_model_info_present_flag         f(1)
+            if ( decoder_model_info_present_flag )
+                decoder_model_info( )
+        }
+        initial_display_delay_present_flag          f(1)
+        operating_points_cnt_minus_1                f(5)
+        for ( i = 0; i <= operating_points_cnt_minus_1; i++ ) {
+            operating_point_idc[ i ]                f(12)
+            seq_level_idx[ i ]                      f(5)
+            if ( seq_level_idx[ i ] > 7 )
+                seq_tier[ i ]                       f(1)
+            if ( decoder_model_info_present_flag ) {
+                decoder_model_present_for_this_op[ i ]  f(1)
+                if ( decoder_model_present_for_this_op[ i ] )
+                    operating_parameters_info( i )
+            }
+            if ( initial_display_delay_present_flag ) {
+                initial_display_delay_present_for_this_op[ i ]  f(1)
+                if ( initial_display_delay_present_for_this_op[ i ] )
+                    initial_display_delay_minus_1[ i ]  f(4)
+            }
+        }
+    }
+    frame_width_bits_minus_1                        f(4)
+    frame_height_bits_minus_1                       f(4)
+    max_frame_width_minus_1                         f(n)
+    max_frame_height_minus_1                        f(n)
+    if ( !reduced_still_picture_header )
+        frame_id_numbers_present_flag               f(1)
+    if ( frame_id_numbers_present_flag ) {
+        delta_frame_id_length_minus_2               f(4)
+        additional_frame_id_length_minus_1          f(3)
+    }
+    use_128x128_superblock                          f(1)
+    enable_filter_intra                             f(1)
+    enable_intra_edge_filter                        f(1)
+    if ( !reduced_still_picture_header ) {
+        enable_interintra_compound                  f(1)
+        enable_masked_compound                      f(1)
+        enable_warped_motion                        f(1)
+        enable_dual_filter                          f(1)
+        enable_order_hint                           f(1)
+        if ( enable_order_hint ) {
+            enable_jnt_comp                         f(1)
+            enable_ref_frame_mvs                    f(1)
+        }
+        seq_choose_screen_content_tools             f(1)
+        if ( !seq_choose_screen_content_tools )
+            seq_force_screen_content_tools          f(1)
+        if ( seq_force_screen_content_tools > 0 ) {
+            seq_choose_integer_mv                   f(1)
+            if ( !seq_choose_integer_mv )
+                seq_force_integer_mv                f(1)
+        }
+        if ( enable_order_hint )
+            order_hint_bits_minus_1                 f(3)
+    }
+    enable_superres                                 f(1)
+    enable_cdef                                     f(1)
+    enable_restoration                              f(1)
+    color_config( )
+    film_grain_params_present                       f(1)
+}
+
+color_config( ) is decoded as in 5.5.2; the chroma subsampling depends on seq_profile:
+profile 0 is 4:2:0, profile 1 is 4:4:4, profile 2 is 4:2:2 below 12 bits.
+
+**/
+
+use super::{ColourDescription, Timing};
+use super::bits::BitReader;
+use Mp4Error;
+
+pub const OBU_SEQUENCE_HEADER: u8 = 1;
+
+// color_config() defaults: CP_BT_709 / TC_SRGB / MC_IDENTITY select 4:4:4 full range.
+const CP_BT_709: u8 = 1;
+const TC_SRGB: u8 = 13;
+const MC_IDENTITY: u8 = 0;
+const CP_UNSPECIFIED: u8 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Obu<'a> {
+    pub obu_type: u8,
+    pub payload : &'a [u8]
+}
+
+// Split a sequence of OBUs, as found in `av1C` configOBUs or in a sample.
+// An OBU without `obu_size` extends to the end of the data.
+pub fn parse_obus(mut data: &[u8]) -> Result<Vec<Obu<'_>>, Mp4Error> {
+    let mut obus = Vec::new();
+    while !data.is_empty() {
+        let header = data[0];
+        let obu_type = (header >> 3) & 0x0F;
+        let extension = header & 0x04 != 0;
+        let has_size = header & 0x02 != 0;
+        let mut pos = if extension { 2 } else { 1 };
+        if pos > data.len() {
+            return Err(Mp4Error::InvalidBitstream("OBU header is truncated"));
+        }
+        let size = if has_size {
+            let (size, len) = read_leb128(&data[pos..])?;
+            pos += len;
+            size as usize
+        } else {
+            data.len() - pos
+        };
+        if data.len() - pos < size {
+            return Err(Mp4Error::InvalidBitstream("OBU is truncated"));
+        }
+        obus.push(Obu {
+            obu_type,
+            payload : &data[pos..pos + size]
+        });
+        data = &data[pos + size..];
+    }
+    Ok(obus)
+}
+
+// leb128(), returns the value and the number of bytes read.
+fn read_leb128(data: &[u8]) -> Result<(u64, usize), Mp4Error> {
+    let mut value = 0u64;
+    for (i, byte) in data.iter().take(8).enumerate() {
+        value |= ((byte & 0x7F) as u64) << (i * 7);
+        if byte & 0x80 == 0 {
+            return Ok((value, i + 1));
+        }
+    }
+    Err(Mp4Error::InvalidBitstream("leb128 value is truncated"))
+}
+
+// uvlc()
+fn read_uvlc(r: &mut BitReader) -> Result<u32, Mp4Error> {
+    let mut leading_zeros = 0;
+    while !r.read_bit()? {
+        leading_zeros += 1;
+    }
+    if leading_zeros >= 32 {
+        return Ok(u32::MAX);
+    }
+    let value = r.read_bits(leading_zeros)?;
+    Ok((value + (1u64 << leading_zeros) - 1) as u32)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SequenceHeader {
+    pub seq_profile  : u8,
+    pub still_picture: bool,
+    pub reduced_still_picture_header: bool,
+    pub seq_level_idx: u8,     // of operating point 0
+    pub seq_tier     : u8,     // of operating point 0
+    pub timing       : Option<Timing>,
+    pub max_frame_width : u32,
+    pub max_frame_height: u32,
+    pub bit_depth    : u8,
+    pub mono_chrome  : bool,
+    pub color_description: Option<ColourDescription>,
+    pub color_range  : bool,   // full range when set
+    pub subsampling_x: bool,
+    pub subsampling_y: bool,
+    pub chroma_sample_position: u8,
+    pub film_grain_params_present: bool
+}
+
+impl SequenceHeader {
+    // Decode the first sequence header OBU of `obus`.
+    pub fn from_obus(obus: &[u8]) -> Result<Option<SequenceHeader>, Mp4Error> {
+        for obu in parse_obus(obus)? {
+            if obu.obu_type == OBU_SEQUENCE_HEADER {
+                return SequenceHeader::parse(obu.payload).map(Some);
+            }
+        }
+        Ok(None)
+    }
+    // `payload` is the OBU payload, without the OBU header and size.
+    pub fn parse(payload: &[u8]) -> Result<SequenceHeader, Mp4Error> {
+        let mut r = BitReader::new(payload);
+
+        let seq_profile = r.read_u8(3)?;
+        if seq_profile > 2 {
+            return Err(Mp4Error::InvalidBitstream("seq_profile is out of range"));
+        }
+        let still_picture = r.read_bit()?;
+        let reduced_still_picture_header = r.read_bit()?;
+        let mut timing = None;
+        let seq_level_idx;
+        let mut seq_tier = 0;
+        if reduced_still_picture_header {
+            seq_level_idx = r.read_u8(5)?;
+        } else {
+            let mut decoder_model_info_present = false;
+            let mut buffer_delay_length = 0;
+            if r.read_bit()? {
+                let num_units_in_display_tick = r.read_u32(32)?;
+                let time_scale = r.read_u32(32)?;
+                let equal_picture_interval = r.read_bit()?;
+                if equal_picture_interval {
+                    // num_ticks_per_picture_minus_1
+                    read_uvlc(&mut r)?;
+                }
+                timing = Some(Timing {
+                    num_units_in_tick: num_units_in_display_tick,
+                    time_scale,
+                    fixed_frame_rate : equal_picture_interval
+                });
+                decoder_model_info_present = r.read_bit()?;
+                if decoder_model_info_present {
+                    buffer_delay_length = r.read_bits(5)? as usize + 1;
+                    // num_units_in_decoding_tick, buffer_removal_time_length_minus_1,
+                    // frame_presentation_time_length_minus_1
+                    r.skip(32 + 5 + 5)?;
+                }
+            }
+            let initial_display_delay_present = r.read_bit()?;
+            let operating_points = r.read_u8(5)? + 1;
+            let mut first_point = None;
+            for _ in 0..operating_points {
+                // operating_point_idc
+                r.skip(12)?;
+                let level = r.read_u8(5)?;
+                let tier = if level > 7 { r.read_u8(1)? } else { 0 };
+                if decoder_model_info_present && r.read_bit()? {
+                    // decoder_buffer_delay, encoder_buffer_delay, low_delay_mode_flag
+                    r.skip(2 * buffer_delay_length + 1)?;
+                }
+                if initial_display_delay_present && r.read_bit()? {
+                    r.skip(4)?;
+                }
+                if first_point.is_none() {
+                    first_point = Some((level, tier));
+                }
+            }
+            let (level, tier) = first_point.unwrap_or((0, 0));
+            seq_level_idx = level;
+            seq_tier = tier;
+        }
+
+        let frame_width_bits = r.read_bits(4)? as usize + 1;
+        let frame_height_bits = r.read_bits(4)? as usize + 1;
+        let max_frame_width = r.read_u32(frame_width_bits)? + 1;
+        let max_frame_height = r.read_u32(frame_height_bits)? + 1;
+        if !reduced_still_picture_header && r.read_bit()? {
+            // delta_frame_id_length_minus_2, additional_frame_id_length_minus_1
+            r.skip(4 + 3)?;
+        }
+        // use_128x128_superblock, enable_filter_intra, enable_intra_edge_filter
+        r.skip(3)?;
+        if !reduced_still_picture_header {
+            // enable_interintra_compound, enable_masked_compound,
+            // enable_warped_motion, enable_dual_filter
+            r.skip(4)?;
+            let enable_order_hint = r.read_bit()?;
+            if enable_order_hint {
+                // enable_jnt_comp, enable_ref_frame_mvs
+                r.skip(2)?;
+            }
+            let seq_force_screen_content_tools = if r.read_bit()? { 2 } else { r.read_u8(1)? };
+            if seq_force_screen_content_tools > 0 && !r.read_bit()? {
+                // seq_force_integer_mv
+                r.skip(1)?;
+            }
+            if enable_order_hint {
+                // order_hint_bits_minus_1
+                r.skip(3)?;
+            }
+        }
+        // enable_superres, enable_cdef, enable_restoration
+        r.skip(3)?;
+
+        // color_config()
+        let high_bitdepth = r.read_bit()?;
+        let bit_depth = if seq_profile == 2 && high_bitdepth {
+            if r.read_bit()? { 12 } else { 10 }
+        } else if high_bitdepth {
+            10
+        } else {
+            8
+        };
+        let mono_chrome = seq_profile != 1 && r.read_bit()?;
+        let color_description = if r.read_bit()? {
+            Some(ColourDescription {
+                colour_primaries        : r.read_u8(8)?,
+                transfer_characteristics: r.read_u8(8)?,
+                matrix_coefficients     : r.read_u8(8)?
+            })
+        } else {
+            None
+        };
+        let (primaries, transfer, matrix) = match color_description {
+            Some(ref c) => (c.colour_primaries, c.transfer_characteristics, c.matrix_coefficients),
+            None => (CP_UNSPECIFIED, CP_UNSPECIFIED, CP_UNSPECIFIED)
+        };
+        let color_range;
+        let mut subsampling_x = true;
+        let mut subsampling_y = true;
+        let mut chroma_sample_position = 0;
+        if mono_chrome {
+            color_range = r.read_bit()?;
+        } else if primaries == CP_BT_709 && transfer == TC_SRGB && matrix == MC_IDENTITY {
+            color_range = true;
+            subsampling_x = false;
+            subsampling_y = false;
+        } else {
+            color_range = r.read_bit()?;
+            match seq_profile {
+                0 => {},
+                1 => {
+                    subsampling_x = false;
+                    subsampling_y = false;
+                },
+                _ => {
+                    if bit_depth == 12 {
+                        subsampling_x = r.read_bit()?;
+                        subsampling_y = subsampling_x && r.read_bit()?;
+                    } else {
+                        subsampling_y = false;
+                    }
+                }
+            }
+            if subsampling_x && subsampling_y {
+                chroma_sample_position = r.read_u8(2)?;
+            }
+        }
+        if !mono_chrome {
+            // separate_uv_delta_q
+            r.skip(1)?;
+        }
+        let film_grain_params_present = r.read_bit()?;
+
+        Ok(SequenceHeader {
+            seq_profile,
+            still_picture,
+            reduced_still_picture_header,
+            seq_level_idx,
+            seq_tier,
+            timing,
+            max_frame_width,
+            max_frame_height,
+            bit_depth,
+            mono_chrome,
+            color_description,
+            color_range,
+            subsampling_x,
+            subsampling_y,
+            chroma_sample_position,
+            film_grain_params_present
+        })
+    }
+}
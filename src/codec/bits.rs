@@ -0,0 +1,88 @@
+use Mp4Error;
+
+// Drop the emulation prevention bytes (`0x000003` -> `0x0000`) of a NAL unit payload.
+pub fn unescape_rbsp(data: &[u8]) -> Vec<u8> {
+    let mut rbsp = Vec::with_capacity(data.len());
+    let mut zeros = 0;
+    for &byte in data {
+        if zeros >= 2 && byte == 3 {
+            zeros = 0;
+            continue;
+        }
+        zeros = if byte == 0 { zeros + 1 } else { 0 };
+        rbsp.push(byte);
+    }
+    rbsp
+}
+
+// MSB-first bit reader with the Exp-Golomb codes of H.264 / H.265 (9.1).
+pub struct BitReader<'a> {
+    data: &'a [u8],
+    pos : usize    // in bits
+}
+
+impl<'a> BitReader<'a> {
+    pub fn new(data: &'a [u8]) -> BitReader<'a> {
+        BitReader {
+            data,
+            pos : 0
+        }
+    }
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+    pub fn bits_left(&self) -> usize {
+        self.data.len() * 8 - self.pos
+    }
+    pub fn read_bit(&mut self) -> Result<bool, Mp4Error> {
+        let byte = match self.data.get(self.pos / 8) {
+            Some(byte) => *byte,
+            None => return Err(Mp4Error::InvalidBitstream("bitstream ended unexpectedly"))
+        };
+        let bit = (byte >> (7 - self.pos % 8)) & 1;
+        self.pos += 1;
+        Ok(bit == 1)
+    }
+    // u(n), n <= 64
+    pub fn read_bits(&mut self, n: usize) -> Result<u64, Mp4Error> {
+        let mut value = 0u64;
+        for _ in 0..n {
+            value = (value << 1) | self.read_bit()? as u64;
+        }
+        Ok(value)
+    }
+    pub fn read_u8(&mut self, n: usize) -> Result<u8, Mp4Error> {
+        Ok(self.read_bits(n)? as u8)
+    }
+    pub fn read_u16(&mut self, n: usize) -> Result<u16, Mp4Error> {
+        Ok(self.read_bits(n)? as u16)
+    }
+    pub fn read_u32(&mut self, n: usize) -> Result<u32, Mp4Error> {
+        Ok(self.read_bits(n)? as u32)
+    }
+    pub fn skip(&mut self, n: usize) -> Result<(), Mp4Error> {
+        if n > self.bits_left() {
+            return Err(Mp4Error::InvalidBitstream("bitstream ended unexpectedly"));
+        }
+        self.pos += n;
+        Ok(())
+    }
+    // ue(v)
+    pub fn read_ue(&mut self) -> Result<u32, Mp4Error> {
+        let mut leading_zeros = 0;
+        while !self.read_bit()? {
+            leading_zeros += 1;
+            if leading_zeros > 31 {
+                return Err(Mp4Error::InvalidBitstream("exp-Golomb code is too long"));
+            }
+        }
+        let suffix = self.read_bits(leading_zeros)?;
+        Ok(((1u64 << leading_zeros) - 1 + suffix) as u32)
+    }
+    // se(v)
+    pub fn read_se(&mut self) -> Result<i32, Mp4Error> {
+        let code = self.read_ue()? as i64;
+        let value = if code % 2 == 1 { (code + 1) / 2 } else { -(code / 2) };
+        Ok(value as i32)
+    }
+}
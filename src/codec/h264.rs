@@ -0,0 +1,344 @@
+/*
+
+H.264 / AVC parameter sets, ITU-T H.264 7.3.2.1.1 and 7.3.2.2.
+
+seq_parameter_set_data( ) {
+    profile_idc                                     u(8)
+    constraint_set0_flag .. constraint_set5_flag    u(1) x 6
+    reserved_zero_2bits                             u(2)
+    level_idc                                       u(8)
+    seq_parameter_set_id                            ue(v)
+    if( profile_idc == 100 || profile_idc == 110 || profile_idc == 122 || profile_idc == 244 ||
+        profile_idc == 44 || profile_idc == 83 || profile_idc == 86 || profile_idc == 118 ||
+        profile_idc == 128 || profile_idc == 138 || profile_idc == 139 || profile_idc == 134 ||
+        profile_idc == 135 ) {
+        chroma_format_idc                           ue(v)
+        if( chroma_format_idc == 3 )
+            separate_colour_plane_flag              u(1)
+        bit_depth_luma_minus8                       ue(v)
+        bit_depth_chroma_minus8                     ue(v)
+        qpprime_y_zero_transform_bypass_flag        u(1)
+        seq_scaling_matrix_present_flag             u(1)
+        if( seq_scaling_matrix_present_flag )
+            for( i = 0; i < ( ( chroma_format_idc != 3 ) ? 8 : 12 ); i++ ) {
+                seq_scaling_list_present_flag[ i ]  u(1)
+                if( seq_scaling_list_present_flag[ i ] )
+                    scaling_list( i < 6 ? 16 : 64 )
+            }
+    }
+    log2_max_frame_num_minus4                       ue(v)
+    pic_order_cnt_type                              ue(v)
+    if( pic_order_cnt_type == 0 )
+        log2_max_pic_order_cnt_lsb_minus4           ue(v)
+    else if( pic_order_cnt_type == 1 ) {
+        delta_pic_order_always_zero_flag            u(1)
+        offset_for_non_ref_pic                      se(v)
+        offset_for_top_to_bottom_field              se(v)
+        num_ref_frames_in_pic_order_cnt_cycle       ue(v)
+        for( i = 0; i < num_ref_frames_in_pic_order_cnt_cycle; i++ )
+            offset_for_ref_frame[ i ]               se(v)
+    }
+    max_num_ref_frames                              ue(v)
+    gaps_in_frame_num_value_allowed_flag            u(1)
+    pic_width_in_mbs_minus1                         ue(v)
+    pic_height_in_map_units_minus1                  ue(v)
+    frame_mbs_only_flag                             u(1)
+    if( !frame_mbs_only_flag )
+        mb_adaptive_frame_field_flag                u(1)
+    direct_8x8_inference_flag                       u(1)
+    frame_cropping_flag                             u(1)
+    if( frame_cropping_flag ) {
+        frame_crop_left_offset                      ue(v)
+        frame_crop_right_offset                     ue(v)
+        frame_crop_top_offset                       ue(v)
+        frame_crop_bottom_offset                    ue(v)
+    }
+    vui_parameters_present_flag                     u(1)
+    if( vui_parameters_present_flag )
+        vui_parameters( )
+}
+
+The cropped picture size follows 7.4.2.1.1:
+
+    ChromaArrayType = separate_colour_plane_flag ? 0 : chroma_format_idc
+    CropUnitX = ChromaArrayType == 0 ? 1 : SubWidthC
+    CropUnitY = ( ChromaArrayType == 0 ? 1 : SubHeightC ) * ( 2 − frame_mbs_only_flag )
+
+**/
+
+use super::Vui;
+use super::bits::{BitReader, unescape_rbsp};
+use Mp4Error;
+
+const NAL_UNIT_TYPE_SPS: u8 = 7;
+const NAL_UNIT_TYPE_PPS: u8 = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameCrop {
+    pub left  : u32,
+    pub right : u32,
+    pub top   : u32,
+    pub bottom: u32
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sps {
+    pub profile_idc     : u8,
+    pub constraint_flags: u8,   // constraint_set0_flag in the high bit
+    pub level_idc       : u8,
+    pub seq_parameter_set_id: u32,
+    pub chroma_format_idc   : u32,  // 0: monochrome, 1: 4:2:0, 2: 4:2:2, 3: 4:4:4
+    pub separate_colour_plane: bool,
+    pub bit_depth_luma  : u8,
+    pub bit_depth_chroma: u8,
+    pub pic_order_cnt_type: u32,
+    pub max_num_ref_frames: u32,
+    pub pic_width_in_mbs       : u32,
+    pub pic_height_in_map_units: u32,
+    pub frame_mbs_only: bool,
+    pub frame_crop    : Option<FrameCrop>,
+    pub vui           : Option<Vui>
+}
+
+impl Sps {
+    // `nal` is a complete SPS NAL unit, header byte included, as stored in `avcC`.
+    pub fn parse(nal: &[u8]) -> Result<Sps, Mp4Error> {
+        match nal.first() {
+            Some(byte) if byte & 0x1F == NAL_UNIT_TYPE_SPS => {},
+            _ => return Err(Mp4Error::InvalidBitstream("not an H.264 SPS NAL unit"))
+        }
+        let rbsp = unescape_rbsp(&nal[1..]);
+        let mut r = BitReader::new(&rbsp);
+
+        let profile_idc = r.read_u8(8)?;
+        let constraint_flags = r.read_u8(8)?;
+        let level_idc = r.read_u8(8)?;
+        let seq_parameter_set_id = r.read_ue()?;
+
+        let mut chroma_format_idc = 1;
+        let mut separate_colour_plane = false;
+        let mut bit_depth_luma = 8;
+        let mut bit_depth_chroma = 8;
+        match profile_idc {
+            100 | 110 | 122 | 244 | 44 | 83 | 86 | 118 | 128 | 138 | 139 | 134 | 135 => {
+                chroma_format_idc = r.read_ue()?;
+                if chroma_format_idc > 3 {
+                    return Err(Mp4Error::InvalidBitstream("chroma_format_idc is out of range"));
+                }
+                if chroma_format_idc == 3 {
+                    separate_colour_plane = r.read_bit()?;
+                }
+                bit_depth_luma = 8 + read_bit_depth(&mut r)?;
+                bit_depth_chroma = 8 + read_bit_depth(&mut r)?;
+                // qpprime_y_zero_transform_bypass_flag
+                r.skip(1)?;
+                // seq_scaling_matrix_present_flag
+                if r.read_bit()? {
+                    let lists = if chroma_format_idc != 3 { 8 } else { 12 };
+                    for i in 0..lists {
+                        if r.read_bit()? {
+                            skip_scaling_list(&mut r, if i < 6 { 16 } else { 64 })?;
+                        }
+                    }
+                }
+            },
+            _ => {}
+        }
+
+        // log2_max_frame_num_minus4
+        r.read_ue()?;
+        let pic_order_cnt_type = r.read_ue()?;
+        if pic_order_cnt_type == 0 {
+            // log2_max_pic_order_cnt_lsb_minus4
+            r.read_ue()?;
+        } else if pic_order_cnt_type == 1 {
+            // delta_pic_order_always_zero_flag, offset_for_non_ref_pic, offset_for_top_to_bottom_field
+            r.skip(1)?;
+            r.read_se()?;
+            r.read_se()?;
+            let cycle = r.read_ue()?;
+            for _ in 0..cycle {
+                r.read_se()?;
+            }
+        }
+        let max_num_ref_frames = r.read_ue()?;
+        // gaps_in_frame_num_value_allowed_flag
+        r.skip(1)?;
+        let pic_width_in_mbs = r.read_ue()? + 1;
+        let pic_height_in_map_units = r.read_ue()? + 1;
+        let frame_mbs_only = r.read_bit()?;
+        if !frame_mbs_only {
+            // mb_adaptive_frame_field_flag
+            r.skip(1)?;
+        }
+        // direct_8x8_inference_flag
+        r.skip(1)?;
+        let frame_crop = if r.read_bit()? {
+            Some(FrameCrop {
+                left  : r.read_ue()?,
+                right : r.read_ue()?,
+                top   : r.read_ue()?,
+                bottom: r.read_ue()?
+            })
+        } else {
+            None
+        };
+        let vui = if r.read_bit()? {
+            Some(parse_vui(&mut r)?)
+        } else {
+            None
+        };
+
+        Ok(Sps {
+            profile_idc,
+            constraint_flags,
+            level_idc,
+            seq_parameter_set_id,
+            chroma_format_idc,
+            separate_colour_plane,
+            bit_depth_luma,
+            bit_depth_chroma,
+            pic_order_cnt_type,
+            max_num_ref_frames,
+            pic_width_in_mbs,
+            pic_height_in_map_units,
+            frame_mbs_only,
+            frame_crop,
+            vui
+        })
+    }
+    // Width and height of the decoded frame, in luma samples, before cropping.
+    // Sizes out of the u32 range saturate.
+    pub fn coded_width(&self) -> u32 {
+        self.pic_width_in_mbs.saturating_mul(16)
+    }
+    pub fn coded_height(&self) -> u32 {
+        let field_factor: u32 = if self.frame_mbs_only { 1 } else { 2 };
+        field_factor.saturating_mul(self.pic_height_in_map_units).saturating_mul(16)
+    }
+    // Width and height of the cropped output picture.
+    pub fn width(&self) -> u32 {
+        let (crop_unit_x, _) = self.crop_units();
+        let crop = self.frame_crop.map(|c| c.left.saturating_add(c.right)).unwrap_or(0);
+        self.coded_width().saturating_sub(crop_unit_x.saturating_mul(crop))
+    }
+    pub fn height(&self) -> u32 {
+        let (_, crop_unit_y) = self.crop_units();
+        let crop = self.frame_crop.map(|c| c.top.saturating_add(c.bottom)).unwrap_or(0);
+        self.coded_height().saturating_sub(crop_unit_y.saturating_mul(crop))
+    }
+    fn crop_units(&self) -> (u32, u32) {
+        let chroma_array_type = if self.separate_colour_plane { 0 } else { self.chroma_format_idc };
+        let (sub_width_c, sub_height_c) = match chroma_array_type {
+            1 => (2, 2),
+            2 => (2, 1),
+            _ => (1, 1)
+        };
+        let field_factor = if self.frame_mbs_only { 1 } else { 2 };
+        (sub_width_c, sub_height_c * field_factor)
+    }
+    // Level 1b is signalled as level_idc 11 with constraint_set3_flag in the
+    // Baseline, Constrained Baseline and Main profiles; reported as 9 here (A.3.1).
+    pub fn level(&self) -> u8 {
+        let constraint_set3 = self.constraint_flags & 0x10 != 0;
+        match (self.profile_idc, self.level_idc) {
+            (66, 11) | (77, 11) if constraint_set3 => 9,
+            (_, level) => level
+        }
+    }
+    // Frames per second from the VUI timing, one frame being two fields (E.2.1).
+    pub fn frame_rate(&self) -> Option<f64> {
+        let timing = self.vui.as_ref()?.timing?;
+        if timing.num_units_in_tick == 0 {
+            return None;
+        }
+        Some(timing.time_scale as f64 / (2.0 * timing.num_units_in_tick as f64))
+    }
+}
+
+fn read_bit_depth(r: &mut BitReader) -> Result<u8, Mp4Error> {
+    let minus8 = r.read_ue()?;
+    if minus8 > 6 {
+        return Err(Mp4Error::InvalidBitstream("bit depth is out of range"));
+    }
+    Ok(minus8 as u8)
+}
+
+// 7.3.2.1.1.1
+fn skip_scaling_list(r: &mut BitReader, size: usize) -> Result<(), Mp4Error> {
+    let mut last_scale = 8i32;
+    let mut next_scale = 8i32;
+    for _ in 0..size {
+        if next_scale != 0 {
+            let delta_scale = r.read_se()?;
+            if !(-128..=127).contains(&delta_scale) {
+                return Err(Mp4Error::InvalidBitstream("delta_scale is out of range"));
+            }
+            next_scale = (last_scale + delta_scale + 256) % 256;
+        }
+        if next_scale != 0 {
+            last_scale = next_scale;
+        }
+    }
+    Ok(())
+}
+
+// E.1.1, up to the timing information.
+fn parse_vui(r: &mut BitReader) -> Result<Vui, Mp4Error> {
+    let mut vui = Vui::parse_common(r)?;
+    // timing_info_present_flag
+    if r.read_bit()? {
+        vui.timing = Some(super::Timing {
+            num_units_in_tick: r.read_u32(32)?,
+            time_scale       : r.read_u32(32)?,
+            fixed_frame_rate : r.read_bit()?
+        });
+    }
+    Ok(vui)
+}
+
+/**
+
+pic_parameter_set_rbsp( ) {
+    pic_parameter_set_id                            ue(v)
+    seq_parameter_set_id                            ue(v)
+    entropy_coding_mode_flag                        u(1)
+    bottom_field_pic_order_in_frame_present_flag    u(1)
+    num_slice_groups_minus1                         ue(v)
+    ...
+}
+
+**/
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pps {
+    pub pic_parameter_set_id: u32,
+    pub seq_parameter_set_id: u32,
+    pub entropy_coding_mode : bool,   // CABAC when set, CAVLC otherwise
+    pub num_slice_groups    : u32
+}
+
+impl Pps {
+    pub fn parse(nal: &[u8]) -> Result<Pps, Mp4Error> {
+        match nal.first() {
+            Some(byte) if byte & 0x1F == NAL_UNIT_TYPE_PPS => {},
+            _ => return Err(Mp4Error::InvalidBitstream("not an H.264 PPS NAL unit"))
+        }
+        let rbsp = unescape_rbsp(&nal[1..]);
+        let mut r = BitReader::new(&rbsp);
+
+        let pic_parameter_set_id = r.read_ue()?;
+        let seq_parameter_set_id = r.read_ue()?;
+        let entropy_coding_mode = r.read_bit()?;
+        // bottom_field_pic_order_in_frame_present_flag
+        r.skip(1)?;
+        let num_slice_groups = r.read_ue()? + 1;
+
+        Ok(Pps {
+            pic_parameter_set_id,
+            seq_parameter_set_id,
+            entropy_coding_mode,
+            num_slice_groups
+        })
+    }
+}
@@ -0,0 +1,315 @@
+/*
+
+H.265 / HEVC parameter sets, ITU-T H.265 7.3.
+
+nal_unit_header( ) {
+    forbidden_zero_bit                              f(1)
+    nal_unit_type                                   u(6)
+    nuh_layer_id                                    u(6)
+    nuh_temporal_id_plus1                           u(3)
+}
+
+profile_tier_level( profilePresentFlag, maxNumSubLayersMinus1 ) {
+    general_profile_space                           u(2)
+    general_tier_flag                               u(1)
+    general_profile_idc                             u(5)
+    for( j = 0; j < 32; j++ )
+        general_profile_compatibility_flag[ j ]     u(1)
+    general_progressive_source_flag                 u(1)
+    general_interlaced_source_flag                  u(1)
+    general_non_packed_constraint_flag              u(1)
+    general_frame_only_constraint_flag              u(1)
+    ...                                             u(44) in total with the four flags above
+    general_level_idc                               u(8)
+    for( i = 0; i < maxNumSubLayersMinus1; i++ ) {
+        sub_layer_profile_present_flag[ i ]         u(1)
+        sub_layer_level_present_flag[ i ]           u(1)
+    }
+    if( maxNumSubLayersMinus1 > 0 )
+        for( i = maxNumSubLayersMinus1; i < 8; i++ )
+            reserved_zero_2bits[ i ]                u(2)
+    for( i = 0; i < maxNumSubLayersMinus1; i++ ) {
+        if( sub_layer_profile_present_flag[ i ] )
+            ...                                     u(88)
+        if( sub_layer_level_present_flag[ i ] )
+            sub_layer_level_idc[ i ]                u(8)
+    }
+}
+
+video_parameter_set_rbsp( ) {
+    vps_video_parameter_set_id                      u(4)
+    vps_base_layer_internal_flag                    u(1)
+    vps_base_layer_available_flag                   u(1)
+    vps_max_layers_minus1                           u(6)
+    vps_max_sub_layers_minus1                       u(3)
+    vps_temporal_id_nesting_flag                    u(1)
+    vps_reserved_0xffff_16bits                      u(16)
+    profile_tier_level( 1, vps_max_sub_layers_minus1 )
+    vps_sub_layer_ordering_info_present_flag        u(1)
+    for( i = ( vps_sub_layer_ordering_info_present_flag ? 0 : vps_max_sub_layers_minus1 );
+            i <= vps_max_sub_layers_minus1; i++ ) {
+        vps_max_dec_pic_buffering_minus1[ i ]       ue(v)
+        vps_max_num_reorder_pics[ i ]               ue(v)
+        vps_max_latency_increase_plus1[ i ]         ue(v)
+    }
+    vps_max_layer_id                                u(6)
+    vps_num_layer_sets_minus1                       ue(v)
+    for( i = 1; i <= vps_num_layer_sets_minus1; i++ )
+        for( j = 0; j <= vps_max_layer_id; j++ )
+            layer_id_included_flag[ i ][ j ]        u(1)
+    vps_timing_info_present_flag                    u(1)
+    if( vps_timing_info_present_flag ) {
+        vps_num_units_in_tick                       u(32)
+        vps_time_scale                              u(32)
+        ...
+    }
+    ...
+}
+
+seq_parameter_set_rbsp( ) {
+    sps_video_parameter_set_id                      u(4)
+    sps_max_sub_layers_minus1                       u(3)
+    sps_temporal_id_nesting_flag                    u(1)
+    profile_tier_level( 1, sps_max_sub_layers_minus1 )
+    sps_seq_parameter_set_id                        ue(v)
+    chroma_format_idc                               ue(v)
+    if( chroma_format_idc == 3 )
+        separate_colour_plane_flag                  u(1)
+    pic_width_in_luma_samples                       ue(v)
+    pic_height_in_luma_samples                      ue(v)
+    conformance_window_flag                         u(1)
+    if( conformance_window_flag ) {
+        conf_win_left_offset                        ue(v)
+        conf_win_right_offset                       ue(v)
+        conf_win_top_offset                         ue(v)
+        conf_win_bottom_offset                      ue(v)
+    }
+    bit_depth_luma_minus8                           ue(v)
+    bit_depth_chroma_minus8                         ue(v)
+    ...
+}
+
+The SPS is decoded up to the bit depths, which is all the picture format needs.
+The conformance window is in chroma sample units (SubWidthC, SubHeightC, table 6-1).
+
+**/
+
+use super::Timing;
+use super::bits::{BitReader, unescape_rbsp};
+use Mp4Error;
+
+pub const NAL_UNIT_TYPE_VPS: u8 = 32;
+pub const NAL_UNIT_TYPE_SPS: u8 = 33;
+pub const NAL_UNIT_TYPE_PPS: u8 = 34;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProfileTierLevel {
+    pub profile_space: u8,
+    pub tier_flag    : bool,   // 0: Main tier, 1: High tier
+    pub profile_idc  : u8,
+    pub profile_compatibility_flags: u32,
+    pub constraint_indicator_flags : u64,  // 48 bits, progressive_source_flag first
+    pub level_idc    : u8      // 30 times the level number
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConformanceWindow {
+    pub left  : u32,
+    pub right : u32,
+    pub top   : u32,
+    pub bottom: u32
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Vps {
+    pub video_parameter_set_id: u8,
+    pub max_layers    : u8,
+    pub max_sub_layers: u8,
+    pub temporal_id_nesting: bool,
+    pub profile_tier_level : ProfileTierLevel,
+    pub timing: Option<Timing>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sps {
+    pub video_parameter_set_id: u8,
+    pub max_sub_layers        : u8,
+    pub temporal_id_nesting   : bool,
+    pub profile_tier_level    : ProfileTierLevel,
+    pub seq_parameter_set_id  : u32,
+    pub chroma_format_idc     : u32,  // 0: monochrome, 1: 4:2:0, 2: 4:2:2, 3: 4:4:4
+    pub separate_colour_plane : bool,
+    pub pic_width_in_luma_samples : u32,
+    pub pic_height_in_luma_samples: u32,
+    pub conformance_window: Option<ConformanceWindow>,
+    pub bit_depth_luma  : u8,
+    pub bit_depth_chroma: u8
+}
+
+// Check the two byte NAL unit header and return the RBSP that follows it.
+fn rbsp(nal: &[u8], nal_unit_type: u8, reason: &'static str) -> Result<Vec<u8>, Mp4Error> {
+    if nal.len() < 2 || (nal[0] >> 1) & 0x3F != nal_unit_type {
+        return Err(Mp4Error::InvalidBitstream(reason));
+    }
+    Ok(unescape_rbsp(&nal[2..]))
+}
+
+impl ProfileTierLevel {
+    fn parse(r: &mut BitReader, max_sub_layers_minus1: u8) -> Result<ProfileTierLevel, Mp4Error> {
+        let profile_space = r.read_u8(2)?;
+        let tier_flag = r.read_bit()?;
+        let profile_idc = r.read_u8(5)?;
+        let profile_compatibility_flags = r.read_u32(32)?;
+        let constraint_indicator_flags = r.read_bits(48)?;
+        let level_idc = r.read_u8(8)?;
+
+        let mut sub_layers = Vec::new();
+        for _ in 0..max_sub_layers_minus1 {
+            sub_layers.push((r.read_bit()?, r.read_bit()?));
+        }
+        if max_sub_layers_minus1 > 0 {
+            r.skip(2 * (8 - max_sub_layers_minus1 as usize))?;
+        }
+        for (profile_present, level_present) in sub_layers {
+            if profile_present {
+                r.skip(88)?;
+            }
+            if level_present {
+                r.skip(8)?;
+            }
+        }
+
+        Ok(ProfileTierLevel {
+            profile_space,
+            tier_flag,
+            profile_idc,
+            profile_compatibility_flags,
+            constraint_indicator_flags,
+            level_idc
+        })
+    }
+}
+
+impl Vps {
+    // `nal` is a complete VPS NAL unit, header included, as stored in `hvcC`.
+    pub fn parse(nal: &[u8]) -> Result<Vps, Mp4Error> {
+        let rbsp = rbsp(nal, NAL_UNIT_TYPE_VPS, "not an H.265 VPS NAL unit")?;
+        let mut r = BitReader::new(&rbsp);
+
+        let video_parameter_set_id = r.read_u8(4)?;
+        // vps_base_layer_internal_flag, vps_base_layer_available_flag
+        r.skip(2)?;
+        let max_layers = r.read_u8(6)? + 1;
+        let max_sub_layers_minus1 = r.read_u8(3)?;
+        let temporal_id_nesting = r.read_bit()?;
+        // vps_reserved_0xffff_16bits
+        r.skip(16)?;
+        let profile_tier_level = ProfileTierLevel::parse(&mut r, max_sub_layers_minus1)?;
+
+        let ordering_info_present = r.read_bit()?;
+        let first = if ordering_info_present { 0 } else { max_sub_layers_minus1 };
+        for _ in first..max_sub_layers_minus1 + 1 {
+            r.read_ue()?;
+            r.read_ue()?;
+            r.read_ue()?;
+        }
+        let max_layer_id = r.read_u8(6)? as usize;
+        let num_layer_sets_minus1 = r.read_ue()? as usize;
+        r.skip(num_layer_sets_minus1 * (max_layer_id + 1))?;
+        let timing = if r.read_bit()? {
+            Some(Timing {
+                num_units_in_tick: r.read_u32(32)?,
+                time_scale       : r.read_u32(32)?,
+                fixed_frame_rate : false
+            })
+        } else {
+            None
+        };
+
+        Ok(Vps {
+            video_parameter_set_id,
+            max_layers,
+            max_sub_layers: max_sub_layers_minus1 + 1,
+            temporal_id_nesting,
+            profile_tier_level,
+            timing
+        })
+    }
+}
+
+impl Sps {
+    // `nal` is a complete SPS NAL unit, header included, as stored in `hvcC`.
+    pub fn parse(nal: &[u8]) -> Result<Sps, Mp4Error> {
+        let rbsp = rbsp(nal, NAL_UNIT_TYPE_SPS, "not an H.265 SPS NAL unit")?;
+        let mut r = BitReader::new(&rbsp);
+
+        let video_parameter_set_id = r.read_u8(4)?;
+        let max_sub_layers_minus1 = r.read_u8(3)?;
+        let temporal_id_nesting = r.read_bit()?;
+        let profile_tier_level = ProfileTierLevel::parse(&mut r, max_sub_layers_minus1)?;
+        let seq_parameter_set_id = r.read_ue()?;
+        let chroma_format_idc = r.read_ue()?;
+        if chroma_format_idc > 3 {
+            return Err(Mp4Error::InvalidBitstream("chroma_format_idc is out of range"));
+        }
+        let separate_colour_plane = chroma_format_idc == 3 && r.read_bit()?;
+        let pic_width_in_luma_samples = r.read_ue()?;
+        let pic_height_in_luma_samples = r.read_ue()?;
+        let conformance_window = if r.read_bit()? {
+            Some(ConformanceWindow {
+                left  : r.read_ue()?,
+                right : r.read_ue()?,
+                top   : r.read_ue()?,
+                bottom: r.read_ue()?
+            })
+        } else {
+            None
+        };
+        let bit_depth_luma = 8 + read_bit_depth(&mut r)?;
+        let bit_depth_chroma = 8 + read_bit_depth(&mut r)?;
+
+        Ok(Sps {
+            video_parameter_set_id,
+            max_sub_layers        : max_sub_layers_minus1 + 1,
+            temporal_id_nesting,
+            profile_tier_level,
+            seq_parameter_set_id,
+            chroma_format_idc,
+            separate_colour_plane,
+            pic_width_in_luma_samples,
+            pic_height_in_luma_samples,
+            conformance_window,
+            bit_depth_luma,
+            bit_depth_chroma
+        })
+    }
+    // Width and height of the cropped output picture; a window past the picture crops it all.
+    pub fn width(&self) -> u32 {
+        let (sub_width_c, _) = self.chroma_subsampling();
+        let crop = self.conformance_window.map(|w| w.left.saturating_add(w.right)).unwrap_or(0);
+        self.pic_width_in_luma_samples.saturating_sub(sub_width_c.saturating_mul(crop))
+    }
+    pub fn height(&self) -> u32 {
+        let (_, sub_height_c) = self.chroma_subsampling();
+        let crop = self.conformance_window.map(|w| w.top.saturating_add(w.bottom)).unwrap_or(0);
+        self.pic_height_in_luma_samples.saturating_sub(sub_height_c.saturating_mul(crop))
+    }
+    fn chroma_subsampling(&self) -> (u32, u32) {
+        if self.separate_colour_plane {
+            return (1, 1);
+        }
+        match self.chroma_format_idc {
+            1 => (2, 2),
+            2 => (2, 1),
+            _ => (1, 1)
+        }
+    }
+}
+
+fn read_bit_depth(r: &mut BitReader) -> Result<u8, Mp4Error> {
+    let minus8 = r.read_ue()?;
+    if minus8 > 8 {
+        return Err(Mp4Error::InvalidBitstream("bit depth is out of range"));
+    }
+    Ok(minus8 as u8)
+}
@@ -0,0 +1,104 @@
+/*
+
+Codec bitstream decoders for the configuration records carried in sample entries.
+
+    h264    sequence / picture parameter sets from `avcC`
+    h265    video / sequence parameter sets from `hvcC`
+    av1     sequence header OBU from `av1C`
+    aac     AudioSpecificConfig from `esds`
+
+The H.264 / H.265 parameter sets are NAL units; their payload is read as RBSP,
+i.e. with the emulation prevention bytes removed.
+
+**/
+
+mod bits;
+pub mod h264;
+pub mod h265;
+pub mod av1;
+pub mod aac;
+
+use self::bits::BitReader;
+use Mp4Error;
+
+// Video usability information shared by H.264 (E.1.1) and H.265 (E.2.1).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Vui {
+    pub aspect_ratio_idc   : Option<u8>,
+    pub sample_aspect_ratio: Option<(u16, u16)>,   // sar_width, sar_height; None if unspecified
+    pub video_format       : Option<u8>,
+    pub video_full_range   : bool,
+    pub colour_description : Option<ColourDescription>,
+    pub timing             : Option<Timing>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColourDescription {
+    pub colour_primaries        : u8,
+    pub transfer_characteristics: u8,
+    pub matrix_coefficients     : u8
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timing {
+    pub num_units_in_tick: u32,
+    pub time_scale       : u32,
+    pub fixed_frame_rate : bool   // H.264 fixed_frame_rate_flag, AV1 equal_picture_interval
+}
+
+// Table E-1
+const SAMPLE_ASPECT_RATIOS: [(u16, u16); 17] = [
+    (0, 0), (1, 1), (12, 11), (10, 11), (16, 11), (40, 33), (24, 11), (20, 11), (32, 11),
+    (80, 33), (18, 11), (15, 11), (64, 33), (160, 99), (4, 3), (3, 2), (2, 1)
+];
+const EXTENDED_SAR: u8 = 255;
+
+impl Vui {
+    // The fields up to and including the chroma sample location, identical in both codecs.
+    fn parse_common(r: &mut BitReader) -> Result<Vui, Mp4Error> {
+        let mut vui = Vui {
+            aspect_ratio_idc   : None,
+            sample_aspect_ratio: None,
+            video_format       : None,
+            video_full_range   : false,
+            colour_description : None,
+            timing             : None
+        };
+        // aspect_ratio_info_present_flag
+        if r.read_bit()? {
+            let idc = r.read_u8(8)?;
+            vui.aspect_ratio_idc = Some(idc);
+            vui.sample_aspect_ratio = if idc == EXTENDED_SAR {
+                Some((r.read_u16(16)?, r.read_u16(16)?))
+            } else {
+                match SAMPLE_ASPECT_RATIOS.get(idc as usize) {
+                    Some(&(0, 0)) | None => None,
+                    Some(&sar) => Some(sar)
+                }
+            };
+        }
+        // overscan_info_present_flag, overscan_appropriate_flag
+        if r.read_bit()? {
+            r.skip(1)?;
+        }
+        // video_signal_type_present_flag
+        if r.read_bit()? {
+            vui.video_format = Some(r.read_u8(3)?);
+            vui.video_full_range = r.read_bit()?;
+            // colour_description_present_flag
+            if r.read_bit()? {
+                vui.colour_description = Some(ColourDescription {
+                    colour_primaries        : r.read_u8(8)?,
+                    transfer_characteristics: r.read_u8(8)?,
+                    matrix_coefficients     : r.read_u8(8)?
+                });
+            }
+        }
+        // chroma_loc_info_present_flag
+        if r.read_bit()? {
+            r.read_ue()?;
+            r.read_ue()?;
+        }
+        Ok(vui)
+    }
+}
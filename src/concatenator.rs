@@ -0,0 +1,238 @@
+/*
+
+Concatenator: join progressive files one after the other, without re-encoding.
+
+Tracks are matched on `track_ID`: every file must have the same tracks, with the
+same handler and media timescale. A sample description of a later file that is
+byte for byte identical to one already in the track reuses its index; any other is
+added to the `stsd` of the first file, and its samples refer to the new index.
+
+Each file starts where the previous one ended, at the `mvhd` duration of the files
+before it. The last sample of a track that ends early is stretched to close the
+gap, so the tracks stay in sync.
+
+When any of the files has an edit list, the joined track gets one made of the
+edits of every file, moved to where the media of that file starts; a file without
+edits is shown whole, and the edits of a file are extended to its `mvhd` duration.
+The leading samples each file hides, such as the 1024 or 2112 priming samples of
+AAC, thus stay hidden. Edits that carry on from one another are merged.
+
+The other boxes come from the first file, and the file is written with a single
+`mdat` (see `progressive`).
+
+**/
+
+use std::io::{Read, Seek, Write};
+
+use atom::{Atom, Kind, Moov, Trak, Elst, ELST_Entry, SampleEntry};
+use progressive::{self, Track};
+use sample_table::SampleInfo;
+use timeline::rescale;
+use {Mp4File, Mp4Error};
+
+pub fn concatenate<R: Read + Seek, W: Write>(sources: &mut [Mp4File<R>], w: &mut W) -> Result<(), Mp4Error> {
+    let mut moovs: Vec<Moov> = Vec::new();
+    for mp4 in sources.iter() {
+        if let Some(moof) = mp4.moofs().first() {
+            return Err(moof.header().invalid_value("file is fragmented"));
+        }
+        moovs.push(progressive::source_moov(mp4)?);
+    }
+    let mut moov = match moovs.first() {
+        Some(moov) => moov.clone(),
+        None => return Err(Mp4Error::InvalidValue {
+            kind  : Kind::moov,
+            offset: 0,
+            reason: "no files to concatenate"
+        })
+    };
+    let movie_timescale = moov.mvhd().map(|mvhd| mvhd.timescale()).unwrap_or(1);
+    for other in &moovs[1..] {
+        if other.traks().len() != moov.traks().len() {
+            return Err(other.header().invalid_value("files have different tracks"));
+        }
+    }
+
+    let mut tracks = Vec::new();
+    let mut added_entries = Vec::new();
+    for trak in moov.traks() {
+        let (track, added) = concatenate_track(trak, &moovs, movie_timescale)?;
+        tracks.push(track);
+        added_entries.push(added);
+    }
+    let mut added = added_entries.iter();
+    for atom in moov.children_mut().iter_mut() {
+        if let Atom::trak(ref mut trak) = *atom {
+            if let Some(entries) = added.next() {
+                for child in trak.children_mut().iter_mut() {
+                    add_entries(child, entries);
+                }
+            }
+        }
+    }
+    progressive::write_sources(sources, w, &moov, &tracks)
+}
+
+// The samples of a track across all files, and the sample descriptions to add to its `stsd`.
+fn concatenate_track(trak: &Trak, moovs: &[Moov], movie_timescale: u32) -> Result<(Track, Vec<SampleEntry>), Mp4Error> {
+    let (track_id, handler_type, timescale) = track_info(trak)?;
+    let mut descriptions: Vec<Vec<u8>> = Vec::new();
+    if let Some(stsd) = trak.stbl().and_then(|stbl| stbl.stsd()) {
+        for entry in stsd.entries() {
+            descriptions.push(entry_bytes(entry)?);
+        }
+    }
+    let mut added = Vec::new();
+    let mut samples: Vec<(usize, SampleInfo)> = Vec::new();
+    let mut elapsed = 0u64;   // sum of the sample durations
+    let mut start = 0u64;     // media time the current file starts at, at the earliest
+    let mut edits: Vec<ELST_Entry> = Vec::new();
+    let mut has_edits = false;
+    for (source, moov) in moovs.iter().enumerate() {
+        let other = match moov.traks().into_iter().find(|other| other.tkhd().is_some_and(|tkhd| tkhd.track_id() == track_id)) {
+            Some(other) => other,
+            None => return Err(moov.header().invalid_value("files have different tracks"))
+        };
+        let (_, other_handler_type, other_timescale) = track_info(other)?;
+        if other_handler_type != handler_type {
+            return Err(other.header().invalid_value("tracks have different handlers"));
+        }
+        if other_timescale != timescale {
+            return Err(other.header().invalid_value("tracks have different media timescales"));
+        }
+
+        if let Some(&mut (_, ref mut last)) = samples.last_mut() {
+            if start > elapsed {
+                last.duration = (last.duration as u64 + start - elapsed).min(u32::MAX as u64) as u32;
+                elapsed = start;
+            }
+        }
+        let media_offset = elapsed;
+        // Sample description indices of this file in the joined `stsd`.
+        let mut indices = Vec::new();
+        if let Some(stsd) = other.stbl().and_then(|stbl| stbl.stsd()) {
+            for entry in stsd.entries() {
+                let bytes = entry_bytes(entry)?;
+                match descriptions.iter().position(|description| *description == bytes) {
+                    Some(idx) => indices.push(idx as u32 + 1),
+                    None => {
+                        descriptions.push(bytes);
+                        added.push(entry.clone());
+                        indices.push(descriptions.len() as u32);
+                    }
+                }
+            }
+        }
+        for mut sample in other.sample_table()?.iter() {
+            if let Some(index) = (sample.description_index as usize).checked_sub(1).and_then(|idx| indices.get(idx)) {
+                sample.description_index = *index;
+            }
+            elapsed += sample.duration as u64;
+            samples.push((source, sample));
+        }
+
+        let (duration, file_timescale) = match moov.mvhd() {
+            Some(mvhd) => (mvhd.duration(), mvhd.timescale()),
+            None => (0, movie_timescale)
+        };
+        let file_duration = rescale(duration, file_timescale, movie_timescale);
+        let mut file_edits = match other.elst() {
+            Some(elst) => {
+                has_edits = true;
+                // Edit durations are in the timescale of this file's mvhd.
+                elst.entries().iter().map(|edit| {
+                    let mut edit = edit.clone();
+                    edit.set_segment_duration(rescale(edit.segment_duration(), file_timescale, movie_timescale));
+                    edit
+                }).collect()
+            },
+            None => vec![ELST_Entry::new(file_duration, 0)]
+        };
+        // Also fills a last edit of zero duration, which runs to the end of the media.
+        let total: u64 = file_edits.iter().map(|edit| edit.segment_duration()).sum();
+        if let Some(last) = file_edits.last_mut() {
+            if total < file_duration {
+                let segment_duration = last.segment_duration() + file_duration - total;
+                last.set_segment_duration(segment_duration);
+            }
+        }
+        for edit in file_edits.iter_mut().filter(|edit| !edit.is_empty()) {
+            let media_time = edit.media_time() + media_offset as i64;
+            edit.set_media_time(media_time);
+        }
+        // The next file starts where the edits of this one stop showing media.
+        start = match other.elst().and(file_edits.iter().rev().find(|edit| !edit.is_empty() && !edit.is_dwell())) {
+            Some(edit) => edit.media_time() as u64 + rescale(edit.segment_duration(), movie_timescale, timescale),
+            None => media_offset + rescale(duration, file_timescale, timescale)
+        };
+        for edit in file_edits {
+            match edits.last_mut() {
+                Some(last) if carries_on(last, &edit, movie_timescale, timescale) => {
+                    let segment_duration = last.segment_duration() + edit.segment_duration();
+                    last.set_segment_duration(segment_duration);
+                },
+                _ => edits.push(edit)
+            }
+        }
+    }
+
+    let media_duration = samples.iter().map(|(_, sample)| sample.duration as u64).sum();
+    let elst = if has_edits { Some(Elst::new(edits)) } else { None };
+    let track_duration = match elst {
+        Some(ref elst) => elst.entries().iter().map(|entry| entry.segment_duration()).sum(),
+        None => rescale(media_duration, timescale, movie_timescale)
+    };
+    Ok((Track {
+        samples,
+        elst,
+        media_duration,
+        track_duration
+    }, added))
+}
+
+// Whether `next` shows the media right after the one `edit` ends on, both at normal rate.
+fn carries_on(edit: &ELST_Entry, next: &ELST_Entry, movie_timescale: u32, timescale: u32) -> bool {
+    let normal_rate = |edit: &ELST_Entry| (edit.media_rate_integer(), edit.media_rate_fraction()) == (1, 0);
+    !edit.is_empty() && !next.is_empty() && normal_rate(edit) && normal_rate(next)
+        && edit.media_time() + rescale(edit.segment_duration(), movie_timescale, timescale) as i64 == next.media_time()
+}
+
+// (track_ID, handler type, media timescale)
+fn track_info(trak: &Trak) -> Result<(u32, String, u32), Mp4Error> {
+    let track_id = match trak.tkhd() {
+        Some(tkhd) => tkhd.track_id(),
+        None => return Err(trak.header().invalid_value("trak has no track header"))
+    };
+    let mdia = match trak.mdia() {
+        Some(mdia) => mdia,
+        None => return Err(trak.header().invalid_value("trak has no media box"))
+    };
+    let handler_type = mdia.hdlr().map(|hdlr| hdlr.handler_type().to_string()).unwrap_or_default();
+    match mdia.mdhd() {
+        Some(mdhd) => Ok((track_id, handler_type, mdhd.timescale())),
+        None => Err(trak.header().invalid_value("trak has no media header"))
+    }
+}
+
+fn entry_bytes(entry: &SampleEntry) -> Result<Vec<u8>, Mp4Error> {
+    let mut bytes = Vec::new();
+    entry.write(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn add_entries(atom: &mut Atom, entries: &[SampleEntry]) {
+    match *atom {
+        Atom::stsd(ref mut stsd) => {
+            for entry in entries {
+                stsd.add_entry(entry.clone());
+            }
+        },
+        ref mut atom => {
+            if let Some(children) = atom.children_mut() {
+                for child in children.iter_mut() {
+                    add_entries(child, entries);
+                }
+            }
+        }
+    }
+}
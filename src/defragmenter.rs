@@ -0,0 +1,97 @@
+/*
+
+Defragmenter: rewrite a fragmented file as a progressive one (see `progressive`).
+
+The samples of a track are the ones already in its sample table, followed by those
+of its track fragments, all written to a single `mdat` in file order, which keeps
+the interleaving of the fragments. Decode times are rebuilt from the sample
+durations, so each track starts at 0 and gaps between the `tfdt` of consecutive
+fragments are dropped.
+
+The media duration in `mdhd` is the sum of the sample durations. A track with an
+edit list keeps it, with a last edit of zero duration (the usual "rest of the
+media" edit of fragmented files) replaced by the remaining media duration; its
+`tkhd` duration is the sum of the edits. `mvhd` gets the longest track duration.
+
+**/
+
+use std::io::{Read, Seek, Write};
+
+use atom::{Kind, Moov, Elst};
+use progressive::{self, Track};
+use sample_table::SampleInfo;
+use timeline::rescale;
+use {Mp4File, Mp4Error};
+
+pub fn defragment<R: Read + Seek, W: Write>(mp4: &mut Mp4File<R>, w: &mut W) -> Result<(), Mp4Error> {
+    let moov = progressive::source_moov(mp4)?;
+    let movie_timescale = moov.mvhd().map(|mvhd| mvhd.timescale()).unwrap_or(1);
+    let tracks = tracks(mp4, &moov, movie_timescale)?;
+    progressive::write(mp4, w, &moov, &tracks)
+}
+
+fn tracks<R: Read + Seek>(mp4: &Mp4File<R>, moov: &Moov, movie_timescale: u32) -> Result<Vec<Track>, Mp4Error> {
+    let mut track_ids = Vec::new();
+    let mut tracks = Vec::new();
+    for trak in moov.traks() {
+        match trak.tkhd() {
+            Some(tkhd) => track_ids.push(tkhd.track_id()),
+            None => return Err(trak.header().invalid_value("trak has no track header"))
+        }
+        tracks.push(Track {
+            samples: trak.sample_table()?.iter().map(|sample| (0, sample)).collect(),
+            elst   : None,
+            media_duration: 0,
+            track_duration: 0
+        });
+    }
+    for sample in mp4.fragment_samples() {
+        let sample = sample?;
+        let track = match track_ids.iter().position(|track_id| *track_id == sample.track_id) {
+            Some(idx) => &mut tracks[idx],
+            None => return Err(Mp4Error::InvalidValue {
+                kind  : Kind::traf,
+                offset: 0,
+                reason: "track fragment of a track not in moov"
+            })
+        };
+        let number = track.samples.len() as u32 + 1;
+        track.samples.push((0, SampleInfo {
+            number,
+            offset  : sample.offset,
+            size    : sample.size,
+            dts     : sample.dts,
+            cts     : sample.cts,
+            duration: sample.duration,
+            is_sync : sample.is_sync,
+            description_index: sample.description_index
+        }));
+    }
+
+    for (track, trak) in tracks.iter_mut().zip(moov.traks()) {
+        let timescale = match trak.mdia().and_then(|mdia| mdia.mdhd()) {
+            Some(mdhd) => mdhd.timescale(),
+            None => return Err(trak.header().invalid_value("trak has no media header"))
+        };
+        track.media_duration = track.samples.iter().map(|(_, sample)| sample.duration as u64).sum();
+        let media_duration = rescale(track.media_duration, timescale, movie_timescale);
+        track.track_duration = match trak.elst() {
+            Some(elst) => {
+                let mut entries = elst.entries().clone();
+                if let Some(entry) = entries.last_mut() {
+                    if entry.segment_duration() == 0 && !entry.is_empty() {
+                        let media_time = entry.media_time().max(0) as u64;
+                        let rest = rescale(track.media_duration.saturating_sub(media_time), timescale, movie_timescale);
+                        entry.set_segment_duration(rest);
+                    }
+                }
+                let duration = entries.iter().map(|entry| entry.segment_duration()).sum();
+                track.elst = Some(Elst::new(entries));
+                duration
+            },
+            None => media_duration
+        };
+    }
+    Ok(tracks)
+}
+
@@ -0,0 +1,120 @@
+use std::fmt;
+use std::error::Error;
+use std::io;
+
+use atom::Kind;
+
+/**
+    Errors returned while reading an MP4 file.
+
+    Box level errors carry the box kind and the file offset of the
+    box header, so a caller can tell where in the file parsing stopped.
+**/
+
+#[derive(Debug)]
+pub enum Mp4Error {
+    // Underlying reader error (other than a premature end of file).
+    Io(io::Error),
+    // The stream ended before the box was fully read.
+    Truncated {
+        kind  : Kind,
+        offset: u64
+    },
+    // The box size field is smaller than its header or larger than its container.
+    InvalidSize {
+        kind  : Kind,
+        offset: u64,
+        size  : u64
+    },
+    // A child box extends past the end of its container.
+    Overrun {
+        kind  : Kind,
+        offset: u64,
+        size  : u64,
+        limit : u64
+    },
+    // A container ends with bytes too short to hold another box header.
+    // `kind` is the container, or `None` at file level.
+    Underrun {
+        kind     : Option<Kind>,
+        offset   : u64,
+        remaining: u64
+    },
+    // The FullBox version is not defined by the specification.
+    UnsupportedVersion {
+        kind   : Kind,
+        offset : u64,
+        version: u8
+    },
+    // A field holds a value that can not be interpreted.
+    InvalidValue {
+        kind  : Kind,
+        offset: u64,
+        reason: &'static str
+    },
+    // A codec bitstream (e.g. an SPS NAL unit) carried in a box can not be decoded.
+    InvalidBitstream(&'static str),
+    // No RFC 6381 codec string is defined or derivable for the sample entry format.
+    UnsupportedCodec(String)
+}
+
+impl Mp4Error {
+    // Attach the box kind and offset to a bare I/O error raised by the reader.
+    pub fn at(self, kind: &Kind, offset: u64) -> Mp4Error {
+        match self {
+            Mp4Error::Io(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                Mp4Error::Truncated { kind: kind.clone(), offset }
+            },
+            e => e
+        }
+    }
+}
+
+impl fmt::Display for Mp4Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Mp4Error::Io(ref e) => write!(f, "I/O error: {}", e),
+            Mp4Error::Truncated { ref kind, offset } => {
+                write!(f, "box `{}` at offset {} is truncated", kind, offset)
+            },
+            Mp4Error::InvalidSize { ref kind, offset, size } => {
+                write!(f, "box `{}` at offset {} has invalid size {}", kind, offset, size)
+            },
+            Mp4Error::Overrun { ref kind, offset, size, limit } => {
+                write!(f, "box `{}` at offset {} with size {} overruns its container ending at {}",
+                       kind, offset, size, limit)
+            },
+            Mp4Error::Underrun { kind: Some(ref kind), offset, remaining } => {
+                write!(f, "box `{}` has {} unparsable trailing bytes at offset {}", kind, remaining, offset)
+            },
+            Mp4Error::Underrun { kind: None, offset, remaining } => {
+                write!(f, "file has {} unparsable trailing bytes at offset {}", remaining, offset)
+            },
+            Mp4Error::UnsupportedVersion { ref kind, offset, version } => {
+                write!(f, "box `{}` at offset {} has unsupported version {}", kind, offset, version)
+            },
+            Mp4Error::InvalidValue { ref kind, offset, reason } => {
+                write!(f, "box `{}` at offset {}: {}", kind, offset, reason)
+            },
+            Mp4Error::InvalidBitstream(reason) => write!(f, "invalid bitstream: {}", reason),
+            Mp4Error::UnsupportedCodec(ref format) => {
+                write!(f, "no codec string for sample entry `{}`", format)
+            }
+        }
+    }
+}
+
+impl Error for Mp4Error {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            Mp4Error::Io(ref e) => Some(e),
+            _ => None
+        }
+    }
+}
+
+impl From<io::Error> for Mp4Error {
+    fn from(e: io::Error) -> Mp4Error {
+        Mp4Error::Io(e)
+    }
+}
@@ -0,0 +1,102 @@
+/*
+
+Extractor: keep or drop tracks of a progressive file, without re-encoding.
+
+Tracks are selected by `track_ID` or by `hdlr` handler type (vide, soun, text,
+hint, tmcd, ...). The dropped `trak` boxes are removed from `moov`, along with
+every `tref` reference to them; `mvhd` gets the longest duration and keeps its
+next track ID, so the IDs of dropped tracks are not handed out again.
+
+The file is written with a single `mdat` holding the samples of the tracks left
+(see `progressive`), so the media data of the dropped tracks is not carried over.
+
+**/
+
+use std::io::{Read, Seek, Write};
+
+use atom::{Atom, Trak};
+use progressive::{self, Track};
+use {Mp4File, Mp4Error};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrackSelector {
+    TrackId(u32),
+    Handler(String)     // `hdlr` handler_type
+}
+
+impl TrackSelector {
+    pub fn matches(&self, trak: &Trak) -> bool {
+        match *self {
+            TrackSelector::TrackId(track_id) => trak.tkhd().is_some_and(|tkhd| tkhd.track_id() == track_id),
+            TrackSelector::Handler(ref handler_type) => trak.mdia()
+                .and_then(|mdia| mdia.hdlr())
+                .is_some_and(|hdlr| hdlr.handler_type() == handler_type)
+        }
+    }
+}
+
+// Write a copy of the file with only the tracks matching one of `selectors`.
+pub fn extract<R: Read + Seek, W: Write>(mp4: &mut Mp4File<R>, w: &mut W, selectors: &[TrackSelector]) -> Result<(), Mp4Error> {
+    select(mp4, w, selectors, true)
+}
+
+// Write a copy of the file without the tracks matching one of `selectors`.
+pub fn remove<R: Read + Seek, W: Write>(mp4: &mut Mp4File<R>, w: &mut W, selectors: &[TrackSelector]) -> Result<(), Mp4Error> {
+    select(mp4, w, selectors, false)
+}
+
+fn select<R: Read + Seek, W: Write>(mp4: &mut Mp4File<R>, w: &mut W, selectors: &[TrackSelector], keep: bool) -> Result<(), Mp4Error> {
+    if let Some(moof) = mp4.moofs().first() {
+        return Err(moof.header().invalid_value("file is fragmented"));
+    }
+    let mut moov = progressive::source_moov(mp4)?;
+
+    let mut kept = Vec::new();
+    let mut removed = Vec::new();
+    for trak in moov.traks() {
+        let track_id = match trak.tkhd() {
+            Some(tkhd) => tkhd.track_id(),
+            None => return Err(trak.header().invalid_value("trak has no track header"))
+        };
+        if selectors.iter().any(|selector| selector.matches(trak)) == keep {
+            kept.push(track_id);
+        } else {
+            removed.push(track_id);
+        }
+    }
+    if kept.is_empty() {
+        return Err(moov.header().invalid_value("no track left"));
+    }
+
+    moov.children_mut().retain(|atom| match *atom {
+        Atom::trak(ref trak) => trak.tkhd().is_some_and(|tkhd| kept.contains(&tkhd.track_id())),
+        _ => true
+    });
+    for atom in moov.children_mut().iter_mut() {
+        if let Atom::trak(ref mut trak) = *atom {
+            let children = trak.children_mut();
+            for child in children.iter_mut() {
+                if let Atom::tref(ref mut tref) = *child {
+                    for track_id in &removed {
+                        tref.remove_track(*track_id);
+                    }
+                }
+            }
+            children.retain(|atom| match *atom {
+                Atom::tref(ref tref) => !tref.is_empty(),
+                _ => true
+            });
+        }
+    }
+
+    let mut tracks = Vec::new();
+    for trak in moov.traks() {
+        tracks.push(Track {
+            samples: trak.sample_table()?.iter().map(|sample| (0, sample)).collect(),
+            elst   : trak.elst().cloned(),
+            media_duration: trak.mdia().and_then(|mdia| mdia.mdhd()).map_or(0, |mdhd| mdhd.duration()),
+            track_duration: trak.tkhd().map_or(0, |tkhd| tkhd.duration())
+        });
+    }
+    progressive::write(mp4, w, &moov, &tracks)
+}
@@ -0,0 +1,117 @@
+/*
+
+Faststart: move `moov` in front of the media data.
+
+Progressive players need the movie box before `mdat`. The boxes are written in this order:
+
+    boxes in front of the first mdat (ftyp, free, ...)
+    moov
+    every other box, in file order
+
+Each chunk offset in `stco` / `co64` is moved by the same amount as the top level
+box holding its data. A `stco` whose new offsets no longer fit in 32 bits is
+written as `co64`, which grows `moov` and so the offsets again; the layout is
+recomputed until the `moov` size is stable.
+
+**/
+
+use std::io::{Read, Seek, Write};
+
+use atom::{Atom, Kind, Co64};
+use {Mp4File, Mp4Error};
+
+// (old offset, size, new offset) of a top level box.
+type Placement = (u64, u64, u64);
+
+pub fn faststart<R: Read + Seek, W: Write>(mp4: &mut Mp4File<R>, w: &mut W) -> Result<(), Mp4Error> {
+    let atoms = mp4.atoms().clone();
+    let moov_idx = match atoms.iter().position(|atom| *atom.kind() == Kind::moov) {
+        Some(idx) => idx,
+        None => return Err(Mp4Error::InvalidValue {
+            kind  : Kind::moov,
+            offset: 0,
+            reason: "file has no moov box"
+        })
+    };
+    let first_mdat = atoms.iter().position(|atom| *atom.kind() == Kind::mdat).unwrap_or(atoms.len());
+
+    let mut order: Vec<usize> = (0..first_mdat).filter(|idx| *idx != moov_idx).collect();
+    order.push(moov_idx);
+    order.extend((first_mdat..atoms.len()).filter(|idx| *idx != moov_idx));
+
+    let mut moov_size = atoms[moov_idx].header().atom_size();
+    let moov = loop {
+        let placements = place(&atoms, &order, moov_idx, moov_size);
+        let mut moov = atoms[moov_idx].clone();
+        relocate(&mut moov, &placements);
+        let mut data: Vec<u8> = Vec::new();
+        moov.write(&mut data)?;
+        if data.len() as u64 == moov_size {
+            break data;
+        }
+        moov_size = data.len() as u64;
+    };
+
+    for idx in order {
+        match atoms[idx] {
+            _ if idx == moov_idx => w.write_all(&moov)?,
+            _ => mp4.write_atom(idx, w)?
+        }
+    }
+    Ok(())
+}
+
+fn place(atoms: &[Atom], order: &[usize], moov_idx: usize, moov_size: u64) -> Vec<Placement> {
+    let mut placements: Vec<Placement> = Vec::new();
+    let mut offset = 0u64;
+    for idx in order {
+        let header = atoms[*idx].header();
+        let size = if *idx == moov_idx { moov_size } else { header.atom_size() };
+        placements.push((header.offset(), header.atom_size(), offset));
+        offset += size;
+    }
+    placements
+}
+
+// New file offset of `offset`; offsets outside every box (e.g. into external data) are kept.
+fn shift(offset: u64, placements: &[Placement]) -> u64 {
+    for &(old, size, new) in placements {
+        if offset >= old && offset < old + size {
+            return offset - old + new;
+        }
+    }
+    offset
+}
+
+fn relocate(atom: &mut Atom, placements: &[Placement]) {
+    let replacement = match *atom {
+        Atom::stco(ref mut stco) => {
+            let offsets: Vec<u64> = stco.chunks().iter()
+                .map(|offset| shift(*offset as u64, placements))
+                .collect();
+            if offsets.iter().all(|offset| *offset <= u32::MAX as u64) {
+                *stco.chunks_mut() = offsets.iter().map(|offset| *offset as u32).collect();
+                None
+            } else {
+                Some(Atom::co64(Co64::new(offsets)))
+            }
+        },
+        Atom::co64(ref mut co64) => {
+            for offset in co64.chunks_mut().iter_mut() {
+                *offset = shift(*offset, placements);
+            }
+            None
+        },
+        ref mut atom => {
+            if let Some(children) = atom.children_mut() {
+                for child in children.iter_mut() {
+                    relocate(child, placements);
+                }
+            }
+            None
+        }
+    };
+    if let Some(replacement) = replacement {
+        *atom = replacement;
+    }
+}
@@ -0,0 +1,57 @@
+/*
+
+Fixed-point numbers used by the ISO base media file format.
+
+    16.16   template int(32) rate, matrix a b c d x y; unsigned in `tkhd` width / height
+    8.8     template int(16) volume, balance
+    2.30    matrix u v w
+
+Each type keeps the raw bits, so a parsed box is written back unchanged.
+Every raw value converts to f64 exactly; `from_f64` rounds to the nearest
+representable value and saturates at the ends of the range.
+
+**/
+
+use std::fmt;
+
+macro_rules! fixed_point {
+    ($name:ident, $bits:ty, $fraction:expr) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+        pub struct $name($bits);
+
+        impl $name {
+            pub const ONE: $name = $name(1 << $fraction);
+
+            pub fn from_bits(bits: $bits) -> $name {
+                $name(bits)
+            }
+            pub fn to_bits(self) -> $bits {
+                self.0
+            }
+            pub fn from_f64(value: f64) -> $name {
+                // `as` saturates, and maps NaN to 0.
+                $name((value * (1u64 << $fraction) as f64).round() as $bits)
+            }
+            pub fn to_f64(self) -> f64 {
+                self.0 as f64 / (1u64 << $fraction) as f64
+            }
+        }
+
+        impl From<$name> for f64 {
+            fn from(value: $name) -> f64 {
+                value.to_f64()
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "{}", self.to_f64())
+            }
+        }
+    }
+}
+
+fixed_point!(Fixed16_16, i32, 16);
+fixed_point!(UFixed16_16, u32, 16);
+fixed_point!(Fixed8_8, i16, 8);
+fixed_point!(Fixed2_30, i32, 30);
@@ -0,0 +1,170 @@
+/*
+
+Fragmented MP4 sample resolver.
+
+Walks every `moof` of a file and resolves the samples described by its
+track runs. Sample values are taken from the first level that provides them:
+
+    trun          per-sample (and first-sample) values
+    tfhd          track fragment defaults
+    trex          track defaults from `moov/mvex`
+
+Absolute data offsets follow 8.8.7 / 8.8.8:
+
+    base_data_offset present    the explicit offset
+    default-base-is-moof        the first byte of the enclosing `moof`
+    otherwise                   the enclosing `moof` for the first `traf`,
+                                the end of the data of the preceding `traf` after that
+
+A `trun` without `data_offset` continues where the preceding `trun` ended.
+
+Decode times are kept per track. A `tfdt` sets the decode time of the first
+sample of its track fragment; without one, decoding continues from the end of
+the previous fragment of the same track (0 for the first one).
+
+**/
+
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryFrom;
+
+use atom::{Moof, Mvex};
+use Mp4Error;
+
+// sample_is_non_sync_sample, see 8.8.3.1
+const SAMPLE_IS_NON_SYNC_SAMPLE: u32 = 0x0001_0000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FragmentSample {
+    pub track_id: u32,
+    pub sequence_number: u32, // `mfhd` sequence number of the enclosing fragment
+    pub number  : u32,  // 1-based sample number within the track's fragments
+    pub offset  : u64,  // absolute file offset of the sample data
+    pub size    : u32,
+    pub dts     : u64,  // decoding time
+    pub cts     : i64,  // composition time
+    pub duration: u32,
+    pub flags   : u32,
+    pub is_sync : bool,
+    pub description_index: u32
+}
+
+pub struct FragmentSamples<'a> {
+    mvex  : Option<&'a Mvex>,
+    moofs : Vec<&'a Moof>,
+    next_moof: usize,
+    pending  : VecDeque<FragmentSample>,
+    next_dts   : HashMap<u32, u64>,
+    next_number: HashMap<u32, u32>
+}
+
+impl<'a> FragmentSamples<'a> {
+    pub fn new(mvex: Option<&'a Mvex>, moofs: Vec<&'a Moof>) -> FragmentSamples<'a> {
+        FragmentSamples {
+            mvex,
+            moofs,
+            next_moof: 0,
+            pending  : VecDeque::new(),
+            next_dts   : HashMap::new(),
+            next_number: HashMap::new()
+        }
+    }
+
+    fn resolve_moof(&mut self, moof: &Moof) -> Result<(), Mp4Error> {
+        let moof_offset = moof.header().offset();
+        let sequence_number = moof.mfhd().map(|mfhd| mfhd.sequence_number()).unwrap_or(0);
+        // End of the data of the preceding track fragment in this `moof`.
+        let mut prev_traf_end: Option<u64> = None;
+
+        for traf in moof.trafs() {
+            let tfhd = match traf.tfhd() {
+                Some(tfhd) => tfhd,
+                None => return Err(traf.header().invalid_value("traf has no tfhd"))
+            };
+            let track_id = tfhd.track_id();
+            let trex = self.mvex.and_then(|mvex| mvex.trex(track_id));
+
+            let base = match tfhd.base_data_offset() {
+                Some(offset) => offset,
+                None if tfhd.default_base_is_moof() => moof_offset,
+                None => prev_traf_end.unwrap_or(moof_offset)
+            };
+            let mut dts = match traf.tfdt() {
+                Some(tfdt) => tfdt.base_media_decode_time(),
+                None => self.next_dts.get(&track_id).cloned().unwrap_or(0)
+            };
+            let mut number = self.next_number.get(&track_id).cloned().unwrap_or(1);
+            let mut offset = base;
+
+            for trun in traf.truns() {
+                if let Some(data_offset) = trun.data_offset() {
+                    offset = i64::try_from(base).ok()
+                        .and_then(|base| base.checked_add(data_offset as i64))
+                        .and_then(|offset| u64::try_from(offset).ok())
+                        .ok_or_else(|| trun.header().invalid_value("trun data_offset is out of range"))?;
+                }
+                for (idx, sample) in trun.samples().iter().enumerate() {
+                    let duration = sample.duration()
+                        .or(tfhd.defaults().duration())
+                        .or(trex.map(|trex| trex.default_sample_duration()));
+                    let size = sample.size()
+                        .or(tfhd.defaults().size())
+                        .or(trex.map(|trex| trex.default_sample_size()));
+                    let first_flags = if idx == 0 { trun.first_sample_flags() } else { None };
+                    let flags = first_flags
+                        .or(sample.flags())
+                        .or(tfhd.defaults().flags())
+                        .or(trex.map(|trex| trex.default_sample_flags()));
+                    let description_index = tfhd.defaults().description_index()
+                        .or(trex.map(|trex| trex.default_sample_description_index()));
+
+                    let (duration, size, flags, description_index) = match (duration, size, flags, description_index) {
+                        (Some(d), Some(s), Some(f), Some(i)) => (d, s, f, i),
+                        _ => return Err(trun.header().invalid_value("sample defaults are missing; no trex for track"))
+                    };
+
+                    self.pending.push_back(FragmentSample {
+                        track_id,
+                        sequence_number,
+                        number,
+                        offset,
+                        size,
+                        dts,
+                        cts     : dts as i64 + sample.composition_time_offset().unwrap_or(0) as i64,
+                        duration,
+                        flags,
+                        is_sync : flags & SAMPLE_IS_NON_SYNC_SAMPLE == 0,
+                        description_index
+                    });
+                    number += 1;
+                    offset = offset.checked_add(size as u64)
+                        .ok_or_else(|| trun.header().invalid_value("trun sample data is out of range"))?;
+                    dts = dts.checked_add(duration as u64)
+                        .ok_or_else(|| trun.header().invalid_value("trun decode time overflows"))?;
+                }
+            }
+
+            prev_traf_end = Some(offset);
+            self.next_dts.insert(track_id, dts);
+            self.next_number.insert(track_id, number);
+        }
+        Ok(())
+    }
+}
+
+impl<'a> Iterator for FragmentSamples<'a> {
+    type Item = Result<FragmentSample, Mp4Error>;
+
+    fn next(&mut self) -> Option<Result<FragmentSample, Mp4Error>> {
+        while self.pending.is_empty() {
+            let moof = *self.moofs.get(self.next_moof)?;
+            self.next_moof += 1;
+            if let Err(e) = self.resolve_moof(moof) {
+                // Stop after reporting the error.
+                self.next_moof = self.moofs.len();
+                self.pending.clear();
+                return Some(Err(e));
+            }
+        }
+        self.pending.pop_front().map(Ok)
+    }
+}
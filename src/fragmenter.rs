@@ -0,0 +1,286 @@
+/*
+
+Fragmenter: rewrite a progressive file as a fragmented one.
+
+    ftyp        major brand iso5
+    moov        empty sample tables, zero durations, mvex with mehd and a trex per track
+    moof        mfhd, then per track: traf (tfhd, tfdt, trun)
+    mdat        the samples of the moof, in trun order
+    moof
+    mdat
+    ...
+
+The first two boxes are the initialization segment, each `moof` / `mdat` pair a
+media segment.
+
+Fragments are cut at the sync samples of a reference track, the first track with a
+`stss` box (normally video), or the first track if every track is all sync samples:
+a fragment ends at the first sync sample at least `fragment_duration` seconds after
+its start. The other tracks are cut at the same instants, compared on decode times.
+
+A track fragment holds the samples of one track that share a sample description,
+so a track whose description changes mid-fragment gets several. Its `tfdt` is the
+decode time of the first sample, and its `trun` lists the duration, size, flags and
+(if the track has composition offsets) composition offset of every sample.
+Track fragments use default-base-is-moof: `trun` data offsets count from the first
+byte of the `moof`, which is why the brands earlier than iso5 are dropped.
+
+**/
+
+use std::io::{Read, Seek, Write};
+
+use atom::{Atom, Kind, Header, Ftyp, FileType, Moov, Mvex, Mehd, Trex, Moof, Mfhd, Traf,
+           Tfhd, Tfdt, Trun, Sample, Stts, Stsc, Stsz, Stco};
+use sample_table::SampleInfo;
+use {Mp4File, Mp4Error};
+
+// sample_flags, see 8.8.3.1: sync samples do not depend on others (sample_depends_on = 2),
+// other samples do (sample_depends_on = 1) and set sample_is_non_sync_sample.
+const SYNC_SAMPLE_FLAGS    : u32 = 0x0200_0000;
+const NON_SYNC_SAMPLE_FLAGS: u32 = 0x0101_0000;
+
+// Brands that do not allow default-base-is-moof.
+const EARLIER_BRANDS: [&[u8; 4]; 4] = [b"isom", b"iso2", b"iso3", b"iso4"];
+
+struct Track {
+    track_id : u32,
+    timescale: u32,
+    samples  : Vec<SampleInfo>,
+    has_sync_samples: bool,   // has a `stss` box
+    has_composition_offsets: bool
+}
+
+// Samples of one track fragment: (index into the tracks, samples).
+type Run<'a> = (usize, &'a [SampleInfo]);
+
+// Write the initialization segment followed by every media segment.
+pub fn fragment<R: Read + Seek, W: Write>(mp4: &mut Mp4File<R>, w: &mut W, fragment_duration: f64) -> Result<(), Mp4Error> {
+    write_init_segment(mp4, w)?;
+    write_fragments(mp4, w, fragment_duration)
+}
+
+pub fn write_init_segment<R: Read + Seek, W: Write>(mp4: &Mp4File<R>, w: &mut W) -> Result<(), Mp4Error> {
+    Atom::write_children(&init_segment(mp4)?, w)
+}
+
+// `ftyp` and `moov` of the initialization segment.
+pub fn init_segment<R: Read + Seek>(mp4: &Mp4File<R>) -> Result<Vec<Atom>, Mp4Error> {
+    let moov = source_moov(mp4)?;
+
+    let iso5 = FileType::Other(*b"iso5");
+    let mut brands = vec![iso5.clone()];
+    if let Some(ftyp) = mp4.ftyp() {
+        for brand in ftyp.compatible_brands() {
+            let bytes = brand.into_bytes();
+            if bytes != iso5.into_bytes() && !EARLIER_BRANDS.iter().any(|earlier| bytes == earlier[..]) {
+                brands.push(brand.clone());
+            }
+        }
+    }
+    let ftyp = Ftyp::new(iso5, 0, brands);
+
+    let mut mvex = vec![Atom::mehd(Mehd::new(moov.mvhd().map(|mvhd| mvhd.duration()).unwrap_or(0)))];
+    for trak in moov.traks() {
+        match trak.tkhd() {
+            Some(tkhd) => mvex.push(Atom::trex(Trex::new(tkhd.track_id(), 1, 0, 0, 0))),
+            None => return Err(trak.header().invalid_value("trak has no track header"))
+        }
+    }
+    let mut moov = moov.clone();
+    moov.children_mut().retain(|atom| *atom.kind() != Kind::mvex);
+    for child in moov.children_mut().iter_mut() {
+        clear_sample_tables(child);
+    }
+    moov.children_mut().push(Atom::mvex(Mvex::new(mvex)));
+
+    Ok(vec![Atom::ftyp(ftyp), Atom::moov(moov)])
+}
+
+// Write a `moof` / `mdat` pair per fragment, with sequence numbers from 1.
+pub fn write_fragments<R: Read + Seek, W: Write>(mp4: &mut Mp4File<R>, w: &mut W, fragment_duration: f64) -> Result<(), Mp4Error> {
+    let tracks = tracks(source_moov(mp4)?)?;
+    let reference = tracks.iter().position(|track| track.has_sync_samples).unwrap_or(0);
+    let cuts = match tracks.get(reference) {
+        Some(track) => cut_points(track, fragment_duration),
+        None => return Ok(())
+    };
+    let reference_timescale = tracks[reference].timescale as u128;
+
+    let mut fragments: Vec<Vec<Run>> = vec![Vec::new(); cuts.len() + 1];
+    for (idx, track) in tracks.iter().enumerate() {
+        let mut start = 0;
+        while start < track.samples.len() {
+            let first = &track.samples[start];
+            // Number of cut points at or before this sample.
+            let fragment = cuts.partition_point(|cut| {
+                *cut as u128 * track.timescale as u128 <= first.dts as u128 * reference_timescale
+            });
+            let end = match cuts.get(fragment) {
+                Some(cut) => start + track.samples[start..].partition_point(|sample| {
+                    (sample.dts as u128 * reference_timescale) < *cut as u128 * track.timescale as u128
+                }),
+                None => track.samples.len()
+            };
+            // One run per sample description.
+            let mut run_start = start;
+            for i in start + 1..=end {
+                if i == end || track.samples[i].description_index != track.samples[run_start].description_index {
+                    fragments[fragment].push((idx, &track.samples[run_start..i]));
+                    run_start = i;
+                }
+            }
+            start = end;
+        }
+    }
+
+    let mut sequence_number = 0u32;
+    for runs in fragments.iter().filter(|runs| !runs.is_empty()) {
+        sequence_number += 1;
+        write_fragment(mp4, w, &tracks, sequence_number, runs)?;
+    }
+    Ok(())
+}
+
+fn source_moov<R: Read + Seek>(mp4: &Mp4File<R>) -> Result<&Moov, Mp4Error> {
+    if let Some(moof) = mp4.moofs().first() {
+        return Err(moof.header().invalid_value("file is already fragmented"));
+    }
+    match mp4.moov() {
+        Some(moov) => Ok(moov),
+        None => Err(Mp4Error::InvalidValue {
+            kind  : Kind::moov,
+            offset: 0,
+            reason: "file has no moov box"
+        })
+    }
+}
+
+fn tracks(moov: &Moov) -> Result<Vec<Track>, Mp4Error> {
+    let mut tracks = Vec::new();
+    for trak in moov.traks() {
+        let track_id = match trak.tkhd() {
+            Some(tkhd) => tkhd.track_id(),
+            None => return Err(trak.header().invalid_value("trak has no track header"))
+        };
+        let timescale = match trak.mdia().and_then(|mdia| mdia.mdhd()) {
+            Some(mdhd) => mdhd.timescale(),
+            None => return Err(trak.header().invalid_value("trak has no media header"))
+        };
+        let table = trak.sample_table()?;
+        let samples: Vec<SampleInfo> = table.iter().collect();
+        tracks.push(Track {
+            track_id,
+            timescale,
+            has_sync_samples: table.sync_samples().is_some(),
+            has_composition_offsets: samples.iter().any(|sample| sample.cts != sample.dts as i64),
+            samples
+        });
+    }
+    Ok(tracks)
+}
+
+// Decode times of the sync samples that start a new fragment, in the track's timescale.
+fn cut_points(track: &Track, fragment_duration: f64) -> Vec<u64> {
+    let target = (fragment_duration.max(0.0) * track.timescale as f64).round() as u64;
+    let mut cuts = Vec::new();
+    let mut start = match track.samples.first() {
+        Some(sample) => sample.dts,
+        None => return cuts
+    };
+    for sample in &track.samples[1..] {
+        if sample.is_sync && sample.dts - start >= target {
+            cuts.push(sample.dts);
+            start = sample.dts;
+        }
+    }
+    cuts
+}
+
+fn build_moof(tracks: &[Track], sequence_number: u32, runs: &[Run], data_offsets: &[i32]) -> Moof {
+    let mut children = vec![Atom::mfhd(Mfhd::new(sequence_number))];
+    for (&(idx, samples), data_offset) in runs.iter().zip(data_offsets) {
+        let track = &tracks[idx];
+        // trex carries description index 1.
+        let description_index = match samples[0].description_index {
+            1 => None,
+            index => Some(index)
+        };
+        let entries = samples.iter().map(|sample| {
+            let flags = if sample.is_sync { SYNC_SAMPLE_FLAGS } else { NON_SYNC_SAMPLE_FLAGS };
+            let composition_time_offset = if track.has_composition_offsets {
+                Some((sample.cts - sample.dts as i64) as i32)
+            } else {
+                None
+            };
+            Sample::new(Some(sample.duration), Some(sample.size), Some(flags), composition_time_offset)
+        }).collect();
+        children.push(Atom::traf(Traf::new(vec![
+            Atom::tfhd(Tfhd::new(track.track_id, description_index, Sample::new(None, None, None, None))),
+            Atom::tfdt(Tfdt::new(samples[0].dts)),
+            Atom::trun(Trun::new(Some(*data_offset), entries))
+        ])));
+    }
+    Moof::new(children)
+}
+
+fn write_fragment<R: Read + Seek, W: Write>(mp4: &mut Mp4File<R>, w: &mut W, tracks: &[Track],
+                                            sequence_number: u32, runs: &[Run]) -> Result<(), Mp4Error> {
+    // The size of the moof does not depend on the data offsets it holds.
+    let mut moof: Vec<u8> = Vec::new();
+    build_moof(tracks, sequence_number, runs, &vec![0; runs.len()]).write(&mut moof)?;
+
+    let payload_size: u64 = runs.iter()
+        .flat_map(|&(_, samples)| samples.iter())
+        .map(|sample| sample.size as u64)
+        .sum();
+    let mdat = Header::new(Kind::mdat);
+    let mdat_header_size = if 8 + payload_size > u32::MAX as u64 { 16 } else { 8 };
+
+    let mut data_offsets = Vec::new();
+    let mut offset = moof.len() as u64 + mdat_header_size;
+    for &(_, samples) in runs {
+        if offset > i32::MAX as u64 {
+            return Err(Mp4Error::InvalidValue {
+                kind  : Kind::trun,
+                offset: 0,
+                reason: "fragment data does not fit the trun data_offset"
+            });
+        }
+        data_offsets.push(offset as i32);
+        offset += samples.iter().map(|sample| sample.size as u64).sum::<u64>();
+    }
+    moof.clear();
+    build_moof(tracks, sequence_number, runs, &data_offsets).write(&mut moof)?;
+    w.write_all(&moof)?;
+
+    mdat.write_header(w, payload_size)?;
+    let ranges = runs.iter()
+        .flat_map(|&(_, samples)| samples.iter())
+        .map(|sample| (sample.offset, sample.size as u64));
+    mp4.copy_ranges(ranges, w)
+}
+
+// Empty the sample tables of the init segment and zero its durations; the movie
+// duration moves to `mehd`.
+fn clear_sample_tables(atom: &mut Atom) {
+    match *atom {
+        Atom::mvhd(ref mut mvhd) => mvhd.set_duration(0),
+        Atom::tkhd(ref mut tkhd) => tkhd.set_duration(0),
+        Atom::mdhd(ref mut mdhd) => mdhd.set_duration(0),
+        Atom::stbl(ref mut stbl) => {
+            let children = stbl.children_mut();
+            children.retain(|atom| matches!(*atom.kind(), Kind::stsd | Kind::sgpd));
+            children.push(Atom::stts(Stts::new(Vec::new())));
+            children.push(Atom::stsc(Stsc::new(Vec::new())));
+            children.push(Atom::stsz(Stsz::new(Vec::new())));
+            children.push(Atom::stco(Stco::new(Vec::new())));
+        },
+        ref mut atom => {
+            if let Some(children) = atom.children_mut() {
+                for child in children.iter_mut() {
+                    clear_sample_tables(child);
+                }
+            }
+        }
+    }
+}
@@ -5,49 +5,153 @@ extern crate byteorder;
 
 use std::fs;
 use std::fs::File;
+use std::io;
 use std::io::{Write, Read, ErrorKind, Error, SeekFrom, Seek};
-use byteorder::{BigEndian, ReadBytesExt};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 
 pub mod atom;
+pub mod sample_table;
+pub mod timeline;
+pub mod fragment;
+pub mod faststart;
+pub mod fragmenter;
+pub mod progressive;
+pub mod defragmenter;
+pub mod trimmer;
+pub mod concatenator;
+pub mod extractor;
+pub mod codec;
+pub mod fixed;
+mod error;
+
+pub use error::Mp4Error;
+use sample_table::SampleInfo;
+use fixed::{Fixed16_16, Fixed2_30};
 
 #[derive(Debug)]
-pub struct Mp4File {
-    file     : File,
+pub struct Mp4File<R = File> {
+    file     : R,
     file_size: u64,
     offset   : u64,
     atoms    : Vec<atom::Atom>
 }
 
-#[derive(Debug, Clone)]
+// a, b, c, d, x, y are fixed-point 16.16 numbers, u, v, w are 2.30.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Matrix {
-    a: f64,
-    b: f64,
-    u: f64,
-    c: f64,
-    d: f64,
-    v: f64,
-    x: f64,
-    y: f64,
-    w: f64
+    a: Fixed16_16,
+    b: Fixed16_16,
+    u: Fixed2_30,
+    c: Fixed16_16,
+    d: Fixed16_16,
+    v: Fixed2_30,
+    x: Fixed16_16,
+    y: Fixed16_16,
+    w: Fixed2_30
 }
 
-impl Mp4File {
-    pub fn new(filename: &str) -> Result<Self, &'static str> {
+// A point (p, q) is displayed at (a*p + c*q + x, b*p + d*q + y).
+impl Matrix {
+    // { 0x00010000,0,0,0,0x00010000,0,0,0,0x40000000 }
+    pub fn identity() -> Matrix {
+        Matrix {
+            a: Fixed16_16::ONE, b: Fixed16_16::default(), u: Fixed2_30::default(),
+            c: Fixed16_16::default(), d: Fixed16_16::ONE, v: Fixed2_30::default(),
+            x: Fixed16_16::default(), y: Fixed16_16::default(), w: Fixed2_30::ONE
+        }
+    }
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Error>{
+        for value in &self.values() {
+            w.write_i32::<BigEndian>(*value)?;
+        }
+        Ok(())
+    }
+    // Raw bits of a, b, u, c, d, v, x, y, w.
+    pub fn values(&self) -> [i32; 9] {
+        [
+            self.a.to_bits(), self.b.to_bits(), self.u.to_bits(),
+            self.c.to_bits(), self.d.to_bits(), self.v.to_bits(),
+            self.x.to_bits(), self.y.to_bits(), self.w.to_bits()
+        ]
+    }
+    pub fn a(&self) -> Fixed16_16 {
+        self.a
+    }
+    pub fn b(&self) -> Fixed16_16 {
+        self.b
+    }
+    pub fn u(&self) -> Fixed2_30 {
+        self.u
+    }
+    pub fn c(&self) -> Fixed16_16 {
+        self.c
+    }
+    pub fn d(&self) -> Fixed16_16 {
+        self.d
+    }
+    pub fn v(&self) -> Fixed2_30 {
+        self.v
+    }
+    pub fn x(&self) -> Fixed16_16 {
+        self.x
+    }
+    pub fn y(&self) -> Fixed16_16 {
+        self.y
+    }
+    pub fn w(&self) -> Fixed2_30 {
+        self.w
+    }
+    // The matrix mirrors the image horizontally (negative determinant).
+    pub fn is_flipped(&self) -> bool {
+        let (a, b, c, d) = (self.a.to_bits() as i64, self.b.to_bits() as i64, self.c.to_bits() as i64, self.d.to_bits() as i64);
+        a * d - b * c < 0
+    }
+    // Clockwise rotation in degrees, rounded to 0, 90, 180 or 270,
+    // applied after the horizontal flip if the matrix has one.
+    pub fn rotation(&self) -> u16 {
+        let (a, b) = (self.a.to_f64(), self.b.to_f64());
+        let (a, b) = if self.is_flipped() { (-a, -b) } else { (a, b) };
+        let degrees = b.atan2(a).to_degrees();
+        ((degrees / 90.0).round() as i32).rem_euclid(4) as u16 * 90
+    }
+}
+
+impl Mp4File<File> {
+    pub fn new(filename: &str) -> Result<Self, Mp4Error> {
         let mut file = fs::OpenOptions::new().read(true).write(false)
-                .create(false).open(filename).unwrap();
-        let file_size = file.metadata().unwrap().len();
+                .create(false).open(filename)?;
+        let file_size = file.metadata()?.len();
+
+        let mp4 = Mp4File {
+            file,
+            file_size,
+            offset: 0,
+            atoms: vec![]
+        };
+        Ok(mp4)
+    }
+}
+
+impl<R: Read + Seek> Mp4File<R> {
+    // Wrap any seekable source, e.g. a `Cursor<Vec<u8>>` holding an upload body.
+    pub fn from_reader(mut reader: R) -> Result<Self, Mp4Error> {
+        let file_size = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(0))?;
 
         let mp4 = Mp4File {
-            file: file,
-            file_size: file_size,
+            file: reader,
+            file_size,
             offset: 0,
             atoms: vec![]
         };
         Ok(mp4)
     }
-    pub fn file(&self) -> &File {
+    pub fn file(&self) -> &R {
         &self.file
     }
+    pub fn into_inner(self) -> R {
+        self.file
+    }
     pub fn file_size(&self) -> u64 {
         self.file_size
     }
@@ -61,9 +165,134 @@ impl Mp4File {
     pub fn atoms(&self) -> &Vec<atom::Atom> {
         &self.atoms
     }
-    pub fn parse(&mut self) {
-        let atoms = atom::Atom::parse_children(self);
+    pub fn mdats(&self) -> Vec<&atom::Mdat> {
+        self.atoms.iter().filter_map(|atom| match *atom {
+            atom::Atom::mdat(ref mdat) => Some(mdat),
+            _ => None
+        }).collect()
+    }
+    pub fn ftyp(&self) -> Option<&atom::Ftyp> {
+        self.atoms.iter().filter_map(|atom| match *atom {
+            atom::Atom::ftyp(ref ftyp) => Some(ftyp),
+            _ => None
+        }).next()
+    }
+    pub fn moov(&self) -> Option<&atom::Moov> {
+        self.atoms.iter().filter_map(|atom| match *atom {
+            atom::Atom::moov(ref moov) => Some(moov),
+            _ => None
+        }).next()
+    }
+    pub fn moofs(&self) -> Vec<&atom::Moof> {
+        self.atoms.iter().filter_map(|atom| match *atom {
+            atom::Atom::moof(ref moof) => Some(moof),
+            _ => None
+        }).collect()
+    }
+    // Samples of every movie fragment, in file order.
+    pub fn fragment_samples(&self) -> fragment::FragmentSamples<'_> {
+        let mvex = self.moov().and_then(|moov| moov.mvex());
+        fragment::FragmentSamples::new(mvex, self.moofs())
+    }
+    pub fn parse(&mut self) -> Result<(), Mp4Error> {
+        let atoms = atom::Atom::parse_top_level(self)?;
         self.atoms = atoms;
+        Ok(())
+    }
+    // A recorder that stops without finalising the file leaves its last box
+    // (normally `mdat`) with `size == 0`; such files have no trustworthy index.
+    pub fn is_finalized(&self) -> bool {
+        !self.atoms.iter().any(|atom| atom.header().extends_to_eof())
+    }
+    // Read `size` bytes of media data at file offset `offset`.
+    // The range must lie inside a single `mdat` box.
+    pub fn read_range(&mut self, offset: u64, size: u64) -> Result<Vec<u8>, Mp4Error> {
+        if !self.mdats().iter().any(|mdat| mdat.contains(offset, size)) {
+            return Err(Mp4Error::InvalidValue {
+                kind  : atom::Kind::mdat,
+                offset,
+                reason: "byte range is not inside a media data box"
+            });
+        }
+        let mut buf = vec![0u8; size as usize];
+        self.read_exact_at(offset, &mut buf)?;
+        Ok(buf)
+    }
+    pub fn read_sample(&mut self, sample: &SampleInfo) -> Result<Vec<u8>, Mp4Error> {
+        self.read_range(sample.offset, sample.size as u64)
+    }
+    pub fn read_exact_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<(), Mp4Error> {
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.read_exact(buf)?;
+        Ok(())
+    }
+    // Copy `size` bytes starting at file offset `offset` to `w`, in bounded chunks.
+    pub fn copy_range<W: Write>(&mut self, offset: u64, size: u64, w: &mut W) -> Result<(), Mp4Error> {
+        self.file.seek(SeekFrom::Start(offset))?;
+        let copied = io::copy(&mut (&mut self.file).take(size), w)?;
+        if copied < size {
+            return Err(Mp4Error::Io(Error::new(ErrorKind::UnexpectedEof, "source ended inside the copied range")));
+        }
+        Ok(())
+    }
+    // Copy `(offset, size)` byte ranges in order; adjacent ranges are copied in one go.
+    pub fn copy_ranges<W: Write, I: IntoIterator<Item = (u64, u64)>>(&mut self, ranges: I, w: &mut W) -> Result<(), Mp4Error> {
+        let mut pending: Option<(u64, u64)> = None;
+        for (offset, size) in ranges {
+            pending = match pending {
+                Some((start, len)) if start + len == offset => Some((start, len + size)),
+                Some((start, len)) => {
+                    self.copy_range(start, len, w)?;
+                    Some((offset, size))
+                },
+                None => Some((offset, size))
+            };
+        }
+        if let Some((start, len)) = pending {
+            self.copy_range(start, len, w)?;
+        }
+        Ok(())
+    }
+    // Serialize every parsed box again; `mdat` payloads are streamed from the source.
+    pub fn write_to<W: Write>(&mut self, w: &mut W) -> Result<(), Mp4Error> {
+        for idx in 0..self.atoms.len() {
+            self.write_atom(idx, w)?;
+        }
+        Ok(())
+    }
+    // Serialize the top level box `idx`, streaming a payload left in the source.
+    pub fn write_atom<W: Write>(&mut self, idx: usize, w: &mut W) -> Result<(), Mp4Error> {
+        match self.atoms[idx].source_range() {
+            Some((offset, size)) => {
+                self.atoms[idx].header().write_header(w, size)?;
+                self.copy_range(offset, size, w)
+            },
+            None => self.atoms[idx].write(w)
+        }
+    }
+    // Write a copy of the file with `moov` in front of the media data.
+    pub fn faststart<W: Write>(&mut self, w: &mut W) -> Result<(), Mp4Error> {
+        faststart::faststart(self, w)
+    }
+    // Write a fragmented copy of the file, cut at sync samples about every `fragment_duration` seconds.
+    pub fn fragment<W: Write>(&mut self, w: &mut W, fragment_duration: f64) -> Result<(), Mp4Error> {
+        fragmenter::fragment(self, w, fragment_duration)
+    }
+    // Write a progressive copy of a fragmented file, with every sample in one mdat.
+    pub fn defragment<W: Write>(&mut self, w: &mut W) -> Result<(), Mp4Error> {
+        defragmenter::defragment(self, w)
+    }
+    // Write a copy of the file holding only the presentation from `start` to `end` seconds.
+    pub fn trim<W: Write>(&mut self, w: &mut W, start: f64, end: f64) -> Result<(), Mp4Error> {
+        trimmer::trim(self, w, start, end)
+    }
+    // Write a copy of the file with only the selected tracks.
+    pub fn extract_tracks<W: Write>(&mut self, w: &mut W, selectors: &[extractor::TrackSelector]) -> Result<(), Mp4Error> {
+        extractor::extract(self, w, selectors)
+    }
+    // Write a copy of the file without the selected tracks.
+    pub fn remove_tracks<W: Write>(&mut self, w: &mut W, selectors: &[extractor::TrackSelector]) -> Result<(), Mp4Error> {
+        extractor::remove(self, w, selectors)
     }
     // File Seek
     pub fn seek(&mut self, offset: u64) -> Result<u64, Error> {
@@ -96,45 +325,47 @@ impl Mp4File {
         self.file.read_i64::<BigEndian>()
     }
 
+    pub fn read_bytes(&mut self, len: u64)-> Result<Vec<u8>, Error> {
+        let mut buf = Vec::new();
+        (&mut self.file).take(len).read_to_end(&mut buf)?;
+        if (buf.len() as u64) < len {
+            return Err(Error::new(ErrorKind::UnexpectedEof, "failed to fill whole buffer"));
+        }
+        Ok(buf)
+    }
+
     pub fn read_f32(&mut self)-> Result<f32, Error> {
         self.file.read_f32::<BigEndian>()
     }
     pub fn read_f64(&mut self)-> Result<f64, Error> {
         self.file.read_f64::<BigEndian>()
     }
+    // Signed fixed-point number of 16 or 32 bits, e.g. (16, 16) for `rate` or (8, 8) for `volume`.
+    // See `fixed` for types that keep the raw bits.
     pub fn read_fixed_point(&mut self, integerLength: usize, fractionalLength: usize) -> Result<f64, Error>{
         // https://en.wikipedia.org/wiki/Fixed_point_(mathematics)
+        let scale = (1u64 << fractionalLength) as f64;
         if integerLength + fractionalLength == 16 {
-            let n = self.read_u16().unwrap();
-            let integer: u16 = n >> fractionalLength as u16;
-            let fractional_mask: u16 = 2u16.pow(fractionalLength as u32) - 1;
-            let fractional: u16 = (n&fractional_mask) / (1 << (fractionalLength as u16));
-            let result = (integer + fractional) as f64;
-            Ok(result)
+            Ok(self.read_i16()? as f64 / scale)
         } else {
-            let n = self.read_u32().unwrap();
-            let integer: u32 = n >> fractionalLength as u32;
-            let fractional_mask: u32 = 2u32.pow(fractionalLength as u32) - 1;
-            let fractional: u32 = (n&fractional_mask) / (1 << (fractionalLength as u32));
-            let result = (integer + fractional) as f64;
-            Ok(result)
+            Ok(self.read_i32()? as f64 / scale)
         }
     }
     pub fn read_matrix(&mut self) -> Result<Matrix, Error>{
         // length: u32 * 9 (  4*9 = 36 Bytes )
-        let a = self.read_fixed_point( 16, 16 ).unwrap();
-        let b = self.read_fixed_point( 16, 16 ).unwrap();
-        let u = self.read_fixed_point(  2, 30 ).unwrap();
-        let c = self.read_fixed_point( 16, 16 ).unwrap();
-        let d = self.read_fixed_point( 16, 16 ).unwrap();
-        let v = self.read_fixed_point(  2, 30 ).unwrap();
-        let x = self.read_fixed_point( 16, 16 ).unwrap();
-        let y = self.read_fixed_point( 16, 16 ).unwrap();
-        let w = self.read_fixed_point(  2, 30 ).unwrap();
+        let a = self.read_i32()?;
+        let b = self.read_i32()?;
+        let u = self.read_i32()?;
+        let c = self.read_i32()?;
+        let d = self.read_i32()?;
+        let v = self.read_i32()?;
+        let x = self.read_i32()?;
+        let y = self.read_i32()?;
+        let w = self.read_i32()?;
         Ok(Matrix {
-            a: a, b: b, u: u,
-            c: c, d: d, v: v,
-            x: x, y: y, w: w
+            a: Fixed16_16::from_bits(a), b: Fixed16_16::from_bits(b), u: Fixed2_30::from_bits(u),
+            c: Fixed16_16::from_bits(c), d: Fixed16_16::from_bits(d), v: Fixed2_30::from_bits(v),
+            x: Fixed16_16::from_bits(x), y: Fixed16_16::from_bits(y), w: Fixed2_30::from_bits(w)
         })
     }
     pub fn read_iso639_code(&mut self) -> Result<String, Error> {
@@ -142,7 +373,7 @@ impl Mp4File {
         //      pad   :  1 Bit
         //      string: 15 Bit
         let mut s = String::new();
-        let n = self.read_u16().unwrap();
+        let n = self.read_u16()?;
         let mut c1 = ( n & 0x7C00 ) >> 10;  // Mask is 0111 1100 0000 0000
         let mut c2 = ( n & 0x03E0 ) >> 5;   // Mask is 0000 0011 1110 0000
         let mut c3 = ( n & 0x001F );        // Mask is 0000 0000 0001 1111
@@ -157,12 +388,9 @@ impl Mp4File {
     }
 }
 
-pub fn parse_file(filename: &str) -> Result<Mp4File, &'static str>{
-    let mut mp4 = Mp4File::new(filename).unwrap();
-    mp4.parse();
-    for atom in mp4.atoms() {
-        println!("Atom: \n\t{:?}", atom);
-    }
+pub fn parse_file(filename: &str) -> Result<Mp4File<File>, Mp4Error>{
+    let mut mp4 = Mp4File::new(filename)?;
+    mp4.parse()?;
     Ok(mp4)
 }
 
@@ -0,0 +1,285 @@
+/*
+
+Progressive writer: write a file with one `mdat` from a `moov` and the samples to
+keep of each of its tracks, read from one or more source files.
+
+    boxes in front of moov (ftyp, free, ...)    of the first source
+    moov        sample tables rebuilt from the samples, no mvex
+    every other box in file order, but those of fragments and segments
+                (moof, mdat, mfra, sidx, ssix, styp, emsg, prft)
+    mdat        the chunks, in source file order
+
+A chunk is a run of samples of one track, in decode order, that follow each other
+in one source and share a sample description. Writing the chunks in the order
+they appear in the sources keeps their interleaving, while the samples of each
+chunk stay in decode order, so chunks stored out of order in a source are put
+back in order. Chunk offsets are written as `co64` when they do not fit in 32
+bits, which grows `moov` and so the offsets; the layout is recomputed until it is
+stable.
+
+Sample tables are rebuilt from the sample durations, sizes, composition offsets
+and sync flags; the decode time of the first sample becomes 0.
+
+Media data is streamed from the source, never loaded into memory as a whole.
+
+**/
+
+use std::io::{Read, Seek, Write};
+use std::slice;
+
+use atom::{Atom, Kind, Header, Moov, Trak, Edts, Elst, Entry, Stts, STTS_Entry, Ctts, CTTS_Entry_Offset,
+           Stsc, Stsz, Stco, Co64, Stss};
+use sample_table::SampleInfo;
+use {Mp4File, Mp4Error};
+
+// The samples of a `trak`, in decode order, with its new durations.
+pub struct Track {
+    pub samples: Vec<(usize, SampleInfo)>,  // (index of the source, sample)
+    pub elst   : Option<Elst>,  // edit list of the trak, None to write no edts
+    pub media_duration: u64,    // media timescale
+    pub track_duration: u64     // movie timescale
+}
+
+struct Chunk {
+    offset : u64,   // from the start of the mdat payload
+    first  : usize, // index of the first sample in the track
+    samples: u32,
+    description_index: u32
+}
+
+impl Chunk {
+    fn samples<'a>(&self, track: &'a Track) -> &'a [(usize, SampleInfo)] {
+        &track.samples[self.first..self.first + self.samples as usize]
+    }
+}
+
+// A copy of the `moov` of a source, to rebuild.
+pub fn source_moov<R: Read + Seek>(mp4: &Mp4File<R>) -> Result<Moov, Mp4Error> {
+    match mp4.moov() {
+        Some(moov) => Ok(moov.clone()),
+        None => Err(Mp4Error::InvalidValue {
+            kind  : Kind::moov,
+            offset: 0,
+            reason: "file has no moov box"
+        })
+    }
+}
+
+// `tracks` holds one entry per `trak` of `moov`, in order.
+pub fn write<R: Read + Seek, W: Write>(mp4: &mut Mp4File<R>, w: &mut W, moov: &Moov, tracks: &[Track]) -> Result<(), Mp4Error> {
+    write_sources(slice::from_mut(mp4), w, moov, tracks)
+}
+
+pub fn write_sources<R: Read + Seek, W: Write>(sources: &mut [Mp4File<R>], w: &mut W, moov: &Moov, tracks: &[Track]) -> Result<(), Mp4Error> {
+    if sources.is_empty() {
+        return Ok(());
+    }
+    // Chunks of each track, in decode order.
+    let mut chunks: Vec<Vec<Chunk>> = Vec::new();
+    for track in tracks {
+        let mut track_chunks: Vec<Chunk> = Vec::new();
+        let mut last: Option<&(usize, SampleInfo)> = None;
+        for (idx, entry) in track.samples.iter().enumerate() {
+            let (source, ref sample) = *entry;
+            let follows = last.is_some_and(|&(last_source, ref last)| {
+                last_source == source && last.description_index == sample.description_index
+                    && last.offset.checked_add(last.size as u64) == Some(sample.offset)
+            });
+            match track_chunks.last_mut() {
+                Some(chunk) if follows => chunk.samples += 1,
+                _ => track_chunks.push(Chunk {
+                    offset : 0,
+                    first  : idx,
+                    samples: 1,
+                    description_index: sample.description_index
+                })
+            }
+            last = Some(entry);
+        }
+        chunks.push(track_chunks);
+    }
+
+    // (track, chunk) in file order.
+    let mut order: Vec<(usize, usize)> = Vec::new();
+    for (idx, track_chunks) in chunks.iter().enumerate() {
+        order.extend((0..track_chunks.len()).map(|chunk| (idx, chunk)));
+    }
+    order.sort_by_key(|&(idx, chunk)| {
+        let (source, ref sample) = tracks[idx].samples[chunks[idx][chunk].first];
+        (source, sample.offset)
+    });
+    let mut payload_size = 0u64;
+    for &(idx, chunk) in &order {
+        let chunk = &mut chunks[idx][chunk];
+        chunk.offset = payload_size;
+        payload_size += chunk.samples(&tracks[idx]).iter().map(|(_, sample)| sample.size as u64).sum::<u64>();
+    }
+    let last_chunk = chunks.iter()
+        .flat_map(|track| track.iter())
+        .map(|chunk| chunk.offset)
+        .max()
+        .unwrap_or(0);
+
+    let mdat = Header::new(Kind::mdat);
+    let mdat_header_size = if 8 + payload_size > u32::MAX as u64 { 16 } else { 8 };
+    let mut large = false;
+    let mut head: Vec<u8>;
+    let mut data_start = 0;
+    loop {
+        // The size of moov depends on the chunk offset box type, not on the offsets.
+        head = Vec::new();
+        write_head(&mut sources[0], &rebuild_moov(moov, tracks, &chunks, data_start, large), &mut head)?;
+        let start = head.len() as u64 + mdat_header_size;
+        if start == data_start {
+            break;
+        }
+        data_start = start;
+        if !large && data_start + last_chunk > u32::MAX as u64 {
+            large = true;
+        }
+    }
+    w.write_all(&head)?;
+
+    mdat.write_header(w, payload_size)?;
+    // Runs of chunks from one source.
+    let source = |&(idx, chunk): &(usize, usize)| tracks[idx].samples[chunks[idx][chunk].first].0;
+    let mut start = 0;
+    while start < order.len() {
+        let end = start + order[start..].iter()
+            .position(|entry| source(entry) != source(&order[start]))
+            .unwrap_or(order.len() - start);
+        let ranges = order[start..end].iter().flat_map(|&(idx, chunk)| {
+            chunks[idx][chunk].samples(&tracks[idx]).iter().map(|(_, sample)| (sample.offset, sample.size as u64))
+        });
+        sources[source(&order[start])].copy_ranges(ranges, w)?;
+        start = end;
+    }
+    Ok(())
+}
+
+// Every top level box but the media data and the fragment and segment boxes, with `moov` replaced.
+fn write_head<R: Read + Seek, W: Write>(mp4: &mut Mp4File<R>, moov: &Moov, w: &mut W) -> Result<(), Mp4Error> {
+    for idx in 0..mp4.atoms().len() {
+        match *mp4.atoms()[idx].kind() {
+            Kind::moov => moov.write(w)?,
+            Kind::moof | Kind::mdat | Kind::mfra => {},
+            // Segment boxes are not parsed.
+            Kind::Unrecognized(ref name) if matches!(name.as_str(), "sidx" | "ssix" | "styp" | "emsg" | "prft") => {},
+            _ => mp4.write_atom(idx, w)?
+        }
+    }
+    Ok(())
+}
+
+fn rebuild_moov(moov: &Moov, tracks: &[Track], chunks: &[Vec<Chunk>], data_start: u64, large: bool) -> Moov {
+    let mut moov = moov.clone();
+    moov.children_mut().retain(|atom| *atom.kind() != Kind::mvex);
+    let mut traks = tracks.iter().zip(chunks);
+    for atom in moov.children_mut().iter_mut() {
+        match *atom {
+            Atom::mvhd(ref mut mvhd) => {
+                mvhd.set_duration(tracks.iter().map(|track| track.track_duration).max().unwrap_or(0));
+            },
+            Atom::trak(ref mut trak) => {
+                if let Some((track, chunks)) = traks.next() {
+                    rebuild_trak(trak, track, chunks, data_start, large);
+                }
+            },
+            _ => {}
+        }
+    }
+    moov
+}
+
+fn rebuild_trak(trak: &mut Trak, track: &Track, chunks: &[Chunk], data_start: u64, large: bool) {
+    let children = trak.children_mut();
+    children.retain(|atom| *atom.kind() != Kind::edts);
+    if let Some(ref elst) = track.elst {
+        let idx = children.iter().position(|atom| *atom.kind() == Kind::tkhd).map_or(0, |idx| idx + 1);
+        children.insert(idx, Atom::edts(Edts::new(elst.clone())));
+    }
+    for child in trak.children_mut().iter_mut() {
+        rebuild(child, track, chunks, data_start, large);
+    }
+}
+
+fn rebuild(atom: &mut Atom, track: &Track, chunks: &[Chunk], data_start: u64, large: bool) {
+    match *atom {
+        Atom::tkhd(ref mut tkhd) => tkhd.set_duration(track.track_duration),
+        Atom::mdhd(ref mut mdhd) => mdhd.set_duration(track.media_duration),
+        Atom::stbl(ref mut stbl) => {
+            let children = stbl.children_mut();
+            children.retain(|atom| matches!(*atom.kind(), Kind::stsd | Kind::sgpd));
+            children.extend(sample_tables(track, chunks, data_start, large));
+        },
+        ref mut atom => {
+            if let Some(children) = atom.children_mut() {
+                for child in children.iter_mut() {
+                    rebuild(child, track, chunks, data_start, large);
+                }
+            }
+        }
+    }
+}
+
+fn sample_tables(track: &Track, chunks: &[Chunk], data_start: u64, large: bool) -> Vec<Atom> {
+    let samples: Vec<&SampleInfo> = track.samples.iter().map(|(_, sample)| sample).collect();
+
+    let mut stts: Vec<STTS_Entry> = Vec::new();
+    let mut run: Option<(u32, u32)> = None;
+    for sample in &samples {
+        run = match run {
+            Some((count, delta)) if delta == sample.duration => Some((count + 1, delta)),
+            Some((count, delta)) => {
+                stts.push(STTS_Entry::new(count, delta));
+                Some((1, sample.duration))
+            },
+            None => Some((1, sample.duration))
+        };
+    }
+    stts.extend(run.map(|(count, delta)| STTS_Entry::new(count, delta)));
+
+    let mut ctts: Vec<CTTS_Entry_Offset> = Vec::new();
+    if samples.iter().any(|sample| sample.cts != sample.dts as i64) {
+        let mut run: Option<(u32, i32)> = None;
+        for sample in &samples {
+            let offset = (sample.cts - sample.dts as i64) as i32;
+            run = match run {
+                Some((count, last)) if last == offset => Some((count + 1, last)),
+                Some((count, last)) => {
+                    ctts.push(CTTS_Entry_Offset::new(count, last));
+                    Some((1, offset))
+                },
+                None => Some((1, offset))
+            };
+        }
+        ctts.extend(run.map(|(count, offset)| CTTS_Entry_Offset::new(count, offset)));
+    }
+
+    let mut stsc: Vec<Entry> = Vec::new();
+    let mut last: Option<(u32, u32)> = None;
+    for (idx, chunk) in chunks.iter().enumerate() {
+        if last != Some((chunk.samples, chunk.description_index)) {
+            stsc.push(Entry::new(idx as u32 + 1, chunk.samples, chunk.description_index));
+            last = Some((chunk.samples, chunk.description_index));
+        }
+    }
+
+    let mut tables = vec![Atom::stts(Stts::new(stts))];
+    if !ctts.is_empty() {
+        tables.push(Atom::ctts(Ctts::new(ctts)));
+    }
+    tables.push(Atom::stsc(Stsc::new(stsc)));
+    tables.push(Atom::stsz(Stsz::new(samples.iter().map(|sample| sample.size).collect())));
+    let offsets = chunks.iter().map(|chunk| data_start + chunk.offset);
+    if large {
+        tables.push(Atom::co64(Co64::new(offsets.collect())));
+    } else {
+        tables.push(Atom::stco(Stco::new(offsets.map(|offset| offset as u32).collect())));
+    }
+    if samples.iter().any(|sample| !sample.is_sync) {
+        let sync = samples.iter().zip(1..).filter(|&(sample, _)| sample.is_sync).map(|(_, number)| number).collect();
+        tables.push(Atom::stss(Stss::new(sync)));
+    }
+    tables
+}
@@ -0,0 +1,341 @@
+/*
+
+Sample table resolver.
+
+Joins the raw tables of a `stbl` box into samples:
+
+    stsz / stz2   sample sizes
+    stsc          sample -> chunk mapping
+    stco / co64   chunk -> file offset
+    stts          decoding time (DT)
+    ctts          composition offset, CT(n) = DT(n) + CTTS(n)
+    stss          sync samples (all samples are sync samples when absent)
+
+Sample numbers are 1-based, as in the specification.
+Timestamps are expressed in the media timescale (`mdhd`).
+
+Seeking looks up the sample decoded at the target time through `stts`, steps back
+to the preceding sync sample through `stss`, and keeps stepping back while that
+sample's composition time (`ctts`) is still after the target.
+
+**/
+
+use atom::{Stbl, Header};
+use Mp4Error;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SampleInfo {
+    pub number  : u32,  // 1-based sample number
+    pub offset  : u64,  // absolute file offset of the sample data
+    pub size    : u32,
+    pub dts     : u64,  // decoding time
+    pub cts     : i64,  // composition time
+    pub duration: u32,
+    pub is_sync : bool,
+    pub description_index: u32
+}
+
+#[derive(Debug, Clone)]
+struct TimeRun {
+    first_sample: u64,  // 0-based
+    count       : u32,
+    delta       : u32,
+    first_dts   : u64
+}
+
+#[derive(Debug, Clone)]
+struct OffsetRun {
+    first_sample: u64,  // 0-based
+    count       : u32,
+    offset      : i32
+}
+
+#[derive(Debug, Clone)]
+struct ChunkRun {
+    first_chunk      : u32,  // 0-based
+    first_sample     : u64,  // 0-based
+    samples_per_chunk: u32,
+    description_index: u32
+}
+
+#[derive(Debug, Clone)]
+pub struct SampleTable {
+    sample_count : u32,
+    sample_size  : u32,       // constant sample size, 0 if sizes are listed
+    sample_sizes : Vec<u32>,
+    chunk_offsets: Vec<u64>,
+    chunk_runs   : Vec<ChunkRun>,
+    time_runs    : Vec<TimeRun>,
+    offset_runs  : Vec<OffsetRun>,
+    sync_samples : Option<Vec<u32>>
+}
+
+impl SampleTable {
+    pub fn new(stbl: &Stbl) -> Result<Self, Mp4Error> {
+        let header = stbl.header();
+
+        let (sample_count, sample_size, sample_sizes) = match (stbl.stsz(), stbl.stz2()) {
+            (Some(stsz), _) => {
+                let sizes = stsz.entry_size().cloned().unwrap_or_default();
+                (stsz.sample_count(), stsz.sample_size(), sizes)
+            },
+            (None, Some(stz2)) => (stz2.sample_count(), 0u32, stz2.entry_size().clone()),
+            (None, None) => return Err(header.invalid_value("stbl has no stsz or stz2"))
+        };
+        if sample_size == 0 && (sample_sizes.len() as u64) < sample_count as u64 {
+            return Err(header.invalid_value("sample size table is shorter than sample_count"));
+        }
+
+        let chunk_offsets: Vec<u64> = match (stbl.stco(), stbl.co64()) {
+            (Some(stco), _) => stco.chunks().iter().map(|offset| *offset as u64).collect(),
+            (None, Some(co64)) => co64.chunks().clone(),
+            (None, None) => return Err(header.invalid_value("stbl has no stco or co64"))
+        };
+
+        let chunk_runs = SampleTable::chunk_runs(header, stbl, chunk_offsets.len() as u32, sample_count)?;
+
+        let mut time_runs: Vec<TimeRun> = Vec::new();
+        let mut first_sample = 0u64;
+        let mut first_dts = 0u64;
+        if let Some(stts) = stbl.stts() {
+            for entry in stts.entries() {
+                time_runs.push(TimeRun {
+                    first_sample,
+                    count       : entry.sample_count(),
+                    delta       : entry.sample_delta(),
+                    first_dts
+                });
+                first_sample += entry.sample_count() as u64;
+                first_dts += entry.sample_count() as u64 * entry.sample_delta() as u64;
+            }
+        }
+        if first_sample < sample_count as u64 {
+            return Err(header.invalid_value("stts does not cover every sample"));
+        }
+
+        let mut offset_runs: Vec<OffsetRun> = Vec::new();
+        let mut first_sample = 0u64;
+        if let Some(ctts) = stbl.ctts() {
+            for entry in ctts.entries() {
+                offset_runs.push(OffsetRun {
+                    first_sample,
+                    count       : entry.sample_count(),
+                    offset      : entry.sample_offset()
+                });
+                first_sample += entry.sample_count() as u64;
+            }
+        }
+
+        // Lookups are binary searches; tolerate writers that do not keep the table sorted.
+        let sync_samples = stbl.stss().map(|stss| {
+            let mut samples = stss.entries().clone();
+            samples.sort_unstable();
+            samples.dedup();
+            samples
+        });
+
+        Ok(SampleTable {
+            sample_count,
+            sample_size,
+            sample_sizes,
+            chunk_offsets,
+            chunk_runs,
+            time_runs,
+            offset_runs,
+            sync_samples
+        })
+    }
+
+    fn chunk_runs(header: &Header, stbl: &Stbl, chunk_count: u32, sample_count: u32) -> Result<Vec<ChunkRun>, Mp4Error> {
+        let entries = match stbl.stsc() {
+            Some(stsc) => stsc.entries(),
+            None => return Err(header.invalid_value("stbl has no stsc"))
+        };
+        let mut runs: Vec<ChunkRun> = Vec::new();
+        let mut first_sample = 0u64;
+        for (idx, entry) in entries.iter().enumerate() {
+            if entry.first_chunk() < 1 || entry.first_chunk() > chunk_count {
+                return Err(header.invalid_value("stsc first_chunk is out of range"));
+            }
+            let first_chunk = entry.first_chunk() - 1;
+            let next_chunk = match entries.get(idx + 1) {
+                Some(next) => next.first_chunk().saturating_sub(1),
+                None => chunk_count
+            };
+            if next_chunk < first_chunk {
+                return Err(header.invalid_value("stsc entries are not in chunk order"));
+            }
+            runs.push(ChunkRun {
+                first_chunk,
+                first_sample,
+                samples_per_chunk: entry.samples_per_chunk(),
+                description_index: entry.sample_description_index()
+            });
+            first_sample += (next_chunk - first_chunk) as u64 * entry.samples_per_chunk() as u64;
+        }
+        if first_sample < sample_count as u64 {
+            return Err(header.invalid_value("chunks do not hold every sample"));
+        }
+        Ok(runs)
+    }
+
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+    pub fn chunk_count(&self) -> u32 {
+        self.chunk_offsets.len() as u32
+    }
+    // `None` when the track has no `stss` box, i.e. every sample is a sync sample.
+    pub fn sync_samples(&self) -> Option<&Vec<u32>> {
+        self.sync_samples.as_ref()
+    }
+    pub fn is_sync(&self, number: u32) -> bool {
+        match self.sync_samples {
+            Some(ref samples) => samples.binary_search(&number).is_ok(),
+            None => true
+        }
+    }
+    // The last sync sample at or before sample `number`.
+    pub fn sync_sample_before(&self, number: u32) -> Option<u32> {
+        match self.sync_samples {
+            Some(ref samples) => match samples.partition_point(|sample| *sample <= number) {
+                0 => None,
+                idx => Some(samples[idx - 1])
+            },
+            None if number >= 1 && number <= self.sample_count => Some(number),
+            None => None
+        }
+    }
+    // The sample being decoded at `dts`: the last sample whose decoding time is not after it.
+    pub fn sample_at_dts(&self, dts: u64) -> Option<u32> {
+        if self.sample_count == 0 {
+            return None;
+        }
+        let idx = self.time_runs.partition_point(|run| run.first_dts <= dts);
+        let run = &self.time_runs[idx.max(1) - 1];
+        let last_in_run = run.count.saturating_sub(1) as u64;
+        let in_run = match run.delta {
+            0 => last_in_run,
+            delta => ((dts - run.first_dts) / delta as u64).min(last_in_run)
+        };
+        let n = (run.first_sample + in_run).min(self.sample_count as u64 - 1);
+        Some(n as u32 + 1)
+    }
+    // The sync sample to start decoding from to present composition time `time`, i.e. the
+    // last sync sample not presented after it; the first sync sample if `time` precedes them all.
+    pub fn seek(&self, time: i64) -> Option<SampleInfo> {
+        let number = if time < 0 { 1 } else { self.sample_at_dts(time as u64)? };
+        let first_sync = match self.sync_samples {
+            Some(ref samples) => *samples.first()?,
+            None => 1
+        };
+        let mut sync = self.sync_sample_before(number).unwrap_or(first_sync);
+        loop {
+            let sample = self.sample(sync)?;
+            if sample.cts <= time {
+                return Some(sample);
+            }
+            match self.sync_sample_before(sync - 1) {
+                Some(prev) => sync = prev,
+                None => return Some(sample)
+            }
+        }
+    }
+    pub fn sample_size(&self, number: u32) -> Option<u32> {
+        if number < 1 || number > self.sample_count {
+            return None;
+        }
+        if self.sample_size != 0 {
+            Some(self.sample_size)
+        } else {
+            Some(self.sample_sizes[(number - 1) as usize])
+        }
+    }
+
+    // Random access by 1-based sample number.
+    pub fn sample(&self, number: u32) -> Option<SampleInfo> {
+        self.resolve(number, None)
+    }
+    pub fn iter(&self) -> Samples<'_> {
+        Samples {
+            table: self,
+            next : 1,
+            prev : None
+        }
+    }
+
+    // (chunk index, 0-based number of the first sample in that chunk, description index)
+    fn locate(&self, n: u64) -> (usize, u64, u32) {
+        let idx = self.chunk_runs.partition_point(|run| run.first_sample <= n) - 1;
+        let run = &self.chunk_runs[idx];
+        let chunk_in_run = (n - run.first_sample) / run.samples_per_chunk as u64;
+        let chunk = run.first_chunk as u64 + chunk_in_run;
+        let first_in_chunk = run.first_sample + chunk_in_run * run.samples_per_chunk as u64;
+        (chunk as usize, first_in_chunk, run.description_index)
+    }
+
+    fn resolve(&self, number: u32, prev: Option<&(SampleInfo, usize)>) -> Option<SampleInfo> {
+        let size = self.sample_size(number)?;
+        let n = (number - 1) as u64;
+
+        let (chunk, first_in_chunk, description_index) = self.locate(n);
+        let offset = match prev {
+            Some(&(ref prev, prev_chunk)) if prev_chunk == chunk && prev.number == number - 1 => {
+                prev.offset + prev.size as u64
+            },
+            _ => {
+                let mut offset = self.chunk_offsets[chunk];
+                for i in first_in_chunk..n {
+                    offset += self.sample_size(i as u32 + 1).unwrap_or(0) as u64;
+                }
+                offset
+            }
+        };
+
+        let idx = self.time_runs.partition_point(|run| run.first_sample <= n) - 1;
+        let run = &self.time_runs[idx];
+        let dts = run.first_dts + (n - run.first_sample) * run.delta as u64;
+
+        let composition_offset = match self.offset_runs.partition_point(|run| run.first_sample <= n) {
+            0 => 0,
+            idx => {
+                let run = &self.offset_runs[idx - 1];
+                if n < run.first_sample + run.count as u64 { run.offset } else { 0 }
+            }
+        };
+
+        Some(SampleInfo {
+            number,
+            offset,
+            size,
+            dts,
+            cts     : dts as i64 + composition_offset as i64,
+            duration: run.delta,
+            is_sync : self.is_sync(number),
+            description_index
+        })
+    }
+}
+
+pub struct Samples<'a> {
+    table: &'a SampleTable,
+    next : u32,
+    prev : Option<(SampleInfo, usize)>
+}
+
+impl<'a> Iterator for Samples<'a> {
+    type Item = SampleInfo;
+
+    fn next(&mut self) -> Option<SampleInfo> {
+        let sample = self.table.resolve(self.next, self.prev.as_ref())?;
+        let (chunk, _, _) = self.table.locate((self.next - 1) as u64);
+        self.prev = Some((sample.clone(), chunk));
+        self.next += 1;
+        Some(sample)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let left = (self.table.sample_count + 1).saturating_sub(self.next) as usize;
+        (left, Some(left))
+    }
+}
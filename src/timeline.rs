@@ -0,0 +1,132 @@
+/*
+
+Presentation timeline of a track.
+
+The edit list (`elst`) maps the media timeline, on which the sample table places
+the samples, onto the movie presentation timeline:
+
+    presentation    |-- empty edit --|---- edit ----|
+    media                            |media_time ...|
+
+Without an edit list both timelines are identical.
+
+Media times are composition times in the media timescale (`mdhd`). Presentation
+times are expressed in the same units, counted from the start of the movie, so they
+compare directly with `SampleInfo::cts`; `segment_duration` is rescaled from the
+movie timescale (`mvhd`). An edit with `segment_duration` 0, as written for
+fragmented files, extends to the end of the media.
+
+**/
+
+use atom::Elst;
+
+const RATE_ONE: i32 = 1 << 16;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    pub presentation_start: u64,          // media timescale
+    pub duration  : Option<u64>,          // media timescale, None when unbounded
+    pub media_time: Option<i64>,          // None for an empty edit
+    pub media_rate: i32                   // 16.16 fixed-point, 0 for a dwell
+}
+
+impl Segment {
+    fn contains(&self, presentation_time: u64) -> bool {
+        presentation_time >= self.presentation_start
+            && self.duration.is_none_or(|duration| presentation_time - self.presentation_start < duration)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Timeline {
+    movie_timescale: u32,
+    media_timescale: u32,
+    segments: Vec<Segment>
+}
+
+// Convert `value` from timescale `from` to timescale `to`, rounding down and saturating.
+pub fn rescale(value: u64, from: u32, to: u32) -> u64 {
+    if from == 0 {
+        return 0;
+    }
+    (value as u128 * to as u128 / from as u128).min(u64::MAX as u128) as u64
+}
+
+impl Timeline {
+    pub fn new(elst: Option<&Elst>, movie_timescale: u32, media_timescale: u32) -> Timeline {
+        let mut segments = Vec::new();
+        // Edits are accumulated in the movie timescale so rounding does not drift.
+        let mut elapsed = 0u64;
+        for entry in elst.map(|elst| elst.entries().as_slice()).unwrap_or(&[]) {
+            let start = rescale(elapsed, movie_timescale, media_timescale);
+            elapsed = elapsed.saturating_add(entry.segment_duration());
+            let duration = match entry.segment_duration() {
+                0 => None,
+                _ => Some(rescale(elapsed, movie_timescale, media_timescale).saturating_sub(start))
+            };
+            segments.push(Segment {
+                presentation_start: start,
+                duration,
+                media_time: if entry.is_empty() { None } else { Some(entry.media_time()) },
+                media_rate: (entry.media_rate_integer() as i32) << 16 | entry.media_rate_fraction() as u16 as i32
+            });
+        }
+        if segments.is_empty() {
+            segments.push(Segment {
+                presentation_start: 0,
+                duration  : None,
+                media_time: Some(0),
+                media_rate: RATE_ONE
+            });
+        }
+        Timeline {
+            movie_timescale,
+            media_timescale,
+            segments
+        }
+    }
+    pub fn movie_timescale(&self) -> u32 {
+        self.movie_timescale
+    }
+    pub fn media_timescale(&self) -> u32 {
+        self.media_timescale
+    }
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+    // Presentation duration in the media timescale, None if the last edit is unbounded.
+    pub fn duration(&self) -> Option<u64> {
+        self.segments.last().and_then(|segment| segment.duration.map(|duration| segment.presentation_start + duration))
+    }
+    // Earliest presentation time of `media_time`, None if no edit presents it
+    // (e.g. encoder priming before the first edit's `media_time`).
+    pub fn media_to_presentation(&self, media_time: i64) -> Option<u64> {
+        for segment in &self.segments {
+            let start = match segment.media_time {
+                Some(start) => start,
+                None => continue
+            };
+            if segment.media_rate == 0 {
+                if media_time == start {
+                    return Some(segment.presentation_start);
+                }
+                continue;
+            }
+            if segment.media_rate < 0 || media_time < start {
+                continue;
+            }
+            let offset = ((media_time - start) as i128 * RATE_ONE as i128 / segment.media_rate as i128) as u64;
+            if segment.duration.is_none_or(|duration| offset < duration) {
+                return Some(segment.presentation_start + offset);
+            }
+        }
+        None
+    }
+    // Media time presented at `presentation_time`, None inside an empty edit or after the last edit.
+    pub fn presentation_to_media(&self, presentation_time: u64) -> Option<i64> {
+        let segment = self.segments.iter().find(|segment| segment.contains(presentation_time))?;
+        let start = segment.media_time?;
+        let offset = (presentation_time - segment.presentation_start) as i128;
+        Some(start + (offset * segment.media_rate as i128 / RATE_ONE as i128) as i64)
+    }
+}
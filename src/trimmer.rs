@@ -0,0 +1,116 @@
+/*
+
+Trimmer: keep the presentation from `start` to `end` seconds of a progressive file,
+without re-encoding.
+
+On every track the kept samples run from the sync sample to start decoding from to
+show `start` (see `Trak::seek`) to the last sample decoded before the one presented
+at `end`; a sample presented before `end` never decodes after it. Video thus starts
+at the preceding sync sample, and an edit list hides what is presented before
+`start`:
+
+    elst    empty edit      only if the track shows nothing at `start`
+            edit            media_time: composition time shown at `start`
+                            segment_duration: up to `end`, or the end of the track
+
+Times follow the edit list of the source. The file is written with a single `mdat`
+(see `progressive`).
+
+A range that is empty, or past the end of every track, is an error.
+
+**/
+
+use std::cmp::Ordering;
+use std::io::{Read, Seek, Write};
+
+use atom::{Trak, Elst, ELST_Entry};
+use progressive::{self, Track};
+use sample_table::SampleInfo;
+use timeline::rescale;
+use {Mp4File, Mp4Error};
+
+pub fn trim<R: Read + Seek, W: Write>(mp4: &mut Mp4File<R>, w: &mut W, start: f64, end: f64) -> Result<(), Mp4Error> {
+    if let Some(moof) = mp4.moofs().first() {
+        return Err(moof.header().invalid_value("file is fragmented"));
+    }
+    let moov = progressive::source_moov(mp4)?;
+    if start.partial_cmp(&end) != Some(Ordering::Less) {
+        return Err(moov.header().invalid_value("trim range is empty"));
+    }
+    let movie_timescale = moov.mvhd().map(|mvhd| mvhd.timescale()).unwrap_or(1);
+    let mut tracks = Vec::new();
+    for trak in moov.traks() {
+        tracks.push(trim_track(trak, movie_timescale, start, end)?);
+    }
+    if tracks.iter().all(|track| track.samples.is_empty()) {
+        return Err(moov.header().invalid_value("trim range is past the end of the media"));
+    }
+    progressive::write(mp4, w, &moov, &tracks)
+}
+
+fn trim_track(trak: &Trak, movie_timescale: u32, start: f64, end: f64) -> Result<Track, Mp4Error> {
+    let timeline = trak.timeline(movie_timescale)?;
+    let table = trak.sample_table()?;
+    let timescale = timeline.media_timescale();
+    let empty = Track {
+        samples: Vec::new(),
+        elst   : None,
+        media_duration: 0,
+        track_duration: 0
+    };
+
+    // Presentation times, in the media timescale.
+    let media_end = table.iter().last().map_or(0, |sample| sample.dts + sample.duration as u64);
+    let track_end = timeline.duration()
+        .or_else(|| timeline.media_to_presentation(media_end as i64))
+        .unwrap_or(0);
+    let to_time = |seconds: f64| (seconds.max(0.0) * timescale as f64).round() as u64;
+    let (start, end) = (to_time(start), to_time(end).min(track_end));
+    if start >= end {
+        return Ok(empty);
+    }
+
+    // Inside an empty edit, nothing is shown until the next edit.
+    let (lead, media_start) = match timeline.presentation_to_media(start) {
+        Some(media_time) => (0, media_time),
+        None => match timeline.segments().iter()
+            .filter(|segment| segment.presentation_start > start)
+            .filter_map(|segment| segment.media_time.map(|media_time| (segment.presentation_start - start, media_time)))
+            .next() {
+            Some(edit) => edit,
+            None => return Ok(empty)
+        }
+    };
+    if start + lead >= end {
+        return Ok(empty);
+    }
+    let media_stop = timeline.presentation_to_media(end).unwrap_or(i64::MAX);
+
+    let first = match table.seek(media_start) {
+        Some(sample) => sample,
+        None => return Ok(empty)
+    };
+    let samples: Vec<SampleInfo> = table.iter()
+        .skip(first.number as usize - 1)
+        .take_while(|sample| (sample.dts as i64) < media_stop)
+        .collect();
+    if samples.is_empty() {
+        return Ok(empty);
+    }
+
+    // The kept samples decode from 0.
+    let lead = rescale(lead, timescale, movie_timescale);
+    let duration = rescale(end - start, timescale, movie_timescale);
+    let mut entries = Vec::new();
+    if lead > 0 {
+        entries.push(ELST_Entry::new(lead, -1));
+    }
+    entries.push(ELST_Entry::new(duration - lead, media_start - first.dts as i64));
+
+    Ok(Track {
+        media_duration: samples.iter().map(|sample| sample.duration as u64).sum(),
+        track_duration: duration,
+        samples: samples.into_iter().map(|sample| (0, sample)).collect(),
+        elst   : Some(Elst::new(entries))
+    })
+}
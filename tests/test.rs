@@ -1,6 +1,1739 @@
+extern crate mp4;
+
 #[cfg(test)]
 mod tests {
+    use std::env;
+    use std::fs::File;
+    use std::io::{Cursor, Write};
+    use std::path::PathBuf;
+
+    use mp4::{Mp4File, Mp4Error};
+    use mp4::concatenator;
+    use mp4::extractor::TrackSelector;
+    use mp4::atom::Kind;
+    use mp4::sample_table::SampleInfo;
+    use mp4::fragment::FragmentSample;
+
+    fn write_temp(name: &str, bytes: &[u8]) -> PathBuf {
+        let path = env::temp_dir().join(name);
+        let mut file = File::create(&path).unwrap();
+        file.write_all(bytes).unwrap();
+        path
+    }
+
+    fn boxed(kind: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let size = (body.len() + 8) as u32;
+        let mut bytes = vec![(size >> 24) as u8, (size >> 16) as u8, (size >> 8) as u8, size as u8];
+        bytes.extend_from_slice(kind);
+        bytes.extend_from_slice(body);
+        bytes
+    }
+
+    fn full_boxed(kind: &[u8; 4], version: u8, flags: u32, body: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![version, (flags >> 16) as u8, (flags >> 8) as u8, flags as u8];
+        bytes.extend_from_slice(body);
+        boxed(kind, &bytes)
+    }
+
+    fn be32(values: &[u32]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for value in values {
+            bytes.extend_from_slice(&[(value >> 24) as u8, (value >> 16) as u8, (value >> 8) as u8, *value as u8]);
+        }
+        bytes
+    }
+
+    // One track of five samples sized 10..50, stored in chunks of 2, 2 and 1 samples.
+    fn sample_table_track() -> Vec<u8> {
+        sample_table_track_at(&[100, 200, 300])
+    }
+
+    fn sample_table_track_at(chunk_offsets: &[u32; 3]) -> Vec<u8> {
+        let mut stbl = Vec::new();
+        stbl.extend(full_boxed(b"stsz", 0, 0, &be32(&[0, 5, 10, 20, 30, 40, 50])));
+        stbl.extend(full_boxed(b"stsc", 0, 0, &be32(&[2, 1, 2, 1, 3, 1, 1])));
+        let mut stco = vec![3];
+        stco.extend_from_slice(chunk_offsets);
+        stbl.extend(full_boxed(b"stco", 0, 0, &be32(&stco)));
+        stbl.extend(full_boxed(b"stts", 0, 0, &be32(&[1, 5, 1000])));
+        stbl.extend(full_boxed(b"ctts", 0, 0, &be32(&[2, 1, 2000, 4, 0])));
+        stbl.extend(full_boxed(b"stss", 0, 0, &be32(&[2, 1, 4])));
+        let minf = boxed(b"minf", &boxed(b"stbl", &stbl));
+        let trak = boxed(b"trak", &boxed(b"mdia", &minf));
+        boxed(b"moov", &trak)
+    }
+
+    fn parse_bytes(bytes: &[u8]) -> Result<Mp4File<Cursor<Vec<u8>>>, Mp4Error> {
+        let mut mp4 = Mp4File::from_reader(Cursor::new(bytes.to_vec()))?;
+        mp4.parse()?;
+        Ok(mp4)
+    }
+
     #[test]
     fn it_works() {
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn truncated_box_is_an_error() {
+        // ftyp claims 20 bytes but the file ends after the major brand.
+        let bytes = [
+            0, 0, 0, 20, b'f', b't', b'y', b'p',
+            b'i', b's', b'o', b'm'
+        ];
+        let path = write_temp("mp4_truncated_box.mp4", &bytes);
+        let mut mp4 = Mp4File::new(path.to_str().unwrap()).unwrap();
+        match mp4.parse() {
+            Err(Mp4Error::Truncated { offset, .. }) => assert_eq!(offset, 0),
+            other => panic!("unexpected result: {:?}", other)
+        }
+    }
+
+    #[test]
+    fn table_entry_count_past_the_box_is_an_error() {
+        // Each table claims far more entries than its box holds.
+        let tables = [
+            full_boxed(b"stco", 0, 0, &be32(&[0x4000_0000, 1])),
+            full_boxed(b"co64", 0, 0, &be32(&[0x4000_0000, 0, 1])),
+            full_boxed(b"stsc", 0, 0, &be32(&[0x4000_0000, 1, 1, 1])),
+            full_boxed(b"stts", 0, 0, &be32(&[0x4000_0000, 1, 1])),
+            full_boxed(b"ctts", 0, 0, &be32(&[0x4000_0000, 1, 1])),
+            full_boxed(b"stsz", 0, 0, &be32(&[0, 0x4000_0000, 1])),
+            full_boxed(b"stz2", 0, 0, &be32(&[16, 0x4000_0000, 1]))
+        ];
+        for table in &tables {
+            let bytes = boxed(b"moov", &boxed(b"trak", &boxed(b"mdia", &boxed(b"minf", &boxed(b"stbl", table)))));
+            match parse_bytes(&bytes) {
+                Err(Mp4Error::InvalidSize { .. }) => {},
+                other => panic!("unexpected result: {:?}", other.map(|_| ()))
+            }
+        }
+
+        // Track runs, with and without per-sample fields.
+        for trun in &[full_boxed(b"trun", 0, 0, &be32(&[0xFFFF_FFFF])),
+                      full_boxed(b"trun", 0, 0x000200, &be32(&[0x4000_0000, 1]))] {
+            let bytes = boxed(b"moof", &boxed(b"traf", trun));
+            match parse_bytes(&bytes) {
+                Err(Mp4Error::InvalidSize { .. }) => {},
+                other => panic!("unexpected result: {:?}", other.map(|_| ()))
+            }
+        }
+    }
+
+    #[test]
+    fn largesize_past_u64_is_an_error() {
+        let mut bytes = boxed(b"free", &[]);
+        bytes.extend_from_slice(&[0, 0, 0, 1, b'f', b'r', b'e', b'e']);
+        bytes.extend_from_slice(&[0xFF; 8]);
+        match parse_bytes(&bytes) {
+            Err(Mp4Error::InvalidSize { offset, .. }) => assert_eq!(offset, 8),
+            other => panic!("unexpected result: {:?}", other.map(|_| ()))
+        }
+    }
+
+    #[test]
+    fn missing_file_is_an_error() {
+        match Mp4File::new("/nonexistent/mp4_missing.mp4") {
+            Err(Mp4Error::Io(_)) => {},
+            other => panic!("unexpected result: {:?}", other.map(|_| ()))
+        }
+    }
+
+    #[test]
+    fn container_stops_at_its_own_end() {
+        let mut bytes = boxed(b"moov", &boxed(b"free", &[0; 4]));
+        bytes.extend(boxed(b"mdat", &[1, 2, 3]));
+        let mp4 = parse_bytes(&bytes).unwrap();
+
+        let atoms = mp4.atoms();
+        assert_eq!(atoms.len(), 2);
+        assert_eq!(*atoms[0].kind(), Kind::moov);
+        assert_eq!(*atoms[1].kind(), Kind::mdat);
+        let children = atoms[0].children().unwrap();
+        assert_eq!(children.len(), 1);
+        assert_eq!(*children[0].kind(), Kind::free);
+        assert_eq!(atoms[1].header().offset(), 20);
+    }
+
+    #[test]
+    fn child_overrun_is_an_error() {
+        // moov declares 16 bytes, but its child claims 16 bytes on its own.
+        let mut bytes = vec![0, 0, 0, 16, b'm', b'o', b'o', b'v'];
+        bytes.extend(vec![0, 0, 0, 16, b'f', b'r', b'e', b'e']);
+        bytes.extend(vec![0; 8]);
+        match parse_bytes(&bytes) {
+            Err(Mp4Error::Overrun { offset, limit, .. }) => {
+                assert_eq!(offset, 8);
+                assert_eq!(limit, 16);
+            },
+            other => panic!("unexpected result: {:?}", other.map(|_| ()))
+        }
+    }
+
+    #[test]
+    fn child_underrun_is_an_error() {
+        let bytes = boxed(b"moov", &[0; 4]);
+        match parse_bytes(&bytes) {
+            Err(Mp4Error::Underrun { kind, offset, remaining }) => {
+                assert_eq!(kind, Some(Kind::moov));
+                assert_eq!(offset, 8);
+                assert_eq!(remaining, 4);
+            },
+            other => panic!("unexpected result: {:?}", other.map(|_| ()))
+        }
+    }
+
+    #[test]
+    fn file_and_memory_sources_agree() {
+        let mut bytes = boxed(b"free", &[0; 4]);
+        bytes.extend(boxed(b"mdat", &[1, 2, 3]));
+        let path = write_temp("mp4_file_source.mp4", &bytes);
+        let mut from_file = Mp4File::new(path.to_str().unwrap()).unwrap();
+        from_file.parse().unwrap();
+        let from_memory = parse_bytes(&bytes).unwrap();
+
+        assert_eq!(from_file.file_size(), from_memory.file_size());
+        let kinds = |atoms: &Vec<mp4::atom::Atom>| atoms.iter().map(|a| a.kind().clone()).collect::<Vec<Kind>>();
+        assert_eq!(kinds(from_file.atoms()), kinds(from_memory.atoms()));
+    }
+
+    #[test]
+    fn size_zero_box_extends_to_end_of_file() {
+        let mut bytes = boxed(b"free", &[0; 4]);
+        bytes.extend(vec![0, 0, 0, 0, b'm', b'd', b'a', b't', 1, 2, 3, 4, 5]);
+        let mp4 = parse_bytes(&bytes).unwrap();
+
+        let mdat = mp4.atoms()[1].header();
+        assert!(mdat.extends_to_eof());
+        assert_eq!(mdat.atom_size(), 13);
+        assert_eq!(mdat.data_size(), 5);
+        assert!(!mp4.is_finalized());
+
+        let finalized = parse_bytes(&boxed(b"mdat", &[1, 2, 3])).unwrap();
+        assert!(finalized.is_finalized());
+    }
+
+    #[test]
+    fn sample_table_resolves_samples() {
+        let mp4 = parse_bytes(&sample_table_track()).unwrap();
+        let trak = mp4.moov().unwrap().traks()[0];
+        let table = trak.sample_table().unwrap();
+
+        assert_eq!(table.sample_count(), 5);
+        assert_eq!(table.chunk_count(), 3);
+        assert_eq!(table.sample(4), Some(SampleInfo {
+            number: 4, offset: 230, size: 40, dts: 3000, cts: 3000,
+            duration: 1000, is_sync: true, description_index: 1
+        }));
+        assert_eq!(table.sample(6), None);
+
+        let samples: Vec<SampleInfo> = table.iter().collect();
+        let offsets: Vec<u64> = samples.iter().map(|s| s.offset).collect();
+        assert_eq!(offsets, vec![100, 110, 200, 230, 300]);
+        let cts: Vec<i64> = samples.iter().map(|s| s.cts).collect();
+        assert_eq!(cts, vec![2000, 1000, 2000, 3000, 4000]);
+        let sync: Vec<bool> = samples.iter().map(|s| s.is_sync).collect();
+        assert_eq!(sync, vec![true, false, false, true, false]);
+        for sample in &samples {
+            assert_eq!(Some(sample.clone()), table.sample(sample.number));
+        }
+    }
+
+    #[test]
+    fn read_samples_from_several_mdats() {
+        // Chunks 1 and 2 live in the first mdat, chunk 3 in the second one.
+        let payload = |number: u8, size: usize| vec![number; size];
+        let mut first = payload(1, 10);
+        first.extend(payload(2, 20));
+        first.extend(payload(3, 30));
+        first.extend(payload(4, 40));
+        let mut bytes = boxed(b"mdat", &first);
+        bytes.extend(boxed(b"mdat", &payload(5, 50)));
+        bytes.extend(sample_table_track_at(&[8, 38, 116]));
+        let mut mp4 = parse_bytes(&bytes).unwrap();
+
+        assert_eq!(mp4.mdats().len(), 2);
+        let table = mp4.moov().unwrap().traks()[0].sample_table().unwrap();
+        for sample in table.iter() {
+            let data = mp4.read_sample(&sample).unwrap();
+            assert_eq!(data, payload(sample.number as u8, sample.size as usize));
+        }
+
+        // A range crossing the second mdat header is not media data.
+        assert!(mp4.read_range(100, 20).is_err());
+        assert!(mp4.read_range(16, u64::MAX).is_err());
+
+        let mdat = mp4.mdats()[1].clone();
+        let mut buf = Vec::new();
+        assert_eq!(mdat.read_to_end(&mut mp4, &mut buf).unwrap(), 50);
+        assert_eq!(buf, payload(5, 50));
+    }
+
+    #[test]
+    fn tfhd_and_trun_fields_follow_flags() {
+        use mp4::atom::{Atom, TfFlags, TrFlags};
+
+        // base-data-offset, sample-description-index, default duration/size/flags, default-base-is-moof
+        let mut tfhd = be32(&[1, 0, 0x1000, 2, 512, 100, 0x0101_0000]);
+        tfhd = full_boxed(b"tfhd", 0, 0x02_003B, &tfhd);
+        // data-offset, first-sample-flags, per-sample size, flags and signed composition offset
+        let trun = full_boxed(b"trun", 1, 0x000E05, &be32(&[
+            2, 120, 0x0200_0000,
+            300, 0x0101_0000, (-512i32) as u32,
+            400, 0x0101_0000, 512
+        ]));
+        let mut traf = tfhd;
+        traf.extend(trun);
+        let mut moof = full_boxed(b"mfhd", 0, 0, &be32(&[7]));
+        moof.extend(boxed(b"traf", &traf));
+        let mp4 = parse_bytes(&boxed(b"moof", &moof)).unwrap();
+
+        let moof = match mp4.atoms()[0] {
+            Atom::moof(ref moof) => moof,
+            ref other => panic!("unexpected atom: {:?}", other)
+        };
+        assert_eq!(moof.mfhd().unwrap().sequence_number(), 7);
+        let traf = moof.trafs()[0];
+
+        let tfhd = traf.tfhd().unwrap();
+        assert!(tfhd.tf_flags().contains(TfFlags::BASE_DATA_OFFSET_PRESENT));
+        assert!(tfhd.default_base_is_moof());
+        assert!(!tfhd.duration_is_empty());
+        assert_eq!(tfhd.track_id(), 1);
+        assert_eq!(tfhd.base_data_offset(), Some(0x1000));
+        assert_eq!(tfhd.defaults().description_index(), Some(2));
+        assert_eq!(tfhd.defaults().duration(), Some(512));
+        assert_eq!(tfhd.defaults().size(), Some(100));
+        assert_eq!(tfhd.defaults().flags(), Some(0x0101_0000));
+
+        let trun = traf.truns()[0];
+        assert!(trun.tr_flags().contains(TrFlags::SAMPLE_SIZE_PRESENT));
+        assert!(!trun.tr_flags().contains(TrFlags::SAMPLE_DURATION_PRESENT));
+        assert_eq!(trun.sample_count(), 2);
+        assert_eq!(trun.data_offset(), Some(120));
+        assert_eq!(trun.first_sample_flags(), Some(0x0200_0000));
+        let samples = trun.samples();
+        assert_eq!(samples[0].duration(), None);
+        assert_eq!(samples[0].size(), Some(300));
+        assert_eq!(samples[0].flags(), Some(0x0101_0000));
+        assert_eq!(samples[0].composition_time_offset(), Some(-512));
+        assert_eq!(samples[1].size(), Some(400));
+        assert_eq!(samples[1].composition_time_offset(), Some(512));
+    }
+
+    #[test]
+    fn fragment_samples_apply_defaults_and_offsets() {
+        let trex = |track_id: u32, size: u32| full_boxed(b"trex", 0, 0, &be32(&[track_id, 1, 1000, size, 0x0001_0000]));
+        let mut mvex = trex(1, 10);
+        mvex.extend(trex(2, 30));
+        let moov = boxed(b"moov", &boxed(b"mvex", &mvex));
+
+        // First fragment: track 1 with an explicit tfdt and data_offset,
+        // track 2 continues after the data of track 1.
+        let moof_one = |data_offset: u32| {
+            let mut traf1 = full_boxed(b"tfhd", 0, 0, &be32(&[1]));
+            traf1.extend(full_boxed(b"tfdt", 1, 0, &be32(&[0, 5000])));
+            traf1.extend(full_boxed(b"trun", 0, 0x000205, &be32(&[2, data_offset, 0, 10, 20])));
+            let mut traf2 = full_boxed(b"tfhd", 0, 0x000008, &be32(&[2, 512]));
+            traf2.extend(full_boxed(b"trun", 0, 0, &be32(&[1])));
+            let mut moof = full_boxed(b"mfhd", 0, 0, &be32(&[1]));
+            moof.extend(boxed(b"traf", &traf1));
+            moof.extend(boxed(b"traf", &traf2));
+            boxed(b"moof", &moof)
+        };
+        // Second fragment: track 1 only, relative to its own moof, no tfdt.
+        let moof_two = |data_offset: u32| {
+            let mut traf = full_boxed(b"tfhd", 0, 0x020000, &be32(&[1]));
+            traf.extend(full_boxed(b"trun", 0, 0x000001, &be32(&[1, data_offset])));
+            let mut moof = full_boxed(b"mfhd", 0, 0, &be32(&[2]));
+            moof.extend(boxed(b"traf", &traf));
+            boxed(b"moof", &moof)
+        };
+
+        let mut bytes = moov.clone();
+        let first = moof_one(0).len() as u32;
+        let first_at = bytes.len() as u64;
+        bytes.extend(moof_one(first + 8));
+        bytes.extend(boxed(b"mdat", &[0; 60]));
+        let second = moof_two(0).len() as u32;
+        let second_at = bytes.len() as u64;
+        bytes.extend(moof_two(second + 8));
+        bytes.extend(boxed(b"mdat", &[0; 10]));
+        let mp4 = parse_bytes(&bytes).unwrap();
+
+        let samples: Vec<FragmentSample> = mp4.fragment_samples().map(|s| s.unwrap()).collect();
+        let fields: Vec<(u32, u32, u64, u32, u64, u32, bool)> = samples.iter()
+            .map(|s| (s.track_id, s.number, s.offset, s.size, s.dts, s.duration, s.is_sync))
+            .collect();
+        let data_one = first_at + first as u64 + 8;
+        let data_two = second_at + second as u64 + 8;
+        assert_eq!(fields, vec![
+            (1, 1, data_one, 10, 5000, 1000, true),
+            (1, 2, data_one + 10, 20, 6000, 1000, false),
+            (2, 1, data_one + 30, 30, 0, 512, false),
+            (1, 3, data_two, 10, 7000, 1000, false)
+        ]);
+        assert_eq!(samples[3].sequence_number, 2);
+
+        // Without trex the sample size is unknown.
+        let mut bytes = moof_one(0);
+        bytes.extend(boxed(b"mdat", &[0; 60]));
+        let mp4 = parse_bytes(&bytes).unwrap();
+        match mp4.fragment_samples().next() {
+            Some(Err(Mp4Error::InvalidValue { kind, .. })) => assert_eq!(kind, Kind::trun),
+            other => panic!("unexpected result: {:?}", other)
+        }
+
+        // Offsets before the start of the file and decode times past u64 are errors.
+        let mut tfdt_max = full_boxed(b"tfdt", 1, 0, &be32(&[0xFFFF_FFFF, 0xFFFF_FFFF]));
+        tfdt_max.extend(full_boxed(b"trun", 0, 0x000100, &be32(&[2, 1, 1])));
+        for traf in &[full_boxed(b"trun", 0, 0x000001, &be32(&[1, 0x8000_0000])), tfdt_max] {
+            let mut traf_payload = full_boxed(b"tfhd", 0, 0x020000, &be32(&[1]));
+            traf_payload.extend_from_slice(traf);
+            let mut bytes = moov.clone();
+            bytes.extend(boxed(b"moof", &boxed(b"traf", &traf_payload)));
+            bytes.extend(boxed(b"mdat", &[0; 10]));
+            let mp4 = parse_bytes(&bytes).unwrap();
+            let results: Vec<Result<FragmentSample, Mp4Error>> = mp4.fragment_samples().collect();
+            match results.as_slice() {
+                [Err(Mp4Error::InvalidValue { kind, .. })] => assert_eq!(*kind, Kind::trun),
+                other => panic!("unexpected result: {:?}", other)
+            }
+        }
+    }
+
+    #[test]
+    fn parsed_atoms_write_back_byte_for_byte() {
+        let mut ftyp = b"M4A ".to_vec();
+        ftyp.extend(be32(&[0x200]));
+        ftyp.extend_from_slice(b"isomdash");
+
+        let mut mvhd = be32(&[1, 2, 1000, 5000, 0x0001_0000]);
+        mvhd.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        mvhd.extend(be32(&[0x0001_0000, 0, 0, 0, 0x0001_0000, 0, 0, 0, 0x4000_0000]));
+        mvhd.extend(be32(&[0, 0, 7, 0, 0, 0, 3]));
+
+        let mut mdhd = be32(&[0, 0, 0, 1, 48000, 0, 96000]);
+        mdhd.extend_from_slice(&[0x55, 0xC4, 0, 0]);
+        let mut hdlr = be32(&[0]);
+        hdlr.extend_from_slice(b"soun");
+        hdlr.extend(be32(&[0, 0, 0]));
+        hdlr.extend_from_slice(b"SoundHandler\0");
+
+        let mut stz2 = vec![0, 0, 0, 4];
+        stz2.extend(be32(&[3]));
+        stz2.extend_from_slice(&[0x12, 0x30]);
+
+        let mut stbl = full_boxed(b"stsd", 0, 0, &be32(&[1, 16, 0x6d703461, 0, 1]));
+        stbl.extend(full_boxed(b"stts", 0, 0, &be32(&[1, 3, 1024])));
+        stbl.extend(full_boxed(b"ctts", 0, 0, &be32(&[1, 3, (-1i32) as u32])));
+        stbl.extend(full_boxed(b"stsc", 0, 0, &be32(&[1, 1, 3, 1])));
+        stbl.extend(full_boxed(b"stz2", 0, 0, &stz2));
+        stbl.extend(full_boxed(b"co64", 0, 0, &be32(&[1, 0, 0x1000])));
+        let mut minf = full_boxed(b"smhd", 0, 0, &[0xFF, 0x80, 0, 0]);
+        minf.extend(boxed(b"dinf", &full_boxed(b"dref", 0, 0, &be32(&[0]))));
+        minf.extend(boxed(b"stbl", &stbl));
+        let mut mdia = full_boxed(b"mdhd", 1, 0, &mdhd);
+        mdia.extend(full_boxed(b"hdlr", 0, 0, &hdlr));
+        mdia.extend(boxed(b"minf", &minf));
+        let mut trak = full_boxed(b"tkhd", 0, 7, &[9; 80]);
+        trak.extend(boxed(b"edts", &full_boxed(b"elst", 0, 0, &be32(&[1, 3072, 0, 0x0001_0000]))));
+        trak.extend(boxed(b"mdia", &mdia));
+        let mut moov = full_boxed(b"mvhd", 0, 0, &mvhd);
+        moov.extend(boxed(b"trak", &trak));
+        moov.extend(boxed(b"udta", &boxed(b"name", b"track")));
+
+        let mut bytes = boxed(b"ftyp", &ftyp);
+        bytes.extend(boxed(b"free", &[0xAB; 5]));
+        bytes.extend(boxed(b"moov", &moov));
+        let mut uuid = vec![0x11; 16];
+        uuid.extend_from_slice(b"payload");
+        bytes.extend(boxed(b"uuid", &uuid));
+        // mdat in the 64-bit largesize form
+        bytes.extend(be32(&[1]));
+        bytes.extend_from_slice(b"mdat");
+        bytes.extend(be32(&[0, 16 + 6]));
+        bytes.extend_from_slice(&[1, 2, 3, 4, 5, 6]);
+
+        let mut mp4 = parse_bytes(&bytes).unwrap();
+        let mut out = Vec::new();
+        mp4.write_to(&mut out).unwrap();
+        assert_eq!(out, bytes);
+
+        // The top level free box keeps its payload in the source, like mdat.
+        let free_at = boxed(b"ftyp", &ftyp).len() as u64;
+        assert_eq!(mp4.atoms()[1].source_range(), Some((free_at + 8, 5)));
+        assert!(mp4.atoms()[1].write(&mut Vec::new()).is_err());
+
+        // Single atoms serialize on their own as well.
+        let moov = &mp4.atoms()[2];
+        let mut out = Vec::new();
+        moov.write(&mut out).unwrap();
+        assert_eq!(out, boxed(b"moov", &moov_bytes(&bytes)));
+    }
+
+    #[test]
+    fn fragmented_atoms_write_back_byte_for_byte() {
+        // Reserved fields and the mdhd pad bit are kept as found.
+        let minf_of = |media_header: Vec<u8>| {
+            let mut minf = media_header;
+            minf.extend(boxed(b"dinf", &full_boxed(b"dref", 0, 0, &be32(&[0]))));
+            minf.extend(boxed(b"stbl", &full_boxed(b"stsd", 0, 0, &be32(&[0]))));
+            boxed(b"minf", &minf)
+        };
+        let trak_of = |media_header: Vec<u8>| {
+            let mut mdia = full_boxed(b"mdhd", 0, 0, &be32(&[0, 0, 1000, 0, 0x95C4_0000]));
+            mdia.extend(minf_of(media_header));
+            boxed(b"trak", &boxed(b"mdia", &mdia))
+        };
+        let mut moov = trak_of(full_boxed(b"smhd", 0, 0, &[0, 0, 0x12, 0x34]));
+        moov.extend(trak_of(full_boxed(b"hmhd", 0, 0, &be32(&[0x0100_0080, 64000, 32000, 0xDEAD_BEEF]))));
+        moov.extend(boxed(b"mvex", &full_boxed(b"trex", 0, 0, &be32(&[1, 1, 1000, 10, 0]))));
+
+        let moof = |data_offset: u32| {
+            let mut traf = full_boxed(b"tfhd", 0, 0x020000, &be32(&[1]));
+            traf.extend(full_boxed(b"tfdt", 1, 0, &be32(&[0, 5000])));
+            traf.extend(full_boxed(b"trun", 0, 0x000001, &be32(&[2, data_offset])));
+            let mut moof = full_boxed(b"mfhd", 0, 0, &be32(&[1]));
+            moof.extend(boxed(b"traf", &traf));
+            boxed(b"moof", &moof)
+        };
+
+        let mut bytes = boxed(b"moov", &moov);
+        bytes.extend(moof(moof(0).len() as u32 + 8));
+        bytes.extend(boxed(b"mdat", &[7; 20]));
+
+        let mut mp4 = parse_bytes(&bytes).unwrap();
+        assert_eq!(mp4.fragment_samples().count(), 2);
+        let mut out = Vec::new();
+        mp4.write_to(&mut out).unwrap();
+        assert_eq!(out, bytes);
+    }
+
+    fn moov_bytes(file: &[u8]) -> Vec<u8> {
+        let start = file.windows(4).position(|w| w == b"moov").unwrap() - 4;
+        let size = ((file[start] as usize) << 24) | ((file[start + 1] as usize) << 16)
+                 | ((file[start + 2] as usize) << 8) | file[start + 3] as usize;
+        file[start + 8..start + size].to_vec()
+    }
+
+    #[test]
+    fn new_headers_are_sized_when_written() {
+        use mp4::atom::Header;
+
+        let mut out = Vec::new();
+        Header::new_full(Kind::stss, 0, 0).write(&mut out, &be32(&[1, 1])).unwrap();
+        assert_eq!(out, full_boxed(b"stss", 0, 0, &be32(&[1, 1])));
+
+        let mut out = Vec::new();
+        Header::new(Kind::free).write(&mut out, &[]).unwrap();
+        assert_eq!(out, boxed(b"free", &[]));
+    }
+
+    #[test]
+    fn faststart_moves_moov_and_shifts_offsets() {
+        let payload = |number: u8, size: usize| vec![number; size];
+        let mut media = Vec::new();
+        for (number, size) in [(1u8, 10usize), (2, 20), (3, 30), (4, 40), (5, 50)].iter() {
+            media.extend(payload(*number, *size));
+        }
+        let mut bytes = boxed(b"ftyp", b"isom\0\0\0\0");
+        bytes.extend(boxed(b"mdat", &media));
+        // chunk offsets: ftyp (16) + mdat header (8), then after 30 and 100 bytes of media
+        bytes.extend(sample_table_track_at(&[24, 54, 124]));
+        let mut mp4 = parse_bytes(&bytes).unwrap();
+
+        let mut out = Vec::new();
+        mp4.faststart(&mut out).unwrap();
+        assert_eq!(out.len(), bytes.len());
+
+        let mut moved = parse_bytes(&out).unwrap();
+        let kinds: Vec<Kind> = moved.atoms().iter().map(|a| a.kind().clone()).collect();
+        assert_eq!(kinds, vec![Kind::ftyp, Kind::moov, Kind::mdat]);
+        let table = moved.moov().unwrap().traks()[0].sample_table().unwrap();
+        for sample in table.iter() {
+            assert_eq!(moved.read_sample(&sample).unwrap(), payload(sample.number as u8, sample.size as usize));
+        }
+
+        // Already in faststart order: written unchanged.
+        let mut again = Vec::new();
+        moved.faststart(&mut again).unwrap();
+        assert_eq!(again, out);
+    }
+
+    fn visual_entry(format: &[u8; 4], width: u16, height: u16, children: &[u8]) -> Vec<u8> {
+        let mut body = vec![0, 0, 0, 0, 0, 0, 0, 1];
+        body.extend(vec![0; 16]);
+        body.extend_from_slice(&[(width >> 8) as u8, width as u8, (height >> 8) as u8, height as u8]);
+        body.extend(be32(&[0x0048_0000, 0x0048_0000, 0]));
+        body.extend_from_slice(&[0, 1]);
+        let mut compressorname = vec![0; 32];
+        compressorname[0] = 8;
+        compressorname[1..9].copy_from_slice(b"Lavc x26");
+        body.extend(compressorname);
+        body.extend_from_slice(&[0, 0x18, 0xff, 0xff]);
+        body.extend_from_slice(children);
+        boxed(format, &body)
+    }
+
+    // `fields` starts at the sound description version.
+    fn audio_entry(format: &[u8; 4], fields: &[u8], children: &[u8]) -> Vec<u8> {
+        let mut body = vec![0, 0, 0, 0, 0, 0, 0, 1];
+        body.extend_from_slice(fields);
+        body.extend_from_slice(children);
+        boxed(format, &body)
+    }
+
+    #[test]
+    fn stsd_entries_are_typed_and_written_back() {
+        use mp4::atom::{SampleEntry, QuickTimeSound};
+
+        let mut children = boxed(b"avcC", &[1, 0x64, 0, 0x1f, 0xff, 0xe0]);
+        children.extend(boxed(b"pasp", &be32(&[1, 1])));
+        children.extend(vec![0; 4]);
+        let avc1 = visual_entry(b"avc1", 1920, 1080, &children);
+
+        // ISO entry: 2 channels, 16 bits, 48000 Hz.
+        let mut fields = be32(&[0, 0, 0x0002_0010, 0, 48000 << 16]);
+        let mp4a = audio_entry(b"mp4a", &fields, &full_boxed(b"esds", 0, 0, &[3, 0x19, 0, 1, 0]));
+
+        // QuickTime v1: 1 channel, 16 bits, 22050 Hz, plus 4 packet fields.
+        fields = be32(&[0x0001_0000, 0, 0x0001_0010, 0xfffe_0000, 22050 << 16, 1, 2, 2, 2]);
+        let sowt = audio_entry(b"sowt", &fields, &[]);
+
+        // QuickTime v2: 6 channels, 24 bits, 96000 Hz.
+        fields = be32(&[0x0002_0000, 0, 0x0003_0010, 0xfffe_0000, 0x0001_0000, 72]);
+        fields.extend_from_slice(&96000f64.to_bits().to_be_bytes());
+        fields.extend(be32(&[6, 0x7f00_0000, 24, 0x0c, 18, 1]));
+        let lpcm = audio_entry(b"lpcm", &fields, &[]);
+
+        let tx3g = boxed(b"tx3g", &[0, 0, 0, 0, 0, 0, 0, 1, 9, 9]);
+
+        let mut body = be32(&[5]);
+        for entry in &[&avc1, &mp4a, &sowt, &lpcm, &tx3g] {
+            body.extend_from_slice(entry);
+        }
+        let stsd = full_boxed(b"stsd", 0, 0, &body);
+        let minf = boxed(b"minf", &boxed(b"stbl", &stsd));
+        let bytes = boxed(b"moov", &boxed(b"trak", &boxed(b"mdia", &minf)));
+
+        let mut mp4 = parse_bytes(&bytes).unwrap();
+        {
+            let stsd = mp4.moov().unwrap().traks()[0].stbl().unwrap().stsd().unwrap();
+            let entries = stsd.entries();
+            assert_eq!(entries.len(), 5);
+            assert!(stsd.entry(0).is_none());
+            assert_eq!(stsd.entry(1).unwrap().format(), "avc1");
+
+            let video = entries[0].visual().unwrap();
+            assert_eq!((video.width(), video.height()), (1920, 1080));
+            assert_eq!(video.horizresolution(), 72.0);
+            assert_eq!(video.frame_count(), 1);
+            assert_eq!(video.compressorname(), "Lavc x26");
+            assert_eq!(video.depth(), 0x18);
+            assert_eq!(video.data_reference_index(), 1);
+            let kinds: Vec<String> = entries[0].children().iter().map(|a| a.kind().to_string()).collect();
+            assert_eq!(kinds, vec!["avcC", "pasp"]);
+            // The avcC is cut before numOfPictureParameterSets.
+            let avcc = entries[0].visual().unwrap().avcc().unwrap();
+            assert_eq!(avcc.data(), &[1, 0x64, 0, 0x1f, 0xff, 0xe0]);
+            assert!(avcc.config().is_err());
+
+            let aac = entries[1].audio().unwrap();
+            assert_eq!((aac.channel_count(), aac.sample_size(), aac.sample_rate()), (2, 16, 48000.0));
+            assert!(aac.quicktime().is_none());
+            assert_eq!(entries[1].children()[0].kind().to_string(), "esds");
+            // The ES_Descriptor claims more bytes than the esds holds.
+            assert!(aac.esds().unwrap().es_descriptor().is_err());
+
+            let pcm = entries[2].audio().unwrap();
+            assert_eq!((pcm.version(), pcm.channel_count(), pcm.sample_rate()), (1, 1, 22050.0));
+            match pcm.quicktime() {
+                Some(&QuickTimeSound::V1 { bytes_per_frame, .. }) => assert_eq!(bytes_per_frame, 2),
+                other => panic!("unexpected {:?}", other)
+            }
+
+            let lpcm = entries[3].audio().unwrap();
+            assert_eq!((lpcm.version(), lpcm.channel_count(), lpcm.sample_size()), (2, 6, 24));
+            assert_eq!(lpcm.sample_rate(), 96000.0);
+
+            match entries[4] {
+                SampleEntry::Unknown(ref entry) => assert_eq!(entry.data(), &[0, 0, 0, 0, 0, 0, 0, 1, 9, 9]),
+                ref other => panic!("unexpected {:?}", other)
+            }
+            assert_eq!(entries[4].data_reference_index(), 1);
+        }
+
+        let mut out = Vec::new();
+        mp4.write_to(&mut out).unwrap();
+        assert_eq!(out, bytes);
+    }
+
+    fn hex(text: &str) -> Vec<u8> {
+        let digits: Vec<u8> = text.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+        digits.chunks(2).map(|pair| u8::from_str_radix(::std::str::from_utf8(pair).unwrap(), 16).unwrap()).collect()
+    }
+
+    #[test]
+    fn avcc_decodes_sps_and_pps() {
+        use mp4::codec::h264::{Sps, FrameCrop};
+
+        // x264, High 4.0, 1920x1088 coded and cropped to 1080, 30 fps.
+        let sps = hex("67640028 acd94078 0227e584 00000300 04000003 00f03c60 c658");
+        let pps = hex("68ebe3cb 22c0");
+        let mut record = vec![1, 0x64, 0, 0x28, 0xff, 0xe1, 0, sps.len() as u8];
+        record.extend_from_slice(&sps);
+        record.extend_from_slice(&[1, 0, pps.len() as u8]);
+        record.extend_from_slice(&pps);
+        record.extend_from_slice(&[0xfd, 0xf8, 0xf8, 0]);
+        let avc1 = visual_entry(b"avc1", 1920, 1080, &boxed(b"avcC", &record));
+        let mut body = be32(&[1]);
+        body.extend(avc1);
+        let minf = boxed(b"minf", &boxed(b"stbl", &full_boxed(b"stsd", 0, 0, &body)));
+        let bytes = boxed(b"moov", &boxed(b"trak", &boxed(b"mdia", &minf)));
+
+        let mut mp4 = parse_bytes(&bytes).unwrap();
+        {
+            let stsd = mp4.moov().unwrap().traks()[0].stbl().unwrap().stsd().unwrap();
+            let avcc = stsd.entry(1).unwrap().visual().unwrap().avcc().unwrap();
+            let config = avcc.config().unwrap();
+            assert_eq!((config.profile_indication, config.level_indication), (100, 40));
+            assert_eq!(config.nal_length_size(), 4);
+            assert_eq!(config.sequence_parameter_sets[0], sps);
+            let ext = config.ext.unwrap();
+            assert_eq!((ext.chroma_format, ext.bit_depth_luma, ext.bit_depth_chroma), (1, 8, 8));
+
+            let sps = avcc.sps().unwrap();
+            assert_eq!((sps.profile_idc, sps.level(), sps.chroma_format_idc), (100, 40, 1));
+            assert_eq!((sps.bit_depth_luma, sps.bit_depth_chroma), (8, 8));
+            assert_eq!((sps.coded_width(), sps.coded_height()), (1920, 1088));
+            assert_eq!((sps.width(), sps.height()), (1920, 1080));
+            let vui = sps.vui.as_ref().unwrap();
+            assert_eq!(vui.sample_aspect_ratio, None);
+            assert_eq!(vui.timing.unwrap().time_scale, 60);
+            assert_eq!(sps.frame_rate(), Some(30.0));
+
+            let pps = avcc.pps().unwrap();
+            assert_eq!((pps.pic_parameter_set_id, pps.seq_parameter_set_id), (0, 0));
+            assert!(pps.entropy_coding_mode);
+        }
+
+        let mut out = Vec::new();
+        mp4.write_to(&mut out).unwrap();
+        assert_eq!(out, bytes);
+
+        // x264, High 3.1, 1280x720, square pixels, 25 fps.
+        let sps = Sps::parse(&hex("6764001f acd94050 05bb0110 00000300 10000003 0320f183 1960")).unwrap();
+        assert_eq!((sps.width(), sps.height(), sps.level()), (1280, 720, 31));
+        assert_eq!(sps.vui.as_ref().unwrap().sample_aspect_ratio, Some((1, 1)));
+
+        // Sizes past the u32 range saturate.
+        let mut sps = sps;
+        sps.pic_width_in_mbs = u32::MAX;
+        sps.pic_height_in_map_units = u32::MAX / 4;
+        sps.frame_mbs_only = false;
+        sps.frame_crop = Some(FrameCrop { left: u32::MAX, right: u32::MAX, top: 0, bottom: 1 });
+        assert_eq!((sps.coded_width(), sps.coded_height()), (u32::MAX, u32::MAX));
+        assert_eq!((sps.width(), sps.height()), (0, u32::MAX - 4));
+
+        match Sps::parse(&pps) {
+            Err(Mp4Error::InvalidBitstream(_)) => {},
+            other => panic!("unexpected {:?}", other)
+        }
+        // A scaling list with delta_scale = i32::MAX.
+        match Sps::parse(&hex("67640028 ad800000 00ffffff fe80")) {
+            Err(Mp4Error::InvalidBitstream(_)) => {},
+            other => panic!("unexpected {:?}", other)
+        }
+        // Cut after level_idc.
+        match Sps::parse(&[0x67, 0x64, 0x00, 0x28]) {
+            Err(Mp4Error::InvalidBitstream(_)) => {},
+            other => panic!("unexpected {:?}", other)
+        }
+    }
+
+    #[test]
+    fn hvcc_decodes_vps_and_sps() {
+        use mp4::codec::h265::{Vps, Sps, ConformanceWindow};
+
+        // x265, Main, level 3.1, 1920x1080 4:2:0 8 bit.
+        let vps = hex("40010c01 ffff0160 00000300 90000003 00000300 5d959809");
+        let sps = hex("42010101 60000003 00900000 03000003 005da003 c08010e5 96566924 cae01000 00030010 00000301 e080");
+        let pps = hex("4401c172 b46240");
+        let mut record = hex("01 01 60000000 900000000000 5d f000 fc fd f8 f8 0000 0f 03");
+        for &(nal_unit_type, nal) in &[(32u8, &vps), (33, &sps), (34, &pps)] {
+            record.extend_from_slice(&[0x80 | nal_unit_type, 0, 1, 0, nal.len() as u8]);
+            record.extend_from_slice(nal);
+        }
+        let hvc1 = visual_entry(b"hvc1", 1920, 1080, &boxed(b"hvcC", &record));
+        let mut body = be32(&[1]);
+        body.extend(hvc1);
+        let minf = boxed(b"minf", &boxed(b"stbl", &full_boxed(b"stsd", 0, 0, &body)));
+        let bytes = boxed(b"moov", &boxed(b"trak", &boxed(b"mdia", &minf)));
+
+        let mut mp4 = parse_bytes(&bytes).unwrap();
+        {
+            let stsd = mp4.moov().unwrap().traks()[0].stbl().unwrap().stsd().unwrap();
+            let hvcc = stsd.entry(1).unwrap().visual().unwrap().hvcc().unwrap();
+            assert_eq!((hvcc.general_profile_space(), hvcc.general_tier_flag()), (0, false));
+            assert_eq!((hvcc.general_profile_idc(), hvcc.general_level_idc()), (1, 93));
+            assert_eq!(hvcc.general_profile_compatibility_flags(), 0x6000_0000);
+            assert_eq!(hvcc.general_constraint_indicator_flags(), 0x9000_0000_0000);
+            assert_eq!((hvcc.chroma_format(), hvcc.bit_depth_luma(), hvcc.bit_depth_chroma()), (1, 8, 8));
+            assert_eq!((hvcc.num_temporal_layers(), hvcc.temporal_id_nested()), (1, true));
+            assert_eq!(hvcc.nal_length_size(), 4);
+            assert_eq!(hvcc.arrays().len(), 3);
+            assert!(hvcc.arrays()[1].array_completeness);
+            assert_eq!(hvcc.nal_units(34), vec![&pps[..]]);
+
+            let vps = hvcc.vps().unwrap();
+            assert_eq!((vps.max_layers, vps.max_sub_layers), (1, 1));
+            assert_eq!(vps.profile_tier_level.level_idc, 93);
+
+            let sps = hvcc.sps().unwrap();
+            assert_eq!(sps.profile_tier_level.profile_idc, 1);
+            assert_eq!((sps.width(), sps.height()), (1920, 1080));
+            assert_eq!((sps.chroma_format_idc, sps.bit_depth_luma, sps.bit_depth_chroma), (1, 8, 8));
+        }
+
+        let mut out = Vec::new();
+        mp4.write_to(&mut out).unwrap();
+        assert_eq!(out, bytes);
+
+        match Sps::parse(&vps) {
+            Err(Mp4Error::InvalidBitstream(_)) => {},
+            other => panic!("unexpected {:?}", other)
+        }
+        assert!(Vps::parse(&vps[..8]).is_err());
+
+        // A conformance window past the u32 range crops the whole picture.
+        let mut sps = Sps::parse(&sps).unwrap();
+        sps.conformance_window = Some(ConformanceWindow { left: u32::MAX, right: 1, top: u32::MAX / 2, bottom: 0 });
+        assert_eq!((sps.width(), sps.height()), (0, 0));
+    }
+
+    #[test]
+    fn av1c_and_vpcc_are_parsed() {
+        // Profile 0, level 3.0 (seq_level_idx 4), 960x540 4:2:0 8 bit.
+        let mut av1c = hex("81 04 0c 00");
+        av1c.extend(hex("0a0b0000 0024cf7f 0dbfff30 08"));
+        let av01 = visual_entry(b"av01", 960, 540, &boxed(b"av1C", &av1c));
+        // Profile 0, level 3.1, 10 bit 4:2:0 co-located, BT.2020 / PQ, limited range.
+        let vp09 = visual_entry(b"vp09", 1280, 720, &full_boxed(b"vpcC", 1, 0, &hex("00 1f a2 09 10 09 0000")));
+        let mut body = be32(&[2]);
+        body.extend(av01);
+        body.extend(vp09);
+        let minf = boxed(b"minf", &boxed(b"stbl", &full_boxed(b"stsd", 0, 0, &body)));
+        let bytes = boxed(b"moov", &boxed(b"trak", &boxed(b"mdia", &minf)));
+
+        let mut mp4 = parse_bytes(&bytes).unwrap();
+        {
+            let stsd = mp4.moov().unwrap().traks()[0].stbl().unwrap().stsd().unwrap();
+            let av1c = stsd.entry(1).unwrap().visual().unwrap().av1c().unwrap();
+            assert_eq!((av1c.seq_profile(), av1c.seq_level_idx_0(), av1c.seq_tier_0()), (0, 4, 0));
+            assert_eq!((av1c.bit_depth(), av1c.monochrome()), (8, false));
+            assert_eq!((av1c.chroma_subsampling_x(), av1c.chroma_subsampling_y()), (true, true));
+            assert_eq!(av1c.initial_presentation_delay(), None);
+
+            let sequence_header = av1c.sequence_header().unwrap();
+            assert_eq!((sequence_header.seq_profile, sequence_header.seq_level_idx), (0, 4));
+            assert_eq!((sequence_header.max_frame_width, sequence_header.max_frame_height), (960, 540));
+            assert_eq!((sequence_header.bit_depth, sequence_header.mono_chrome), (8, false));
+            assert_eq!((sequence_header.subsampling_x, sequence_header.subsampling_y), (true, true));
+            assert!(!sequence_header.color_range);
+
+            let vpcc = stsd.entry(2).unwrap().visual().unwrap().vpcc().unwrap().config().unwrap();
+            assert_eq!((vpcc.profile, vpcc.level, vpcc.bit_depth), (0, 31, 10));
+            assert_eq!((vpcc.chroma_subsampling, vpcc.video_full_range_flag), (1, false));
+            assert_eq!((vpcc.colour_primaries, vpcc.transfer_characteristics, vpcc.matrix_coefficients), (9, 16, 9));
+            assert!(vpcc.codec_initialization_data.is_empty());
+        }
+
+        let mut out = Vec::new();
+        mp4.write_to(&mut out).unwrap();
+        assert_eq!(out, bytes);
+
+        // av1C without configOBUs.
+        let empty = visual_entry(b"av01", 960, 540, &boxed(b"av1C", &hex("81 04 0c 00")));
+        let mut body = be32(&[1]);
+        body.extend(empty);
+        let minf = boxed(b"minf", &boxed(b"stbl", &full_boxed(b"stsd", 0, 0, &body)));
+        let mp4 = parse_bytes(&boxed(b"moov", &boxed(b"trak", &boxed(b"mdia", &minf)))).unwrap();
+        let stsd = mp4.moov().unwrap().traks()[0].stbl().unwrap().stsd().unwrap();
+        match stsd.entry(1).unwrap().visual().unwrap().av1c().unwrap().sequence_header() {
+            Err(Mp4Error::InvalidValue { reason, .. }) => assert_eq!(reason, "av1C has no sequence header OBU"),
+            other => panic!("unexpected {:?}", other)
+        }
+
+        // The pre-standard version 0 layout is kept but not decoded.
+        let vp09 = visual_entry(b"vp09", 1280, 720, &full_boxed(b"vpcC", 0, 0, &hex("00 1f a2 09 10 09 0000")));
+        let mut body = be32(&[1]);
+        body.extend(vp09);
+        let minf = boxed(b"minf", &boxed(b"stbl", &full_boxed(b"stsd", 0, 0, &body)));
+        let bytes = boxed(b"moov", &boxed(b"trak", &boxed(b"mdia", &minf)));
+        let mut mp4 = parse_bytes(&bytes).unwrap();
+        {
+            let entry = mp4.moov().unwrap().traks()[0].stbl().unwrap().stsd().unwrap().entry(1).unwrap();
+            match entry.visual().unwrap().vpcc().unwrap().config() {
+                Err(Mp4Error::UnsupportedVersion { version: 0, .. }) => {},
+                other => panic!("unexpected {:?}", other)
+            }
+            assert!(entry.codec_string().is_err());
+        }
+        let mut out = Vec::new();
+        mp4.write_to(&mut out).unwrap();
+        assert_eq!(out, bytes);
+    }
+
+    const ZEROS: [u8; 4096] = [0; 4096];
+
+    // A large file of zeros with `head` at the start and `tail` at the end.
+    struct Sparse {
+        head: Vec<u8>,
+        tail: Vec<u8>,
+        size: u64,
+        pos : u64
+    }
+
+    impl ::std::io::Read for Sparse {
+        fn read(&mut self, buf: &mut [u8]) -> ::std::io::Result<usize> {
+            let tail_start = self.size - self.tail.len() as u64;
+            let len = if self.pos < self.head.len() as u64 {
+                let len = ::std::cmp::min(buf.len(), self.head.len() - self.pos as usize);
+                buf[..len].copy_from_slice(&self.head[self.pos as usize..self.pos as usize + len]);
+                len
+            } else if self.pos < tail_start {
+                let len = ::std::cmp::min(buf.len() as u64, tail_start - self.pos) as usize;
+                for chunk in buf[..len].chunks_mut(ZEROS.len()) {
+                    chunk.copy_from_slice(&ZEROS[..chunk.len()]);
+                }
+                len
+            } else {
+                let at = (self.pos - tail_start) as usize;
+                let len = ::std::cmp::min(buf.len(), self.tail.len() - at);
+                buf[..len].copy_from_slice(&self.tail[at..at + len]);
+                len
+            };
+            self.pos += len as u64;
+            Ok(len)
+        }
+    }
+
+    impl ::std::io::Seek for Sparse {
+        fn seek(&mut self, pos: ::std::io::SeekFrom) -> ::std::io::Result<u64> {
+            self.pos = match pos {
+                ::std::io::SeekFrom::Start(pos) => pos,
+                ::std::io::SeekFrom::End(delta) => (self.size as i64 + delta) as u64,
+                ::std::io::SeekFrom::Current(delta) => (self.pos as i64 + delta) as u64
+            };
+            Ok(self.pos)
+        }
+    }
+
+    // Keeps the first bytes written and counts the rest.
+    struct Head {
+        bytes: Vec<u8>,
+        total: u64
+    }
+
+    impl Write for Head {
+        fn write(&mut self, buf: &[u8]) -> ::std::io::Result<usize> {
+            let keep = ::std::cmp::min(buf.len(), 4096usize.saturating_sub(self.bytes.len()));
+            self.bytes.extend_from_slice(&buf[..keep]);
+            self.total += buf.len() as u64;
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> ::std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn faststart_upgrades_stco_past_4_gib() {
+        let mdat_size = 0x1_0000_0000u64;
+        let mut head = boxed(b"ftyp", b"isom\0\0\0\0");
+        head.extend(be32(&[1]));
+        head.extend_from_slice(b"mdat");
+        head.extend(be32(&[(mdat_size >> 32) as u32, mdat_size as u32]));
+        // The last chunk starts just below 4 GiB and is pushed past it by moov.
+        let tail = sample_table_track_at(&[32, 1000, 0xFFFF_FFF0]);
+        let size = 16 + mdat_size + tail.len() as u64;
+        let source = Sparse { head, tail: tail.clone(), size, pos: 0 };
+
+        let mut mp4 = Mp4File::from_reader(source).unwrap();
+        mp4.parse().unwrap();
+        let mut out = Head { bytes: Vec::new(), total: 0 };
+        mp4.faststart(&mut out).unwrap();
+
+        // stco (12 + 3 * 4 bytes of payload) became co64 (12 + 3 * 8).
+        assert_eq!(out.total, size + 12);
+        let moov_at = 16usize;
+        let moov_size = tail.len() + 12;
+        let mut layout = out.bytes[..moov_at + moov_size].to_vec();
+        layout.extend(be32(&[1]));
+        layout.extend_from_slice(b"mdat");
+        layout.extend(be32(&[(mdat_size >> 32) as u32, mdat_size as u32]));
+        let mp4 = parse_bytes_with_mdat_size(&layout, mdat_size);
+        let table = mp4.moov().unwrap().traks()[0].sample_table().unwrap();
+        let shift = moov_size as u64;
+        assert_eq!(table.sample(1).unwrap().offset, 32 + shift);
+        assert_eq!(table.sample(5).unwrap().offset, 0xFFFF_FFF0 + shift);
+        assert!(mp4.moov().unwrap().traks()[0].stbl().unwrap().co64().is_some());
+    }
+
+    fn parse_bytes_with_mdat_size(head: &[u8], mdat_size: u64) -> Mp4File<Sparse> {
+        let size = head.len() as u64 + mdat_size;
+        let source = Sparse { head: head.to_vec(), tail: Vec::new(), size, pos: 0 };
+        let mut mp4 = Mp4File::from_reader(source).unwrap();
+        mp4.parse().unwrap();
+        mp4
+    }
+
+    // ES_Descriptor with a DecoderConfigDescriptor and an SLConfigDescriptor.
+    fn esds(object_type_indication: u8, info: &[u8]) -> Vec<u8> {
+        let mut config = vec![object_type_indication, 0x15, 0, 0x18, 0];
+        config.extend(be32(&[160_000, 128_000]));
+        if !info.is_empty() {
+            config.extend_from_slice(&[5, info.len() as u8]);
+            config.extend_from_slice(info);
+        }
+        // ES_ID 1, sizes padded to 4 bytes the way some muxers write them.
+        let mut es = vec![0, 1, 0, 4, 0x80, 0x80, 0x80, config.len() as u8];
+        es.extend(config);
+        es.extend_from_slice(&[6, 1, 2]);
+        let mut body = vec![3, es.len() as u8];
+        body.extend(es);
+        full_boxed(b"esds", 0, 0, &body)
+    }
+
+    #[test]
+    fn esds_distinguishes_aac_profiles_and_mp3() {
+        let fields = be32(&[0, 0, 0x0002_0010, 0, 48000 << 16]);
+        let entries = [
+            // AAC LC, 44100 Hz, stereo.
+            audio_entry(b"mp4a", &fields, &esds(0x40, &[0x12, 0x10])),
+            // HE-AAC with explicit SBR: 24000 Hz core, 48000 Hz output.
+            audio_entry(b"mp4a", &fields, &esds(0x40, &[0x2b, 0x11, 0x88, 0x00])),
+            // HE-AAC v2 signalled backward compatibly: AAC LC mono with SBR and PS sync extensions.
+            audio_entry(b"mp4a", &fields, &esds(0x40, &[0x13, 0x08, 0x56, 0xe5, 0x9d, 0x48, 0x80])),
+            audio_entry(b"mp4a", &fields, &esds(0x6b, &[])),
+        ];
+        let mut body = be32(&[entries.len() as u32]);
+        for entry in &entries {
+            body.extend_from_slice(entry);
+        }
+        let minf = boxed(b"minf", &boxed(b"stbl", &full_boxed(b"stsd", 0, 0, &body)));
+        let bytes = boxed(b"moov", &boxed(b"trak", &boxed(b"mdia", &minf)));
+
+        let mut mp4 = parse_bytes(&bytes).unwrap();
+        {
+            let stsd = mp4.moov().unwrap().traks()[0].stbl().unwrap().stsd().unwrap();
+            let esds: Vec<_> = stsd.entries().iter().map(|entry| entry.audio().unwrap().esds().unwrap()).collect();
+
+            let es = esds[0].es_descriptor().unwrap();
+            assert_eq!((es.es_id, es.depends_on_es_id, es.sl_config_predefined), (1, None, Some(2)));
+            let config = esds[0].decoder_config().unwrap();
+            assert_eq!((config.stream_type, config.buffer_size_db), (5, 0x1800));
+            assert_eq!((config.max_bitrate, config.avg_bitrate), (160_000, 128_000));
+
+            let lc = esds[0].audio_specific_config().unwrap();
+            assert_eq!((lc.audio_object_type, lc.sampling_frequency, lc.channel_count), (2, 44100, 2));
+            assert!(!lc.is_he_aac());
+            assert_eq!(lc.output_sampling_frequency(), 44100);
+
+            let he = esds[1].audio_specific_config().unwrap();
+            assert_eq!((he.audio_object_type, he.extension_audio_object_type), (2, 5));
+            assert!(he.is_he_aac() && !he.is_he_aac_v2());
+            assert_eq!((he.sampling_frequency, he.output_sampling_frequency()), (24000, 48000));
+
+            let v2 = esds[2].audio_specific_config().unwrap();
+            assert!(v2.is_he_aac_v2());
+            assert_eq!((v2.channel_count, v2.output_channel_count()), (1, 2));
+            assert_eq!(v2.output_sampling_frequency(), 48000);
+
+            assert!(esds[0].is_aac() && !esds[0].is_mp3());
+            assert!(esds[3].is_mp3() && !esds[3].is_aac());
+            assert_eq!(esds[3].object_type_indication().unwrap(), 0x6b);
+            assert!(esds[3].audio_specific_config().is_err());
+        }
+
+        let mut out = Vec::new();
+        mp4.write_to(&mut out).unwrap();
+        assert_eq!(out, bytes);
+
+        // An unknown version is kept but not decoded.
+        let mut esds_v1 = esds(0x40, &[0x12, 0x10]);
+        esds_v1[8] = 1;
+        let mut body = be32(&[1]);
+        body.extend(audio_entry(b"mp4a", &fields, &esds_v1));
+        let minf = boxed(b"minf", &boxed(b"stbl", &full_boxed(b"stsd", 0, 0, &body)));
+        let mp4 = parse_bytes(&boxed(b"moov", &boxed(b"trak", &boxed(b"mdia", &minf)))).unwrap();
+        let stsd = mp4.moov().unwrap().traks()[0].stbl().unwrap().stsd().unwrap();
+        match stsd.entry(1).unwrap().audio().unwrap().esds().unwrap().es_descriptor() {
+            Err(Mp4Error::UnsupportedVersion { version: 1, .. }) => {},
+            other => panic!("unexpected {:?}", other)
+        }
+    }
+
+    #[test]
+    fn sample_entries_have_codec_strings() {
+        use mp4::Mp4Error;
+
+        let hvcc = [1, 0x02, 0x20, 0, 0, 0, 0xb0, 0, 0, 0, 0, 0, 123, 0xf0, 0, 0xfc, 0xfd, 0xf8, 0xf8, 0, 0, 0x0f, 0];
+        let fields = be32(&[0, 0, 0x0002_0010, 0, 48000 << 16]);
+        let entries = [
+            visual_entry(b"avc1", 1280, 720, &boxed(b"avcC", &[1, 0x64, 0, 0x1f, 0xff, 0xe0, 0])),
+            visual_entry(b"hvc1", 3840, 2160, &boxed(b"hvcC", &hvcc)),
+            visual_entry(b"av01", 1920, 1080, &boxed(b"av1C", &[0x81, 0x08, 0x4c, 0])),
+            visual_entry(b"vp09", 1920, 1080, &full_boxed(b"vpcC", 1, 0, &[0, 10, 0x82, 1, 1, 1, 0, 0])),
+            audio_entry(b"mp4a", &fields, &esds(0x40, &[0x12, 0x10])),
+            audio_entry(b"mp4a", &fields, &esds(0x40, &[0x2b, 0x11, 0x88, 0x00])),
+            audio_entry(b"mp4a", &fields, &esds(0x6b, &[])),
+            audio_entry(b"ec-3", &fields, &[]),
+            audio_entry(b"Opus", &fields, &[]),
+            visual_entry(b"avc1", 1280, 720, &[]),
+            boxed(b"tx3g", &[0, 0, 0, 0, 0, 0, 0, 1]),
+        ];
+        let mut body = be32(&[entries.len() as u32]);
+        for entry in &entries {
+            body.extend_from_slice(entry);
+        }
+        let minf = boxed(b"minf", &boxed(b"stbl", &full_boxed(b"stsd", 0, 0, &body)));
+        let mp4 = parse_bytes(&boxed(b"moov", &boxed(b"trak", &boxed(b"mdia", &minf)))).unwrap();
+        let stsd = mp4.moov().unwrap().traks()[0].stbl().unwrap().stsd().unwrap();
+        let codecs: Vec<String> = stsd.entries()[..9].iter().map(|entry| entry.codec_string().unwrap()).collect();
+        assert_eq!(codecs, vec![
+            "avc1.64001F", "hvc1.2.4.L123.B0", "av01.0.08M.10", "vp09.00.10.08",
+            "mp4a.40.2", "mp4a.40.5", "mp4a.6B", "ec-3", "opus"
+        ]);
+        match stsd.entries()[9].codec_string() {
+            Err(Mp4Error::InvalidValue { reason, .. }) => assert_eq!(reason, "sample entry has no avcC"),
+            other => panic!("unexpected {:?}", other)
+        }
+        match stsd.entries()[10].codec_string() {
+            Err(error @ Mp4Error::UnsupportedCodec(_)) => {
+                assert_eq!(error.to_string(), "no codec string for sample entry `tx3g`")
+            },
+            other => panic!("unexpected {:?}", other)
+        }
+    }
+
+    #[test]
+    fn edit_lists_map_media_and_presentation_time() {
+        fn trak(timescale: u32, edts: &[u8]) -> Vec<u8> {
+            let mut mdhd = be32(&[0, 0, timescale, 0]);
+            mdhd.extend_from_slice(&[0x55, 0xC4, 0, 0]);
+            let mut trak = edts.to_vec();
+            trak.extend(boxed(b"mdia", &full_boxed(b"mdhd", 0, 0, &mdhd)));
+            boxed(b"trak", &trak)
+        }
+        // 0.5 s of silence, then the media from 1024 on for 2 s (AAC priming).
+        let elst = be32(&[2, 500, (-1i32) as u32, 0x0001_0000, 2000, 1024, 0x0001_0000]);
+        let mut moov = trak(48000, &boxed(b"edts", &full_boxed(b"elst", 0, 0, &elst)));
+        // Version 1: the first frame held for 1 s, then everything from 3000 on.
+        let elst = be32(&[2, 0, 1000, 0, 0, 0, 0, 0, 0, 3000, 0x0001_0000]);
+        moov.extend(trak(90000, &boxed(b"edts", &full_boxed(b"elst", 1, 0, &elst))));
+        moov.extend(trak(600, &[]));
+        let bytes = boxed(b"moov", &moov);
+
+        let mut mp4 = parse_bytes(&bytes).unwrap();
+        {
+            let traks = mp4.moov().unwrap().traks();
+            let entries = traks[0].elst().unwrap().entries();
+            assert_eq!(entries.len(), 2);
+            assert!(entries[0].is_empty());
+            assert_eq!((entries[1].segment_duration(), entries[1].media_time(), entries[1].media_rate()), (2000, 1024, 1.0));
+
+            let timeline = traks[0].timeline(1000).unwrap();
+            assert_eq!(timeline.duration(), Some(120000));
+            assert_eq!(timeline.media_to_presentation(0), None);
+            assert_eq!(timeline.media_to_presentation(1024), Some(24000));
+            assert_eq!(timeline.media_to_presentation(1024 + 95999), Some(24000 + 95999));
+            assert_eq!(timeline.media_to_presentation(1024 + 96000), None);
+            assert_eq!(timeline.presentation_to_media(100), None);
+            assert_eq!(timeline.presentation_to_media(30000), Some(7024));
+            assert_eq!(timeline.presentation_to_media(120000), None);
+
+            let entries = traks[1].elst().unwrap().entries();
+            assert_eq!(traks[1].elst().unwrap().header().version(), Some(1));
+            assert!(entries[0].is_dwell() && !entries[1].is_dwell());
+            let timeline = traks[1].timeline(1000).unwrap();
+            assert_eq!(timeline.duration(), None);
+            assert_eq!(timeline.presentation_to_media(45000), Some(0));
+            assert_eq!(timeline.presentation_to_media(1_000_000), Some(3000 + 910_000));
+            assert_eq!(timeline.media_to_presentation(0), Some(0));
+            assert_eq!(timeline.media_to_presentation(2999), None);
+            assert_eq!(timeline.media_to_presentation(3000), Some(90000));
+
+            let timeline = traks[2].timeline(1000).unwrap();
+            assert!(traks[2].elst().is_none());
+            assert_eq!((timeline.media_to_presentation(5), timeline.presentation_to_media(5)), (Some(5), Some(5)));
+        }
+
+        // Edit durations past u64 saturate instead of overflowing.
+        {
+            use mp4::atom::{Elst, ELST_Entry};
+            use mp4::timeline::Timeline;
+
+            let elst = Elst::new(vec![ELST_Entry::new(u64::MAX, 0), ELST_Entry::new(u64::MAX, 0)]);
+            let timeline = Timeline::new(Some(&elst), 1000, 1000);
+            assert_eq!(timeline.segments().len(), 2);
+            assert_eq!(timeline.presentation_to_media(5), Some(5));
+
+            // So do edits that end past u64 once rescaled to the media timescale.
+            let elst = Elst::new(vec![ELST_Entry::new((1 << 63) - 1, 0), ELST_Entry::new(2, 0)]);
+            let timeline = Timeline::new(Some(&elst), 1, 2);
+            let segment = &timeline.segments()[1];
+            assert_eq!((segment.presentation_start, segment.duration), (u64::MAX - 1, Some(1)));
+            assert_eq!(timeline.duration(), Some(u64::MAX));
+            assert_eq!(timeline.presentation_to_media(u64::MAX - 1), Some(0));
+        }
+
+        let mut out = Vec::new();
+        mp4.write_to(&mut out).unwrap();
+        assert_eq!(out, bytes);
+    }
+
+    #[test]
+    fn seek_returns_preceding_sync_sample() {
+        fn trak(edts: &[u8], ctts_and_stss: &[u8]) -> Vec<u8> {
+            let mut mdhd = be32(&[0, 0, 1000, 0]);
+            mdhd.extend_from_slice(&[0x55, 0xC4, 0, 0]);
+            // Ten 100 byte samples of 1000 ticks in one chunk at 1000.
+            let mut stbl = full_boxed(b"stsz", 0, 0, &be32(&[100, 10]));
+            stbl.extend(full_boxed(b"stsc", 0, 0, &be32(&[1, 1, 10, 1])));
+            stbl.extend(full_boxed(b"stco", 0, 0, &be32(&[1, 1000])));
+            stbl.extend(full_boxed(b"stts", 0, 0, &be32(&[1, 10, 1000])));
+            stbl.extend_from_slice(ctts_and_stss);
+            let mut mdia = full_boxed(b"mdhd", 0, 0, &mdhd);
+            mdia.extend(boxed(b"minf", &boxed(b"stbl", &stbl)));
+            let mut trak = edts.to_vec();
+            trak.extend(boxed(b"mdia", &mdia));
+            boxed(b"trak", &trak)
+        }
+        // Sync samples 1, 5 and 9; sample 5 is presented 2000 ticks after its decoding time.
+        let mut tables = full_boxed(b"ctts", 0, 0, &be32(&[3, 4, 0, 1, 2000, 5, 0]));
+        tables.extend(full_boxed(b"stss", 0, 0, &be32(&[3, 1, 5, 9])));
+        // The track starts one second into the movie.
+        let elst = be32(&[2, 1000, (-1i32) as u32, 0x0001_0000, 0, 0, 0x0001_0000]);
+        let mut moov = trak(&boxed(b"edts", &full_boxed(b"elst", 0, 0, &elst)), &tables);
+        moov.extend(trak(&[], &[]));
+        let mp4 = parse_bytes(&boxed(b"moov", &moov)).unwrap();
+        let traks = mp4.moov().unwrap().traks();
+
+        let table = traks[0].sample_table().unwrap();
+        assert_eq!((table.sample_at_dts(0), table.sample_at_dts(999), table.sample_at_dts(1000)), (Some(1), Some(1), Some(2)));
+        assert_eq!(table.sync_sample_before(8), Some(5));
+        // Sample 5 is decoded before 4500 but only presented at 6000.
+        assert_eq!(table.seek(4500).unwrap().number, 1);
+        let sample = table.seek(6000).unwrap();
+        assert_eq!((sample.number, sample.dts, sample.cts, sample.offset), (5, 4000, 6000, 1400));
+        let sample = table.seek(100_000).unwrap();
+        assert_eq!((sample.number, sample.dts, sample.offset, sample.is_sync), (9, 8000, 1800, true));
+
+        // Presentation seconds go through the edit list.
+        assert_eq!(traks[0].seek(0.5, 1000).unwrap().unwrap().number, 1);
+        assert_eq!(traks[0].seek(7.0, 1000).unwrap().unwrap().number, 5);
+        assert_eq!(traks[0].seek(6.9, 1000).unwrap().unwrap().number, 1);
+
+        // Without stss every sample is a sync sample.
+        let table = traks[1].sample_table().unwrap();
+        assert!(table.sync_samples().is_none());
+        assert_eq!(table.seek(3500).unwrap().number, 4);
+        assert_eq!(traks[1].seek(93.4, 1000).unwrap().unwrap().number, 10);
+    }
+
+    #[test]
+    fn tkhd_exposes_flags_rotation_and_size() {
+        const ONE: u32 = 0x0001_0000;
+        const MINUS_ONE: u32 = (-0x0001_0000i32) as u32;
+        fn tkhd(version: u8, flags: u32, track_id: u32, abcd: [u32; 4]) -> Vec<u8> {
+            let mut body = if version == 1 {
+                be32(&[1, 0, 2, 0, track_id, 0, 0, 5000])
+            } else {
+                be32(&[1, 2, track_id, 0, 5000])
+            };
+            body.extend(be32(&[0, 0, 0xFFFF_0002, 0x0100_0000]));
+            body.extend(be32(&[abcd[0], abcd[1], 0, abcd[2], abcd[3], 0, 0, 0, 0x4000_0000]));
+            body.extend(be32(&[1920 << 16, 1080 << 16 | 0x8000]));
+            boxed(b"trak", &full_boxed(b"tkhd", version, flags, &body))
+        }
+        let mut moov = tkhd(0, 3, 1, [0, ONE, MINUS_ONE, 0]);
+        moov.extend(tkhd(1, 7, 2, [MINUS_ONE, 0, 0, MINUS_ONE]));
+        moov.extend(tkhd(0, 0, 3, [0, MINUS_ONE, ONE, 0]));
+        moov.extend(tkhd(0, 1, 4, [MINUS_ONE, 0, 0, ONE]));
+        moov.extend(tkhd(0, 1, 5, [ONE, 0, 0, ONE]));
+        let bytes = boxed(b"moov", &moov);
+
+        let mut mp4 = parse_bytes(&bytes).unwrap();
+        {
+            let tkhds: Vec<_> = mp4.moov().unwrap().traks().iter().map(|trak| trak.tkhd().unwrap()).collect();
+            let tkhd = tkhds[0];
+            assert_eq!((tkhd.creation_time(), tkhd.modification_time(), tkhd.track_id(), tkhd.duration()), (1, 2, 1, 5000));
+            assert_eq!((tkhd.layer(), tkhd.alternate_group(), tkhd.volume().to_f64()), (-1, 2, 1.0));
+            assert_eq!((tkhd.width().to_f64(), tkhd.height().to_f64()), (1920.0, 1080.5));
+            assert!(tkhd.is_enabled() && tkhd.is_in_movie() && !tkhd.is_in_preview());
+            assert_eq!((tkhd.rotation(), tkhd.is_flipped()), (90, false));
+            assert_eq!(tkhd.display_size(), (1080.5, 1920.0));
+
+            assert_eq!((tkhds[1].creation_time(), tkhds[1].modification_time()), (1 << 32, 2 << 32));
+            assert_eq!((tkhds[1].track_id(), tkhds[1].duration()), (2, 5000));
+            assert!(tkhds[1].is_in_preview());
+            assert_eq!((tkhds[1].rotation(), tkhds[1].is_flipped()), (180, false));
+            assert!(!tkhds[2].is_enabled());
+            assert_eq!((tkhds[2].rotation(), tkhds[2].display_size()), (270, (1080.5, 1920.0)));
+            assert_eq!((tkhds[3].rotation(), tkhds[3].is_flipped()), (0, true));
+            assert_eq!((tkhds[4].rotation(), tkhds[4].is_flipped()), (0, false));
+        }
+
+        let mut out = Vec::new();
+        mp4.write_to(&mut out).unwrap();
+        assert_eq!(out, bytes);
+    }
+
+    #[test]
+    fn fixed_point_values_keep_their_bits() {
+        use mp4::fixed::{Fixed16_16, UFixed16_16, Fixed8_8, Fixed2_30};
+
+        assert_eq!(Fixed2_30::from_bits(0x4000_0000).to_f64(), 1.0);
+        assert_eq!(Fixed2_30::from_bits(-0x2000_0000).to_f64(), -0.5);
+        assert_eq!(Fixed16_16::from_bits(-0x0001_8000).to_f64(), -1.5);
+        assert_eq!(UFixed16_16::from_bits(0xFFFF_FFFF).to_f64(), 65535.0 + 65535.0 / 65536.0);
+        assert_eq!(Fixed8_8::from_bits(0x0180).to_f64(), 1.5);
+        assert_eq!(Fixed16_16::from_f64(-1.5), Fixed16_16::from_bits(-0x0001_8000));
+        assert_eq!(Fixed8_8::from_f64(1000.0), Fixed8_8::from_bits(i16::MAX));
+        assert_eq!(Fixed16_16::ONE.to_bits(), 0x0001_0000);
+        assert_eq!(Fixed2_30::ONE.to_bits(), 0x4000_0000);
+        assert_eq!(Fixed8_8::from_bits(-0x80).to_string(), "-0.5");
+
+        let mut mvhd = be32(&[1, 2, 1000, 5000, 0x0001_8000]);
+        mvhd.extend_from_slice(&[0xFF, 0x40, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        mvhd.extend(be32(&[0xFFFF_0000, 0, 0, 0, 0x0000_8000, 0, 0x0010_0000, 0, 0x2000_0000]));
+        mvhd.extend(be32(&[0, 0, 0, 0, 0, 0, 2]));
+        let mut moov = full_boxed(b"mvhd", 0, 0, &mvhd);
+        let minf = full_boxed(b"smhd", 0, 0, &[0xFF, 0x80, 0, 0]);
+        moov.extend(boxed(b"trak", &boxed(b"mdia", &boxed(b"minf", &minf))));
+        let bytes = boxed(b"moov", &moov);
+
+        let mut mp4 = parse_bytes(&bytes).unwrap();
+        {
+            let moov = mp4.moov().unwrap();
+            let mvhd = moov.mvhd().unwrap();
+            assert_eq!((mvhd.rate().to_f64(), mvhd.volume().to_f64()), (1.5, -0.75));
+            let matrix = mvhd.matrix();
+            assert_eq!((matrix.a().to_f64(), matrix.d().to_f64(), matrix.x().to_f64()), (-1.0, 0.5, 16.0));
+            assert_eq!(matrix.w().to_f64(), 0.5);
+            let minf = moov.traks()[0].mdia().unwrap().minf().unwrap();
+            assert_eq!(minf.smhd().unwrap().balance().to_f64(), -0.5);
+        }
+
+        let mut out = Vec::new();
+        mp4.write_to(&mut out).unwrap();
+        assert_eq!(out, bytes);
+
+        let mut file = Mp4File::from_reader(Cursor::new(vec![0xFF, 0x80, 0x00, 0x01, 0x80, 0x00])).unwrap();
+        assert_eq!(file.read_fixed_point(8, 8).unwrap(), -0.5);
+        assert_eq!(file.read_fixed_point(16, 16).unwrap(), 1.5);
+    }
+
+    // A track with a single sample description; `tables` holds stts, stsc, stsz, stco, ...,
+    // `extra` boxes such as tref follow tkhd.
+    fn progressive_trak(track_id: u32, handler: &[u8; 4], timescale: u32, duration: u32, entry: &[u8], tables: &[u8], extra: &[u8]) -> Vec<u8> {
+        let mut tkhd = be32(&[0, 0, track_id, 0, duration * 1000 / timescale, 0, 0, 0, 0x0100_0000]);
+        tkhd.extend(be32(&[0x0001_0000, 0, 0, 0, 0x0001_0000, 0, 0, 0, 0x4000_0000, 0, 0]));
+        let mut mdhd = be32(&[0, 0, timescale, duration]);
+        mdhd.extend_from_slice(&[0x55, 0xc4, 0, 0]);
+        let mut hdlr = be32(&[0]);
+        hdlr.extend_from_slice(handler);
+        hdlr.extend(be32(&[0, 0, 0]));
+        hdlr.push(0);
+        let mut stbl = full_boxed(b"stsd", 0, 0, &[&be32(&[1])[..], entry].concat());
+        stbl.extend_from_slice(tables);
+        let mut mdia = full_boxed(b"mdhd", 0, 0, &mdhd);
+        mdia.extend(full_boxed(b"hdlr", 0, 0, &hdlr));
+        mdia.extend(boxed(b"minf", &boxed(b"stbl", &stbl)));
+        let mut trak = full_boxed(b"tkhd", 0, 7, &tkhd);
+        trak.extend_from_slice(extra);
+        trak.extend(boxed(b"mdia", &mdia));
+        boxed(b"trak", &trak)
+    }
+
+    // Payload of sample `number` of track `track_id`.
+    fn sample_payload(track_id: u32, number: u32, size: u32) -> Vec<u8> {
+        vec![(track_id * 16 + number) as u8; size as usize]
+    }
+
+    // Three seconds of movie (timescale 1000) with media data after moov:
+    // track 1 video, six samples of 500 / 1000 in one chunk, sync samples 1, 3 and 5, ctts,
+    // a `sync` reference to track 2;
+    // track 2 audio, six samples of 250 / 500 in two chunks of three.
+    fn progressive_file() -> Vec<u8> {
+        progressive_file_with(&[])
+    }
+
+    // `audio_extra` boxes follow the tkhd of the audio track.
+    fn progressive_file_with(audio_extra: &[u8]) -> Vec<u8> {
+        const VIDEO_SIZES: [u32; 6] = [30, 10, 12, 25, 11, 13];
+        const AUDIO_SIZES: [u32; 6] = [6, 7, 6, 7, 6, 7];
+        let build = |media_start: u32| {
+            let video_end = media_start + VIDEO_SIZES.iter().sum::<u32>();
+            let mut video = full_boxed(b"stts", 0, 0, &be32(&[1, 6, 500]));
+            video.extend(full_boxed(b"ctts", 0, 0, &be32(&[2, 1, 1000, 5, 0])));
+            video.extend(full_boxed(b"stss", 0, 0, &be32(&[3, 1, 3, 5])));
+            video.extend(full_boxed(b"stsc", 0, 0, &be32(&[1, 1, 6, 1])));
+            video.extend(full_boxed(b"stsz", 0, 0, &[&be32(&[0, 6])[..], &be32(&VIDEO_SIZES)].concat()));
+            video.extend(full_boxed(b"stco", 0, 0, &be32(&[1, media_start])));
+            let mut audio = full_boxed(b"stts", 0, 0, &be32(&[1, 6, 250]));
+            audio.extend(full_boxed(b"stsc", 0, 0, &be32(&[1, 1, 3, 1])));
+            audio.extend(full_boxed(b"stsz", 0, 0, &[&be32(&[0, 6])[..], &be32(&AUDIO_SIZES)].concat()));
+            audio.extend(full_boxed(b"stco", 0, 0, &be32(&[2, video_end, video_end + 19])));
+
+            let mut mvhd = be32(&[0, 0, 1000, 3000, 0x0001_0000]);
+            mvhd.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+            mvhd.extend(be32(&[0x0001_0000, 0, 0, 0, 0x0001_0000, 0, 0, 0, 0x4000_0000]));
+            mvhd.extend(be32(&[0, 0, 0, 0, 0, 0, 3]));
+            let mut moov = full_boxed(b"mvhd", 0, 0, &mvhd);
+            moov.extend(progressive_trak(1, b"vide", 1000, 3000, &visual_entry(b"avc1", 64, 48, &[]), &video,
+                                          &boxed(b"tref", &boxed(b"sync", &be32(&[2])))));
+            moov.extend(progressive_trak(2, b"soun", 500, 1500, &audio_entry(b"mp4a", &be32(&[0, 0, 0x0002_0010, 0, 500 << 16]), &[]), &audio, audio_extra));
+
+            let mut bytes = boxed(b"ftyp", b"isom\0\0\x02\0isomiso2avc1mp41");
+            bytes.extend(boxed(b"moov", &moov));
+            let mut media = Vec::new();
+            for (number, size) in VIDEO_SIZES.iter().enumerate() {
+                media.extend(sample_payload(1, number as u32 + 1, *size));
+            }
+            for (number, size) in AUDIO_SIZES.iter().enumerate() {
+                media.extend(sample_payload(2, number as u32 + 1, *size));
+            }
+            bytes.extend(boxed(b"mdat", &media));
+            bytes
+        };
+        let media_size = VIDEO_SIZES.iter().chain(AUDIO_SIZES.iter()).sum::<u32>();
+        build(build(0).len() as u32 - media_size)
+    }
+
+    #[test]
+    fn fragmenter_cuts_at_sync_samples() {
+        let source = progressive_file();
+        let mut mp4 = parse_bytes(&source).unwrap();
+        let mut out = Vec::new();
+        mp4.fragment(&mut out, 0.9).unwrap();
+
+        let mut fragmented = parse_bytes(&out).unwrap();
+        let kinds: Vec<Kind> = fragmented.atoms().iter().map(|a| a.kind().clone()).collect();
+        assert_eq!(kinds, vec![Kind::ftyp, Kind::moov, Kind::moof, Kind::mdat, Kind::moof, Kind::mdat, Kind::moof, Kind::mdat]);
+        {
+            let ftyp = fragmented.ftyp().unwrap();
+            assert_eq!(ftyp.major_brand().into_bytes(), b"iso5");
+            let brands: Vec<Vec<u8>> = ftyp.compatible_brands().iter().map(|brand| brand.into_bytes()).collect();
+            assert_eq!(brands, vec![b"iso5".to_vec(), b"avc1".to_vec(), b"mp41".to_vec()]);
+
+            let moov = fragmented.moov().unwrap();
+            assert_eq!(moov.mvhd().unwrap().duration(), 0);
+            let mvex = moov.mvex().unwrap();
+            assert_eq!(mvex.mehd().unwrap().fragment_duration(), 3000);
+            let trex: Vec<u32> = mvex.trexs().iter().map(|trex| trex.track_id()).collect();
+            assert_eq!(trex, vec![1, 2]);
+            for trak in moov.traks() {
+                assert_eq!(trak.sample_table().unwrap().sample_count(), 0);
+                assert!(trak.stbl().unwrap().stsd().is_some());
+                assert_eq!(trak.tkhd().unwrap().duration(), 0);
+            }
+
+            let moofs = fragmented.moofs();
+            let sequence: Vec<u32> = moofs.iter().map(|moof| moof.mfhd().unwrap().sequence_number()).collect();
+            assert_eq!(sequence, vec![1, 2, 3]);
+            let tfdts: Vec<Vec<(u32, u64)>> = moofs.iter().map(|moof| moof.trafs().iter().map(|traf| {
+                (traf.tfhd().unwrap().track_id(), traf.tfdt().unwrap().base_media_decode_time())
+            }).collect()).collect();
+            assert_eq!(tfdts, vec![vec![(1, 0), (2, 0)], vec![(1, 1000), (2, 500)], vec![(1, 2000), (2, 1000)]]);
+        }
+
+        let samples: Vec<_> = fragmented.fragment_samples().map(|sample| sample.unwrap()).collect();
+        assert_eq!(samples.len(), 12);
+        for track_id in 1..3u32 {
+            let table = mp4.moov().unwrap().traks()[track_id as usize - 1].sample_table().unwrap();
+            let fragment: Vec<_> = samples.iter().filter(|sample| sample.track_id == track_id).collect();
+            for (source, sample) in table.iter().zip(&fragment) {
+                assert_eq!((sample.number, sample.dts, sample.cts, sample.duration), (source.number, source.dts, source.cts, source.duration));
+                assert_eq!((sample.size, sample.is_sync, sample.description_index), (source.size, source.is_sync, 1));
+                assert_eq!(fragmented.read_range(sample.offset, sample.size as u64).unwrap(), sample_payload(track_id, source.number, source.size));
+            }
+        }
+    }
+
+    #[test]
+    fn defragmenter_rebuilds_sample_tables() {
+        let source = progressive_file();
+        let mut mp4 = parse_bytes(&source).unwrap();
+        let mut fragmented = Vec::new();
+        mp4.fragment(&mut fragmented, 0.9).unwrap();
+        let mut out = Vec::new();
+        parse_bytes(&fragmented).unwrap().defragment(&mut out).unwrap();
+
+        let mut progressive = parse_bytes(&out).unwrap();
+        let kinds: Vec<Kind> = progressive.atoms().iter().map(|a| a.kind().clone()).collect();
+        assert_eq!(kinds, vec![Kind::ftyp, Kind::moov, Kind::mdat]);
+        assert!(progressive.moofs().is_empty());
+
+        let moov = progressive.moov().unwrap().clone();
+        assert!(moov.mvex().is_none());
+        assert_eq!(moov.mvhd().unwrap().duration(), 3000);
+        for (track_id, trak) in (1..3u32).zip(moov.traks()) {
+            assert_eq!(trak.tkhd().unwrap().duration(), 3000);
+            let source = mp4.moov().unwrap().traks()[track_id as usize - 1].sample_table().unwrap();
+            assert_eq!(trak.mdia().unwrap().mdhd().unwrap().duration(), if track_id == 1 { 3000 } else { 1500 });
+
+            let table = trak.sample_table().unwrap();
+            // One chunk per fragment, interleaved as in the fragments.
+            assert_eq!(table.chunk_count(), 3);
+            assert_eq!(table.sync_samples().is_some(), track_id == 1);
+            assert_eq!(table.sample_count(), source.sample_count());
+            for (expected, sample) in source.iter().zip(table.iter()) {
+                assert_eq!((sample.number, sample.dts, sample.cts, sample.duration), (expected.number, expected.dts, expected.cts, expected.duration));
+                assert_eq!((sample.size, sample.is_sync, sample.description_index), (expected.size, expected.is_sync, 1));
+                assert_eq!(progressive.read_sample(&sample).unwrap(), sample_payload(track_id, expected.number, expected.size));
+            }
+        }
+    }
+
+    #[test]
+    fn progressive_writer_puts_backward_chunks_in_decode_order() {
+        // Six audio samples in two chunks of three, the second chunk stored first,
+        // with segment boxes around moov.
+        const SIZES: [u32; 6] = [6, 7, 6, 7, 6, 7];
+        let build = |media_start: u32| {
+            let mut tables = full_boxed(b"stts", 0, 0, &be32(&[1, 6, 250]));
+            tables.extend(full_boxed(b"stsc", 0, 0, &be32(&[1, 1, 3, 1])));
+            tables.extend(full_boxed(b"stsz", 0, 0, &[&be32(&[0, 6])[..], &be32(&SIZES)].concat()));
+            tables.extend(full_boxed(b"stco", 0, 0, &be32(&[2, media_start + 20, media_start])));
+            let entry = audio_entry(b"mp4a", &be32(&[0, 0, 0x0002_0010, 0, 500 << 16]), &[]);
+
+            let mut bytes = boxed(b"styp", b"msdh\0\0\0\0msdhmsix");
+            bytes.extend(boxed(b"moov", &progressive_trak(1, b"soun", 500, 1500, &entry, &tables, &[])));
+            bytes.extend(full_boxed(b"sidx", 0, 0, &be32(&[1, 500, 0, 0, 0, 39, 1500, 0x9000_0000])));
+            bytes.extend(full_boxed(b"prft", 0, 0, &be32(&[1, 0, 0, 0])));
+            let mut media = Vec::new();
+            for number in (4..7).chain(1..4) {
+                media.extend(sample_payload(1, number, SIZES[number as usize - 1]));
+            }
+            bytes.extend(boxed(b"mdat", &media));
+            bytes
+        };
+        let source = build(build(0).len() as u32 - 39);
+        let mut mp4 = parse_bytes(&source).unwrap();
+        let mut out = Vec::new();
+        mp4.defragment(&mut out).unwrap();
+
+        let mut progressive = parse_bytes(&out).unwrap();
+        let kinds: Vec<String> = progressive.atoms().iter().map(|a| a.kind().to_string()).collect();
+        assert_eq!(kinds, vec!["moov", "mdat"]);
+        let table = progressive.moov().unwrap().traks()[0].sample_table().unwrap();
+        assert_eq!(table.chunk_count(), 2);
+        let samples: Vec<_> = table.iter().collect();
+        for sample in &samples {
+            assert_eq!(progressive.read_sample(sample).unwrap(), sample_payload(1, sample.number, sample.size));
+        }
+        // The chunks keep their place in the file.
+        assert_eq!(samples[3].offset + 20, samples[0].offset);
+    }
+
+    #[test]
+    fn trimmer_cuts_at_the_preceding_sync_sample() {
+        let source = progressive_file();
+        let mut mp4 = parse_bytes(&source).unwrap();
+        let mut out = Vec::new();
+        mp4.trim(&mut out, 1.2, 2.2).unwrap();
+
+        let mut trimmed = parse_bytes(&out).unwrap();
+        let kinds: Vec<Kind> = trimmed.atoms().iter().map(|a| a.kind().clone()).collect();
+        assert_eq!(kinds, vec![Kind::ftyp, Kind::moov, Kind::mdat]);
+        let moov = trimmed.moov().unwrap().clone();
+        assert_eq!(moov.mvhd().unwrap().duration(), 1000);
+
+        // Video restarts at sync sample 3 (1.0 s), audio at sample 3 (1.0 s); both edits skip 0.2 s.
+        let expected = [(1u32, 3u32..6, 1500u64, 200i64), (2, 3..6, 750, 100)];
+        for (trak, &(track_id, ref numbers, media_duration, media_time)) in moov.traks().iter().zip(&expected) {
+            assert_eq!(trak.tkhd().unwrap().duration(), 1000);
+            assert_eq!(trak.mdia().unwrap().mdhd().unwrap().duration(), media_duration);
+            let edits: Vec<(u64, i64)> = trak.elst().unwrap().entries().iter()
+                .map(|entry| (entry.segment_duration(), entry.media_time()))
+                .collect();
+            assert_eq!(edits, vec![(1000, media_time)]);
+
+            let source = mp4.moov().unwrap().traks()[track_id as usize - 1].sample_table().unwrap();
+            let table = trak.sample_table().unwrap();
+            assert_eq!(table.sample_count(), 3);
+            for (number, sample) in numbers.clone().zip(table.iter()) {
+                let expected = source.sample(number).unwrap();
+                assert_eq!(sample.dts, expected.dts - source.sample(numbers.start).unwrap().dts);
+                assert_eq!((sample.size, sample.is_sync), (expected.size, expected.is_sync));
+                assert_eq!(trimmed.read_sample(&sample).unwrap(), sample_payload(track_id, number, expected.size));
+            }
+            assert_eq!(trak.seek(0.0, 1000).unwrap().unwrap().number, 1);
+        }
+        let video = moov.traks()[0].sample_table().unwrap();
+        assert_eq!(video.sync_samples(), Some(&vec![1, 3]));
+
+        // Empty ranges and ranges past the end of the media are refused.
+        for &(start, end) in &[(2.0, 2.0), (2.5, 1.0), (3.0, 4.0), (0.0, f64::NAN)] {
+            match mp4.trim(&mut Vec::new(), start, end) {
+                Err(Mp4Error::InvalidValue { kind: Kind::moov, .. }) => {},
+                other => panic!("unexpected result for {}..{}: {:?}", start, end, other)
+            }
+        }
+    }
+
+    #[test]
+    fn concatenator_joins_files_and_adds_sample_descriptions() {
+        let first = progressive_file();
+        // The same file with a wider video sample description.
+        let mut second = first.clone();
+        let entry_offset = {
+            let mp4 = parse_bytes(&first).unwrap();
+            let moov = mp4.moov().unwrap();
+            moov.traks()[0].stbl().unwrap().stsd().unwrap().entries()[0].header().offset() as usize
+        };
+        // Box header, reserved, data_reference_index, pre_defined and reserved.
+        second[entry_offset + 32..entry_offset + 34].copy_from_slice(&[0, 80]);
+
+        for &(ref other, video_entries) in &[(first.clone(), 1usize), (second, 2)] {
+            let mut sources = vec![parse_bytes(&first).unwrap(), parse_bytes(other).unwrap()];
+            let mut out = Vec::new();
+            concatenator::concatenate(&mut sources, &mut out).unwrap();
+
+            let mut joined = parse_bytes(&out).unwrap();
+            let kinds: Vec<Kind> = joined.atoms().iter().map(|a| a.kind().clone()).collect();
+            assert_eq!(kinds, vec![Kind::ftyp, Kind::moov, Kind::mdat]);
+            let moov = joined.moov().unwrap().clone();
+            assert_eq!(moov.mvhd().unwrap().duration(), 6000);
+            for (track_id, trak) in (1..3u32).zip(moov.traks()) {
+                let stsd = trak.stbl().unwrap().stsd().unwrap();
+                let entries = if track_id == 1 { video_entries } else { 1 };
+                assert_eq!(stsd.entries().len(), entries);
+                assert_eq!(trak.tkhd().unwrap().duration(), 6000);
+
+                let source = sources[0].moov().unwrap().traks()[track_id as usize - 1].sample_table().unwrap();
+                let table = trak.sample_table().unwrap();
+                assert_eq!(table.sample_count(), 12);
+                let file_duration = trak.mdia().unwrap().mdhd().unwrap().duration() / 2;
+                for (idx, sample) in table.iter().enumerate() {
+                    let expected = source.sample(idx as u32 % 6 + 1).unwrap();
+                    let file = idx as u64 / 6;
+                    assert_eq!(sample.dts, expected.dts + file * file_duration);
+                    assert_eq!(sample.cts, expected.cts + (file * file_duration) as i64);
+                    assert_eq!(sample.description_index, if file == 1 { entries as u32 } else { 1 });
+                    assert_eq!(sample.is_sync, expected.is_sync);
+                    assert_eq!(joined.read_sample(&sample).unwrap(), sample_payload(track_id, expected.number, expected.size));
+                }
+            }
+        }
+
+        let mut sources = vec![parse_bytes(&first).unwrap(), parse_bytes(&[0u8, 0, 0, 8, b'f', b'r', b'e', b'e']).unwrap()];
+        assert!(concatenator::concatenate(&mut sources, &mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn concatenator_keeps_priming_of_later_files_hidden() {
+        // The audio edit hides its first sample, the way AAC priming is hidden.
+        let primed = progressive_file_with(&boxed(b"edts", &full_boxed(b"elst", 0, 0, &be32(&[1, 3000, 250, 0x0001_0000]))));
+        let mut sources = vec![parse_bytes(&primed).unwrap(), parse_bytes(&primed).unwrap()];
+        let mut out = Vec::new();
+        concatenator::concatenate(&mut sources, &mut out).unwrap();
+
+        let joined = parse_bytes(&out).unwrap();
+        let moov = joined.moov().unwrap();
+        assert_eq!(moov.mvhd().unwrap().duration(), 6000);
+        let (video, audio) = (moov.traks()[0], moov.traks()[1]);
+        assert!(video.elst().is_none());
+        // The first file shows media 250..1750, its last sample stretched to reach 1750;
+        // the second file starts at 1750 and again skips its first sample.
+        let edits: Vec<(u64, i64)> = audio.elst().unwrap().entries().iter()
+            .map(|entry| (entry.segment_duration(), entry.media_time()))
+            .collect();
+        assert_eq!(edits, vec![(3000, 250), (3000, 2000)]);
+        assert_eq!(audio.tkhd().unwrap().duration(), 6000);
+        let table = audio.sample_table().unwrap();
+        let dts: Vec<u64> = table.iter().map(|sample| sample.dts).collect();
+        assert_eq!(dts, vec![0, 250, 500, 750, 1000, 1250, 1750, 2000, 2250, 2500, 2750, 3000]);
+        let timeline = audio.timeline(1000).unwrap();
+        assert_eq!(timeline.presentation_to_media(1500), Some(1750 + 250));
+        assert_eq!(timeline.media_to_presentation(1750), None);
+
+        // Edits that carry on from one another are merged.
+        let edit = progressive_file_with(&boxed(b"edts", &full_boxed(b"elst", 0, 0, &be32(&[1, 3000, 0, 0x0001_0000]))));
+        let mut sources = vec![parse_bytes(&edit).unwrap(), parse_bytes(&progressive_file()).unwrap()];
+        let mut out = Vec::new();
+        concatenator::concatenate(&mut sources, &mut out).unwrap();
+        let joined = parse_bytes(&out).unwrap();
+        let edits: Vec<(u64, i64)> = joined.moov().unwrap().traks()[1].elst().unwrap().entries().iter()
+            .map(|entry| (entry.segment_duration(), entry.media_time()))
+            .collect();
+        assert_eq!(edits, vec![(6000, 0)]);
+
+        // Edits of a later file are in that file's movie timescale.
+        let mut fine = progressive_file_with(&boxed(b"edts", &full_boxed(b"elst", 0, 0, &be32(&[1, 270000, 0, 0x0001_0000]))));
+        let mvhd = fine.windows(4).position(|w| w == b"mvhd").unwrap();
+        fine[mvhd + 16..mvhd + 24].copy_from_slice(&be32(&[90000, 270000]));
+        let mut sources = vec![parse_bytes(&progressive_file()).unwrap(), parse_bytes(&fine).unwrap()];
+        let mut out = Vec::new();
+        concatenator::concatenate(&mut sources, &mut out).unwrap();
+        let joined = parse_bytes(&out).unwrap();
+        let moov = joined.moov().unwrap();
+        assert_eq!(moov.mvhd().unwrap().duration(), 6000);
+        let edits: Vec<(u64, i64)> = moov.traks()[1].elst().unwrap().entries().iter()
+            .map(|entry| (entry.segment_duration(), entry.media_time()))
+            .collect();
+        assert_eq!(edits, vec![(6000, 0)]);
+    }
+
+    #[test]
+    fn extractor_keeps_selected_tracks_and_fixes_references() {
+        let source = progressive_file();
+        let mut mp4 = parse_bytes(&source).unwrap();
+        let mut out = Vec::new();
+        mp4.write_to(&mut out).unwrap();
+        assert_eq!(out, source);
+        {
+            let tref = mp4.moov().unwrap().traks()[0].tref().unwrap();
+            let references: Vec<(&str, &[u32])> = tref.references().iter()
+                .map(|reference| (reference.reference_type(), reference.track_ids()))
+                .collect();
+            assert_eq!(references, vec![("sync", &[2u32][..])]);
+        }
+
+        let audio = [TrackSelector::Handler("soun".to_string())];
+        let audio_id = [TrackSelector::TrackId(2)];
+        // (output, kept track, next_track_id)
+        let mut outputs = Vec::new();
+        let mut out = Vec::new();
+        mp4.extract_tracks(&mut out, &audio).unwrap();
+        outputs.push((out, 2u32, 3u32));
+        let mut out = Vec::new();
+        mp4.remove_tracks(&mut out, &audio_id).unwrap();
+        outputs.push((out, 1, 3));
+
+        for (out, track_id, next_track_id) in outputs {
+            let mut extracted = parse_bytes(&out).unwrap();
+            let moov = extracted.moov().unwrap().clone();
+            assert_eq!(moov.traks().len(), 1);
+            let trak = moov.traks()[0];
+            assert_eq!(trak.tkhd().unwrap().track_id(), track_id);
+            assert!(trak.tref().is_none());
+            let mvhd = moov.mvhd().unwrap();
+            assert_eq!((mvhd.next_track_id(), mvhd.duration()), (next_track_id, 3000));
+
+            let source = mp4.moov().unwrap().traks()[track_id as usize - 1].sample_table().unwrap();
+            let media_size: u64 = source.iter().map(|sample| sample.size as u64).sum();
+            assert_eq!(extracted.mdats()[0].data_size(), media_size);
+            let table = trak.sample_table().unwrap();
+            for (expected, sample) in source.iter().zip(table.iter()) {
+                assert_eq!((sample.dts, sample.cts, sample.is_sync), (expected.dts, expected.cts, expected.is_sync));
+                assert_eq!(extracted.read_sample(&sample).unwrap(), sample_payload(track_id, expected.number, expected.size));
+            }
+        }
+
+        let all = [TrackSelector::Handler("vide".to_string()), TrackSelector::Handler("soun".to_string())];
+        assert!(mp4.remove_tracks(&mut Vec::new(), &all).is_err());
+    }
+}